@@ -0,0 +1,60 @@
+//! Benchmarks for the hot paths: signing app JWTs and building the
+//! `Authorization` header of an installation token.
+//!
+//! Run with `cargo bench --features cassette`. The token is fetched
+//! from a replayed recording, so no network access is needed.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use github_app_auth::cassette::{Cassette, Interaction};
+use github_app_auth::{
+    GithubApp, GithubAuthParams, GithubAuthParamsBuilder,
+    InstallationAccessToken,
+};
+use std::sync::Arc;
+
+const PRIVATE_KEY: &[u8] = include_bytes!("../testdata/private-key.pem");
+
+fn params() -> GithubAuthParams {
+    GithubAuthParams::builder()
+        .user_agent("github-app-auth-bench")
+        .private_key(PRIVATE_KEY)
+        .app_id(1)
+        .installation_id(2)
+        .build()
+}
+
+fn bench_jwt(c: &mut Criterion) {
+    let app = GithubApp::new(params()).unwrap();
+    c.bench_function("jwt", |b| b.iter(|| app.jwt().unwrap()));
+}
+
+fn bench_header(c: &mut Criterion) {
+    let path = std::env::temp_dir()
+        .join(format!("github-app-auth-bench-{}.json", std::process::id()));
+    let interaction = Interaction {
+        method: "POST".into(),
+        url: "https://api.github.com/app/installations/2/access_tokens".into(),
+        status: 201,
+        headers: vec![("content-type".into(), "application/json".into())],
+        body: r#"{"token": "ghs_bench", "expires_at": "2999-01-01T00:00:00Z"}"#
+            .into(),
+    };
+    std::fs::write(&path, serde_json::to_vec(&[interaction]).unwrap()).unwrap();
+    let cassette = Arc::new(Cassette::replay(&path).unwrap());
+    std::fs::remove_file(&path).unwrap();
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let token = runtime
+        .block_on(InstallationAccessToken::new(
+            GithubAuthParamsBuilder::from(params())
+                .hooks(cassette)
+                .build(),
+        ))
+        .unwrap();
+    c.bench_function("header", |b| {
+        b.iter(|| runtime.block_on(token.header()).unwrap())
+    });
+}
+
+criterion_group!(benches, bench_jwt, bench_header);
+criterion_main!(benches);
@@ -0,0 +1,678 @@
+//! Requests authenticated as the app itself, using a JWT signed with
+//! the app's private key, rather than as one of its installations.
+
+use crate::error_response::check_status;
+use crate::pagination;
+use crate::{
+    create_app_jwt, retry, send_as_app, timestamp, Access, AuthError,
+    GithubAuthParams, InstallationAccessToken, GITHUB_JSON,
+};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::time::SystemTime;
+
+/// A user or organization account.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Account {
+    /// Account ID.
+    pub id: u64,
+
+    /// Login name of the user or organization.
+    pub login: String,
+
+    /// Either `"User"` or `"Organization"`.
+    #[serde(rename = "type")]
+    pub account_type: String,
+}
+
+/// Which repositories of its account an installation can access.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum RepositorySelection {
+    /// Every repository, including ones created later.
+    All,
+
+    /// Only the repositories that were selected.
+    Selected,
+}
+
+/// An installation of the app on an account, as returned by
+/// [`GithubApp::installations`] and [`GithubApp::installation`].
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Installation {
+    /// Installation ID, as used in [`GithubAuthParams::installation_id`].
+    pub id: u64,
+
+    /// ID of the app that is installed.
+    pub app_id: u64,
+
+    /// The account the app is installed on.
+    pub account: Account,
+
+    /// Either `"User"` or `"Organization"`.
+    pub target_type: String,
+
+    /// Permissions granted to the installation, mapping permission
+    /// names such as `"contents"` to access levels such as `"read"`.
+    #[serde(default)]
+    pub permissions: BTreeMap<String, String>,
+
+    /// Webhook events the installation is subscribed to.
+    #[serde(default)]
+    pub events: Vec<String>,
+
+    /// Which repositories the installation can access.
+    #[serde(default)]
+    pub repository_selection: Option<RepositorySelection>,
+
+    /// When the installation was suspended, if it is. Suspended
+    /// installations can't get access tokens.
+    #[serde(default, with = "timestamp::option")]
+    pub suspended_at: Option<SystemTime>,
+}
+
+/// Rank of an access level, so that `"write"` also satisfies a
+/// required `"read"`. Unknown levels only satisfy themselves.
+fn access_rank(level: &str) -> Option<Access> {
+    level.parse().ok()
+}
+
+impl Installation {
+    /// Compare the installation's permissions against `required`,
+    /// which maps permission names to the access level needed. Returns
+    /// the required permissions that aren't granted, each as
+    /// `name: level` and the level granted if any.
+    pub fn missing_permissions(
+        &self,
+        required: &BTreeMap<String, String>,
+    ) -> Vec<String> {
+        required
+            .iter()
+            .filter_map(|(name, level)| {
+                let granted = self.permissions.get(name);
+                let satisfied = granted.is_some_and(|granted| {
+                    granted == level
+                        || matches!(
+                            (access_rank(granted), access_rank(level)),
+                            (Some(granted), Some(level)) if granted >= level
+                        )
+                });
+                if satisfied {
+                    return None;
+                }
+                Some(match granted {
+                    Some(granted) => {
+                        format!("{}: {} (granted {})", name, level, granted)
+                    }
+                    None => format!("{}: {} (not granted)", name, level),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Criteria for [`GithubApp::find_installations`]. An installation
+/// matches if it meets every criterion that is set.
+#[derive(Clone, Debug, Default)]
+pub struct InstallationFilter {
+    /// Login names of accounts to include, compared case-insensitively.
+    /// If empty, installations on any account match.
+    pub account_logins: Vec<String>,
+
+    /// Only include installations with this target type, either
+    /// `"User"` or `"Organization"`.
+    pub target_type: Option<String>,
+
+    /// Only include installations that have these permissions, as for
+    /// [`Installation::missing_permissions`].
+    pub permissions: BTreeMap<String, String>,
+}
+
+impl InstallationFilter {
+    /// Whether `installation` matches the filter.
+    pub fn matches(&self, installation: &Installation) -> bool {
+        (self.account_logins.is_empty()
+            || self.account_logins.iter().any(|login| {
+                login.eq_ignore_ascii_case(&installation.account.login)
+            }))
+            && self.target_type.as_ref().is_none_or(|target_type| {
+                *target_type == installation.target_type
+            })
+            && installation
+                .missing_permissions(&self.permissions)
+                .is_empty()
+    }
+}
+
+/// A repository accessible to an installation.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Repository {
+    /// Repository ID.
+    pub id: u64,
+
+    /// Repository name without the owner.
+    pub name: String,
+
+    /// Repository name in `owner/name` form.
+    pub full_name: String,
+
+    /// Whether the repository is private.
+    pub private: bool,
+}
+
+/// Repositories that were added to or removed from an installation, as
+/// found by [`InstallationAccessToken::repository_changes`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RepositoryChanges {
+    /// Repositories the installation can now access.
+    pub added: Vec<Repository>,
+
+    /// Repositories the installation can no longer access.
+    pub removed: Vec<Repository>,
+}
+
+impl RepositoryChanges {
+    /// Compare two lists of repositories by their IDs, so that renamed
+    /// repositories aren't reported.
+    fn between(old: &[Repository], new: &[Repository]) -> RepositoryChanges {
+        let old_ids: HashSet<_> = old.iter().map(|repo| repo.id).collect();
+        let new_ids: HashSet<_> = new.iter().map(|repo| repo.id).collect();
+        RepositoryChanges {
+            added: new
+                .iter()
+                .filter(|repo| !old_ids.contains(&repo.id))
+                .cloned()
+                .collect(),
+            removed: old
+                .iter()
+                .filter(|repo| !new_ids.contains(&repo.id))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Whether no repositories were added or removed.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// A GitHub app, for requests that are authenticated as the app rather
+/// than as an installation.
+pub struct GithubApp {
+    /// The [`reqwest::Client`] used to send requests.
+    pub client: reqwest::Client,
+
+    params: GithubAuthParams,
+}
+
+impl GithubApp {
+    /// Create an app from authentication parameters. The installation
+    /// ID and token scoping fields are not used.
+    pub fn new(params: GithubAuthParams) -> Result<GithubApp, AuthError> {
+        let client = params.build_client()?;
+        Ok(GithubApp { client, params })
+    }
+
+    /// Create a JWT for authenticating as the app. It is valid for one
+    /// minute.
+    pub fn jwt(&self) -> Result<String, AuthError> {
+        create_app_jwt(&self.params, &self.params.signing_keys()[0], 0)
+            .map(|jwt| jwt.token)
+    }
+
+    /// Get the app's configuration with `GET /app`.
+    pub async fn info(&self) -> Result<AppInfo, AuthError> {
+        let url = format!("{}/app", self.params.api_base());
+        let (_, response) = send_as_app(&self.params, |jwt| {
+            self.client
+                .get(&url)
+                .bearer_auth(jwt)
+                .header("Accept", self.params.accept(GITHUB_JSON))
+        })
+        .await;
+        Ok(check_status(&self.params, response?).await?.json().await?)
+    }
+
+    /// Check at startup that the app is subscribed to every webhook
+    /// event in `required`, such as `"push"`. Fails with
+    /// [`AuthError::MissingEvents`] listing the others, so that a
+    /// misconfigured app is noticed before deliveries go missing.
+    pub async fn require_events(
+        &self,
+        required: &[&str],
+    ) -> Result<(), AuthError> {
+        let missing = self.info().await?.missing_events(required);
+        if !missing.is_empty() {
+            return Err(AuthError::MissingEvents(missing.join(", ")));
+        }
+        Ok(())
+    }
+
+    /// List every installation of the app.
+    pub async fn installations(&self) -> Result<Vec<Installation>, AuthError> {
+        self.get_all_pages(&format!(
+            "{}/app/installations",
+            self.params.api_base()
+        ))
+        .await
+    }
+
+    /// Get an installation of the app by its ID.
+    pub async fn installation(
+        &self,
+        installation_id: u64,
+    ) -> Result<Installation, AuthError> {
+        get_installation(&self.client, &self.params, installation_id).await
+    }
+
+    /// List the installations of the app that match `filter`.
+    pub async fn find_installations(
+        &self,
+        filter: &InstallationFilter,
+    ) -> Result<Vec<Installation>, AuthError> {
+        let mut installations = self.installations().await?;
+        installations.retain(|installation| filter.matches(installation));
+        Ok(installations)
+    }
+
+    async fn get_all_pages<T: DeserializeOwned>(
+        &self,
+        url: &str,
+    ) -> Result<Vec<T>, AuthError> {
+        pagination::get_all_pages(url, |url| async move {
+            // JWTs are short-lived, so create one for each page.
+            let (_, response) = send_as_app(&self.params, |jwt| {
+                self.client
+                    .get(&url)
+                    .bearer_auth(jwt)
+                    .header("Accept", self.params.accept(GITHUB_JSON))
+            })
+            .await;
+            check_status(&self.params, response?).await
+        })
+        .await
+    }
+}
+
+/// A GitHub app, as configured in its settings.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct AppInfo {
+    /// App ID, as used in [`GithubAuthParams::app_id`].
+    pub id: u64,
+
+    /// The name of the app in its URL.
+    pub slug: String,
+
+    /// Display name of the app.
+    pub name: String,
+
+    /// Permissions the app requests from installations.
+    #[serde(default)]
+    pub permissions: BTreeMap<String, String>,
+
+    /// Webhook events the app is subscribed to.
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+impl AppInfo {
+    /// The events in `required` that the app isn't subscribed to.
+    pub fn missing_events<'a>(&self, required: &[&'a str]) -> Vec<&'a str> {
+        required
+            .iter()
+            .copied()
+            .filter(|event| !self.events.iter().any(|e| e == event))
+            .collect()
+    }
+}
+
+impl GithubAuthParams {
+    /// If [`app_slug`](Self::app_slug) is set, look up the app's ID
+    /// with `GET /apps/{app_slug}` and set [`app_id`](Self::app_id) to
+    /// it. Call this at startup so that configuration can name the app
+    /// instead of copying its numeric ID. Returns the app ID.
+    pub async fn resolve_app_id(&mut self) -> Result<u64, AuthError> {
+        let Some(slug) = &self.app_slug else {
+            return Ok(self.app_id);
+        };
+        let client = self.build_client()?;
+        let url = format!("{}/apps/{}", self.api_base(), slug);
+        let response = retry::send(
+            &self.hooks,
+            &self.extra_headers,
+            &self.retry_policy,
+            || Ok(client.get(&url).header("Accept", self.accept(GITHUB_JSON))),
+        )
+        .await?;
+        let app: AppInfo = check_status(self, response).await?.json().await?;
+        self.app_id = app.id;
+        Ok(app.id)
+    }
+}
+
+/// Get the installation at `path` under the API base, such as
+/// `/app/installations/1`, with a request authenticated as the app.
+async fn get_installation_at(
+    client: &reqwest::Client,
+    params: &GithubAuthParams,
+    path: &str,
+) -> Result<Installation, AuthError> {
+    let url = format!("{}{}", params.api_base(), path);
+    let (_, response) = send_as_app(params, |jwt| {
+        client
+            .get(&url)
+            .bearer_auth(jwt)
+            .header("Accept", params.accept(GITHUB_JSON))
+    })
+    .await;
+    Ok(check_status(params, response?).await?.json().await?)
+}
+
+/// Get an installation with a request authenticated as the app.
+pub(crate) async fn get_installation(
+    client: &reqwest::Client,
+    params: &GithubAuthParams,
+    installation_id: u64,
+) -> Result<Installation, AuthError> {
+    get_installation_at(
+        client,
+        params,
+        &format!("/app/installations/{}", installation_id),
+    )
+    .await
+}
+
+/// Get the installation of the app on the organization `org`.
+pub(crate) async fn get_org_installation(
+    client: &reqwest::Client,
+    params: &GithubAuthParams,
+    org: &str,
+) -> Result<Installation, AuthError> {
+    get_installation_at(client, params, &format!("/orgs/{}/installation", org))
+        .await
+}
+
+/// Get the installation of the app on the repository `owner/repo`.
+pub(crate) async fn get_repo_installation(
+    client: &reqwest::Client,
+    params: &GithubAuthParams,
+    owner: &str,
+    repo: &str,
+) -> Result<Installation, AuthError> {
+    get_installation_at(
+        client,
+        params,
+        &format!("/repos/{}/{}/installation", owner, repo),
+    )
+    .await
+}
+
+impl InstallationAccessToken {
+    /// Check at startup that the installation has every permission in
+    /// `required`, such as `"contents"` mapped to `"write"`. Fails with
+    /// [`AuthError::MissingPermissions`] listing what's missing, rather
+    /// than leaving requests to fail with `403` later.
+    ///
+    /// This checks the installation, not any
+    /// [`permissions`](GithubAuthParams::permissions) the token is
+    /// restricted to.
+    pub async fn preflight(
+        &self,
+        required: &BTreeMap<String, String>,
+    ) -> Result<(), AuthError> {
+        let installation = get_installation(
+            &self.client,
+            &self.params,
+            self.params.installation_id,
+        )
+        .await?;
+        let missing = installation.missing_permissions(required);
+        if !missing.is_empty() {
+            return Err(AuthError::MissingPermissions(missing.join(", ")));
+        }
+        Ok(())
+    }
+
+    /// List the repositories the installation can access. For a scoped
+    /// token, this is only the repositories it is restricted to.
+    ///
+    /// The list is kept, see
+    /// [`cached_repositories`](Self::cached_repositories).
+    pub async fn repositories(&self) -> Result<Vec<Repository>, AuthError> {
+        let repositories = self.list_repositories().await?;
+        *self.shared.repositories.lock().unwrap() = Some(repositories.clone());
+        Ok(repositories)
+    }
+
+    /// The repositories found the last time they were listed by
+    /// [`repositories`](Self::repositories) or
+    /// [`repository_changes`](Self::repository_changes), without any
+    /// requests. `None` if they haven't been listed yet.
+    pub fn cached_repositories(&self) -> Option<Vec<Repository>> {
+        self.shared.repositories.lock().unwrap().clone()
+    }
+
+    /// List the repositories the installation can access again, and
+    /// compare them with the ones found the last time they were listed.
+    /// Call this on `installation_repositories` webhooks to find out
+    /// which repositories to set up or clean up. If they haven't been
+    /// listed before, every repository is reported as added.
+    pub async fn repository_changes(
+        &self,
+    ) -> Result<RepositoryChanges, AuthError> {
+        let repositories = self.list_repositories().await?;
+        let previous = self
+            .shared
+            .repositories
+            .lock()
+            .unwrap()
+            .replace(repositories.clone());
+        Ok(RepositoryChanges::between(
+            previous.as_deref().unwrap_or_default(),
+            &repositories,
+        ))
+    }
+
+    async fn list_repositories(&self) -> Result<Vec<Repository>, AuthError> {
+        self.get_all_pages(&format!(
+            "{}/installation/repositories",
+            self.params.api_base()
+        ))
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_installation_parse() {
+        let installation: Installation = serde_json::from_str(
+            r#"{
+                "id": 1,
+                "app_id": 2,
+                "account": {"id": 3, "login": "octocat", "type": "User"},
+                "target_type": "User",
+                "repository_selection": "all",
+                "permissions": {"contents": "read"},
+                "events": ["push"]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(installation.account.login, "octocat");
+        assert_eq!(installation.account.account_type, "User");
+        assert_eq!(installation.permissions["contents"], "read");
+        assert_eq!(installation.events, ["push"]);
+        assert_eq!(
+            installation.repository_selection,
+            Some(RepositorySelection::All)
+        );
+        assert_eq!(installation.suspended_at, None);
+        let json = serde_json::to_string(&installation).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Installation>(&json).unwrap(),
+            installation
+        );
+    }
+
+    fn installation() -> Installation {
+        Installation {
+            id: 1,
+            app_id: 2,
+            account: Account {
+                id: 3,
+                login: "octocat".into(),
+                account_type: "User".into(),
+            },
+            target_type: "User".into(),
+            permissions: [
+                ("contents".into(), "write".into()),
+                ("checks".into(), "read".into()),
+            ]
+            .into(),
+            events: Vec::new(),
+            repository_selection: None,
+            suspended_at: None,
+        }
+    }
+
+    #[test]
+    fn test_missing_events() {
+        let app: AppInfo = serde_json::from_str(
+            r#"{
+                "id": 1,
+                "slug": "my-deploy-bot",
+                "name": "My deploy bot",
+                "events": ["push", "pull_request"]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            app.missing_events(&["push", "check_suite", "issues"]),
+            ["check_suite", "issues"]
+        );
+    }
+
+    #[test]
+    fn test_filter() {
+        let installation = installation();
+        assert!(InstallationFilter::default().matches(&installation));
+        let filter = InstallationFilter {
+            account_logins: vec!["OctoCat".into()],
+            target_type: Some("User".into()),
+            permissions: [("contents".into(), "read".into())].into(),
+        };
+        assert!(filter.matches(&installation));
+        let filter = InstallationFilter {
+            target_type: Some("Organization".into()),
+            ..Default::default()
+        };
+        assert!(!filter.matches(&installation));
+        let filter = InstallationFilter {
+            permissions: [("issues".into(), "read".into())].into(),
+            ..Default::default()
+        };
+        assert!(!filter.matches(&installation));
+    }
+
+    #[test]
+    fn test_missing_permissions() {
+        let installation = installation();
+        let required = [
+            ("contents".to_string(), "read".to_string()),
+            ("checks".to_string(), "write".to_string()),
+            ("issues".to_string(), "write".to_string()),
+        ]
+        .into();
+        assert_eq!(
+            installation.missing_permissions(&required),
+            [
+                "checks: write (granted read)",
+                "issues: write (not granted)",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_repository_changes() {
+        use crate::simulation::Simulation;
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        let repo = |id: u64, name: &str| {
+            serde_json::json!({
+                "id": id,
+                "name": name,
+                "full_name": format!("octocat/{}", name),
+                "private": false,
+            })
+        };
+        let simulation = Arc::new(Simulation::new());
+        let token = InstallationAccessToken::lazy(
+            GithubAuthParams::builder()
+                .private_key(
+                    include_bytes!("../testdata/private-key.pem").to_vec(),
+                )
+                .hooks(simulation.clone())
+                .build(),
+        )
+        .unwrap();
+        simulation.respond_token("ghs_test", Duration::from_secs(3600));
+        simulation.respond(
+            200,
+            serde_json::json!({
+                "total_count": 2,
+                "repositories": [repo(1, "hello"), repo(2, "world")],
+            }),
+        );
+        simulation.respond(
+            200,
+            serde_json::json!({
+                "total_count": 2,
+                "repositories": [repo(2, "world-renamed"), repo(3, "new")],
+            }),
+        );
+        assert_eq!(token.repositories().await.unwrap().len(), 2);
+        let changes = token.repository_changes().await.unwrap();
+        let names = |repos: &[Repository]| -> Vec<String> {
+            repos.iter().map(|repo| repo.name.clone()).collect()
+        };
+        assert_eq!(names(&changes.added), ["new"]);
+        assert_eq!(names(&changes.removed), ["hello"]);
+        assert_eq!(token.cached_repositories().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_app_id_retries() {
+        use crate::retry::RetryPolicy;
+        use crate::simulation::Simulation;
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        let simulation = Arc::new(Simulation::new());
+        let mut params = GithubAuthParams::builder()
+            .app_slug("my-deploy-bot")
+            .retry_policy(RetryPolicy {
+                backoff_base: Duration::ZERO,
+                ..RetryPolicy::transient()
+            })
+            .hooks(simulation.clone())
+            .build();
+        simulation.respond_error(502, "Bad Gateway");
+        simulation.respond(
+            200,
+            serde_json::json!({
+                "id": 42,
+                "slug": "my-deploy-bot",
+                "name": "My deploy bot",
+            }),
+        );
+        assert_eq!(params.resolve_app_id().await.unwrap(), 42);
+        assert_eq!(params.app_id, 42);
+        assert_eq!(simulation.remaining(), 0);
+    }
+}
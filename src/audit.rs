@@ -0,0 +1,149 @@
+//! An audit trail of installation access token issuance.
+//!
+//! Attach an [`AuditLog`] to [`GithubAuthParams::audit_log`] to record
+//! every attempt to mint a token with those parameters. Only metadata
+//! is recorded, never the token itself.
+
+use crate::GithubAuthParams;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// A record of one attempt to mint an installation access token.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TokenIssuance {
+    /// When the attempt finished.
+    pub at: SystemTime,
+
+    /// ID of the app.
+    pub app_id: u64,
+
+    /// ID of the installation the token was for.
+    pub installation_id: u64,
+
+    /// Repositories the token was restricted to, if any.
+    pub repositories: Vec<String>,
+
+    /// Permissions the token was restricted to, if any.
+    pub permissions: BTreeMap<String, String>,
+
+    /// The `jti` claim of the app JWT sent to request the token, if one
+    /// could be created.
+    pub jti: Option<String>,
+
+    /// When the token expires if it was minted, or the error message
+    /// if minting failed.
+    pub result: Result<SystemTime, String>,
+}
+
+type Callback = Box<dyn Fn(&TokenIssuance) + Send + Sync>;
+
+/// Keeps the most recent [`TokenIssuance`] records in memory, and
+/// optionally passes each one to a callback as it happens, for example
+/// to write it to a persistent log.
+pub struct AuditLog {
+    capacity: usize,
+    entries: Mutex<VecDeque<TokenIssuance>>,
+    callback: Option<Callback>,
+}
+
+impl AuditLog {
+    /// Create a log that keeps the last `capacity` records.
+    pub fn new(capacity: usize) -> AuditLog {
+        AuditLog {
+            capacity,
+            entries: Mutex::new(VecDeque::new()),
+            callback: None,
+        }
+    }
+
+    /// Also pass each record to `callback`. This is called on the task
+    /// that mints the token, so it should not block.
+    pub fn with_callback(
+        mut self,
+        callback: impl Fn(&TokenIssuance) + Send + Sync + 'static,
+    ) -> AuditLog {
+        self.callback = Some(Box::new(callback));
+        self
+    }
+
+    /// Get the records kept in memory, oldest first.
+    pub fn entries(&self) -> Vec<TokenIssuance> {
+        self.entries.lock().unwrap().iter().cloned().collect()
+    }
+
+    pub(crate) fn record(
+        &self,
+        params: &GithubAuthParams,
+        jti: Option<String>,
+        result: Result<SystemTime, String>,
+    ) {
+        let entry = TokenIssuance {
+            at: SystemTime::now(),
+            app_id: params.app_id,
+            installation_id: params.installation_id,
+            repositories: params.repositories.clone(),
+            permissions: params.permissions.clone(),
+            jti,
+            result,
+        };
+        if let Some(callback) = &self.callback {
+            callback(&entry);
+        }
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() == self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+}
+
+impl fmt::Debug for AuditLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuditLog")
+            .field("capacity", &self.capacity)
+            .field("entries", &self.entries.lock().unwrap().len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_audit_log() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let log = AuditLog::new(2).with_callback({
+            let calls = calls.clone();
+            move |_| {
+                calls.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+        let params = GithubAuthParams {
+            app_id: 1,
+            ..Default::default()
+        };
+        for installation_id in 1..=3 {
+            log.record(
+                &GithubAuthParams {
+                    installation_id,
+                    ..params.clone()
+                },
+                None,
+                Err("failed".into()),
+            );
+        }
+        let entries = log.entries();
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].installation_id, 2);
+        assert_eq!(entries[1].installation_id, 3);
+    }
+}
@@ -0,0 +1,121 @@
+//! Authentication headers that know when they expire.
+
+use crate::AuthError;
+use reqwest::header::HeaderMap;
+use std::fmt;
+use std::time::SystemTime;
+
+/// What [`AuthHeaders::get`] does when the token has expired.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ExpiredUse {
+    /// Return [`AuthError::HeadersExpired`].
+    #[default]
+    Error,
+
+    /// Panic, to find code that keeps headers around in tests.
+    Panic,
+}
+
+/// Headers from [`InstallationAccessToken::auth_headers`], which can
+/// only be used while the token in them is valid.
+///
+/// Installation access tokens expire after an hour, so headers that are
+/// fetched once and reused for longer start failing with
+/// `401 Unauthorized`. [`get`](Self::get) catches this before a request
+/// is sent.
+///
+/// [`InstallationAccessToken::auth_headers`]:
+///     crate::InstallationAccessToken::auth_headers
+#[derive(Clone)]
+pub struct AuthHeaders {
+    headers: HeaderMap,
+    produced_at: SystemTime,
+    expires_at: SystemTime,
+    on_expired: ExpiredUse,
+}
+
+// Written by hand so that the token doesn't end up in logs.
+impl fmt::Debug for AuthHeaders {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuthHeaders")
+            .field("headers", &"<redacted>")
+            .field("produced_at", &self.produced_at)
+            .field("expires_at", &self.expires_at)
+            .field("on_expired", &self.on_expired)
+            .finish()
+    }
+}
+
+impl AuthHeaders {
+    pub(crate) fn new(headers: HeaderMap, expires_at: SystemTime) -> Self {
+        AuthHeaders {
+            headers,
+            produced_at: SystemTime::now(),
+            expires_at,
+            on_expired: ExpiredUse::default(),
+        }
+    }
+
+    /// Set what [`get`](Self::get) does once the token has expired.
+    #[must_use]
+    pub fn on_expired(mut self, on_expired: ExpiredUse) -> Self {
+        self.on_expired = on_expired;
+        self
+    }
+
+    /// Get the headers, or fail as set by
+    /// [`on_expired`](Self::on_expired) if the token has expired.
+    ///
+    /// # Panics
+    ///
+    /// If the token has expired and [`ExpiredUse::Panic`] is set.
+    pub fn get(&self) -> Result<&HeaderMap, AuthError> {
+        if !self.is_expired() {
+            return Ok(&self.headers);
+        }
+        match self.on_expired {
+            ExpiredUse::Error => Err(AuthError::HeadersExpired),
+            ExpiredUse::Panic => panic!(
+                "authentication headers used {}s after they were produced, \
+                 but the token has expired",
+                self.produced_at.elapsed().unwrap_or_default().as_secs()
+            ),
+        }
+    }
+
+    /// When the headers were produced.
+    pub fn produced_at(&self) -> SystemTime {
+        self.produced_at
+    }
+
+    /// When the token in the headers expires.
+    pub fn expires_at(&self) -> SystemTime {
+        self.expires_at
+    }
+
+    /// Whether the token in the headers has expired.
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= SystemTime::now()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::InstallationAccessToken;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_auth_headers() {
+        let token =
+            InstallationAccessToken::for_test("abc", Duration::from_secs(300));
+        let headers = token.auth_headers().await.unwrap();
+        assert_eq!(headers.get().unwrap()["Authorization"], "token abc");
+        assert_eq!(headers.expires_at(), token.expires_at());
+
+        let expired = AuthHeaders::new(HeaderMap::new(), SystemTime::now());
+        assert!(matches!(expired.get(), Err(AuthError::HeadersExpired)));
+        let expired = expired.on_expired(ExpiredUse::Panic);
+        assert!(std::panic::catch_unwind(|| expired.get().is_ok()).is_err());
+    }
+}
@@ -0,0 +1,110 @@
+//! The git credential helper protocol: <https://git-scm.com/docs/git-credential>.
+
+use crate::{BoxError, InstallationArgs};
+use clap::{Args, ValueEnum};
+use github_app_auth::InstallationAccessToken;
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+
+#[derive(Args)]
+pub struct CredentialArgs {
+    #[command(flatten)]
+    installation: InstallationArgs,
+
+    /// Only answer requests for this host; requests for other hosts
+    /// are left to the next credential helper. Hosts other than
+    /// github.com also need `--api-url`.
+    #[arg(long, default_value = "github.com")]
+    host: String,
+
+    /// Operation requested by git.
+    operation: Operation,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Operation {
+    Get,
+    Store,
+    Erase,
+}
+
+/// Parse the `key=value` lines git writes to the helper's stdin.
+fn parse_attributes(input: &str) -> HashMap<&str, &str> {
+    input
+        .lines()
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| line.split_once('='))
+        .collect()
+}
+
+/// Whether a request is one the helper should answer.
+fn wants_credentials(attributes: &HashMap<&str, &str>, host: &str) -> bool {
+    attributes.get("protocol") == Some(&"https")
+        && attributes.get("host") == Some(&host)
+}
+
+/// Make sure tokens for `host` are minted by its own API, rather than
+/// by github.com's.
+fn check_host(host: &str, api_url: Option<&str>) -> Result<(), BoxError> {
+    if host != "github.com" && api_url.is_none() {
+        return Err(format!(
+            "--host {} also needs --api-url, otherwise tokens would be \
+             minted by github.com",
+            host
+        )
+        .into());
+    }
+    Ok(())
+}
+
+pub async fn run(args: CredentialArgs) -> Result<(), BoxError> {
+    check_host(&args.host, args.installation.app.api_url.as_deref())?;
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input)?;
+
+    match args.operation {
+        Operation::Get => {
+            if !wants_credentials(&parse_attributes(&input), &args.host) {
+                return Ok(());
+            }
+            let token =
+                InstallationAccessToken::new(args.installation.params()?)
+                    .await?;
+            let credentials = token.git_credentials().await?;
+            io::stdout().write_all(
+                credentials.to_credential_helper_output().as_bytes(),
+            )?;
+        }
+        // Tokens are minted on demand, so there is nothing to store or
+        // erase.
+        Operation::Store | Operation::Erase => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wants_credentials() {
+        let input = "protocol=https\nhost=github.com\npath=a/b.git\n\n";
+        let attributes = parse_attributes(input);
+        assert_eq!(attributes["path"], "a/b.git");
+        assert!(wants_credentials(&attributes, "github.com"));
+        assert!(!wants_credentials(&attributes, "ghe.example"));
+
+        let attributes = parse_attributes("protocol=http\nhost=github.com\n");
+        assert!(!wants_credentials(&attributes, "github.com"));
+    }
+
+    #[test]
+    fn test_check_host() {
+        assert!(check_host("github.com", None).is_ok());
+        assert!(check_host("ghe.example", None).is_err());
+        assert!(
+            check_host("ghe.example", Some("https://ghe.example/api/v3"))
+                .is_ok()
+        );
+    }
+}
@@ -0,0 +1,60 @@
+use crate::{AppArgs, BoxError, Format, InstallationArgs};
+use clap::Args;
+use github_app_auth::{GithubApp, InstallationAccessToken};
+
+#[derive(Args)]
+pub struct InstallationsArgs {
+    #[command(flatten)]
+    app: AppArgs,
+
+    /// Output format. `plain` prints the ID, account, and account type
+    /// of each installation separated by tabs.
+    #[arg(long, value_enum, default_value_t = Format::Plain)]
+    format: Format,
+}
+
+#[derive(Args)]
+pub struct ReposArgs {
+    #[command(flatten)]
+    installation: InstallationArgs,
+
+    /// Output format. `plain` prints the full name of each repository.
+    #[arg(long, value_enum, default_value_t = Format::Plain)]
+    format: Format,
+}
+
+pub async fn installations(args: InstallationsArgs) -> Result<(), BoxError> {
+    let app = GithubApp::new(args.app.params()?)?;
+    let installations = app.installations().await?;
+    match args.format {
+        Format::Plain => {
+            for installation in installations {
+                println!(
+                    "{}\t{}\t{}",
+                    installation.id,
+                    installation.account.login,
+                    installation.target_type
+                );
+            }
+        }
+        Format::Json => {
+            println!("{}", serde_json::to_string_pretty(&installations)?)
+        }
+    }
+    Ok(())
+}
+
+pub async fn repos(args: ReposArgs) -> Result<(), BoxError> {
+    let token =
+        InstallationAccessToken::new(args.installation.params()?).await?;
+    let repos = token.repositories().await?;
+    match args.format {
+        Format::Plain => {
+            for repo in repos {
+                println!("{}", repo.full_name);
+            }
+        }
+        Format::Json => println!("{}", serde_json::to_string_pretty(&repos)?),
+    }
+    Ok(())
+}
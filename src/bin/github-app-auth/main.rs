@@ -0,0 +1,135 @@
+//! Command line interface for authenticating as a GitHub app.
+//!
+//! Built with the `cli` feature. App credentials can be passed as
+//! options or through the environment:
+//!
+//! - `--app-id` / `GITHUB_APP_ID`
+//! - `--key-file` / `GITHUB_APP_PRIVATE_KEY_FILE`
+//! - `--installation-id` / `GITHUB_APP_INSTALLATION_ID`
+//! - `--api-url` / `GITHUB_API_URL`, for GitHub Enterprise
+
+mod credential;
+mod installations;
+mod token;
+mod verify_webhook;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use github_app_auth::{Endpoints, GithubAuthParams};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+type BoxError = Box<dyn std::error::Error>;
+
+#[derive(Parser)]
+#[command(version, about = "Authenticate as a GitHub app")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Act as a helper for the git credential protocol.
+    ///
+    /// Configure git to use it with
+    /// `git config credential.helper '!github-app-auth credential'`
+    /// and set the GITHUB_APP_* environment variables. Tools that don't
+    /// ask git for credentials, such as gh, don't use it.
+    Credential(credential::CredentialArgs),
+
+    /// Mint an installation access token and print it.
+    Token(token::TokenArgs),
+
+    /// List the app's installations.
+    Installations(installations::InstallationsArgs),
+
+    /// List the repositories an installation can access.
+    Repos(installations::ReposArgs),
+
+    /// Verify the signature of a webhook payload read from stdin.
+    VerifyWebhook(verify_webhook::VerifyWebhookArgs),
+}
+
+/// Output format for commands that print structured data.
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Plain,
+    Json,
+}
+
+/// Options identifying the app.
+#[derive(Args)]
+struct AppArgs {
+    /// GitHub app ID.
+    #[arg(long, env = "GITHUB_APP_ID")]
+    app_id: u64,
+
+    /// Path to the app's PEM-encoded private key.
+    #[arg(long, env = "GITHUB_APP_PRIVATE_KEY_FILE")]
+    key_file: PathBuf,
+
+    /// User agent sent to GitHub.
+    #[arg(long, default_value = concat!("github-app-auth/", env!("CARGO_PKG_VERSION")))]
+    user_agent: String,
+
+    /// Base URL of the REST API, such as `https://ghe.example/api/v3`
+    /// for GitHub Enterprise Server. Defaults to github.com.
+    #[arg(long, env = "GITHUB_API_URL")]
+    api_url: Option<String>,
+}
+
+impl AppArgs {
+    fn params(&self) -> Result<GithubAuthParams, BoxError> {
+        let private_key = std::fs::read(&self.key_file).map_err(|err| {
+            format!("failed to read {}: {}", self.key_file.display(), err)
+        })?;
+        let mut builder = GithubAuthParams::builder()
+            .user_agent(&self.user_agent)
+            .private_key(private_key)
+            .app_id(self.app_id);
+        if let Some(api_url) = &self.api_url {
+            builder = builder.endpoints(Endpoints::from_api_url(api_url));
+        }
+        Ok(builder.build())
+    }
+}
+
+/// Options identifying the app and one of its installations.
+#[derive(Args)]
+struct InstallationArgs {
+    #[command(flatten)]
+    app: AppArgs,
+
+    /// Installation ID.
+    #[arg(long, env = "GITHUB_APP_INSTALLATION_ID")]
+    installation_id: u64,
+}
+
+impl InstallationArgs {
+    fn params(&self) -> Result<GithubAuthParams, BoxError> {
+        let mut params = self.app.params()?;
+        params.installation_id = self.installation_id;
+        Ok(params)
+    }
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Command::Credential(args) => credential::run(args).await,
+        Command::Token(args) => token::run(args).await,
+        Command::Installations(args) => {
+            installations::installations(args).await
+        }
+        Command::Repos(args) => installations::repos(args).await,
+        Command::VerifyWebhook(args) => verify_webhook::run(args),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("github-app-auth: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use crate::{BoxError, Format, InstallationArgs};
+use chrono::{DateTime, SecondsFormat, Utc};
+use clap::Args;
+use github_app_auth::InstallationAccessToken;
+
+#[derive(Args)]
+pub struct TokenArgs {
+    #[command(flatten)]
+    installation: InstallationArgs,
+
+    /// Restrict the token to a repository, given as `name` or
+    /// `owner/name`. Can be repeated.
+    #[arg(long = "repo")]
+    repos: Vec<String>,
+
+    /// Restrict the token to a permission, given as `name=level`, for
+    /// example `contents=read`. Can be repeated.
+    #[arg(long = "permission", value_parser = parse_permission)]
+    permissions: Vec<(String, String)>,
+
+    /// Output format. `plain` prints only the token, `json` prints the
+    /// token and its expiry.
+    #[arg(long, value_enum, default_value_t = Format::Plain)]
+    format: Format,
+}
+
+fn parse_permission(arg: &str) -> Result<(String, String), String> {
+    match arg.split_once('=') {
+        Some((name, level)) if !name.is_empty() && !level.is_empty() => {
+            Ok((name.into(), level.into()))
+        }
+        _ => Err(format!("expected name=level, got {:?}", arg)),
+    }
+}
+
+/// The access token API takes repository names without the owner.
+fn repo_name(repo: &str) -> &str {
+    repo.rsplit_once('/').map_or(repo, |(_, name)| name)
+}
+
+pub async fn run(args: TokenArgs) -> Result<(), BoxError> {
+    let mut params = args.installation.params()?;
+    params.repositories = args
+        .repos
+        .iter()
+        .map(|repo| repo_name(repo).into())
+        .collect();
+    params.permissions = args.permissions.into_iter().collect();
+
+    let token = InstallationAccessToken::new(params).await?;
+    let secret = token.token().await?;
+    match args.format {
+        Format::Plain => println!("{}", secret),
+        Format::Json => {
+            let expires_at = DateTime::<Utc>::from(token.expires_at())
+                .to_rfc3339_opts(SecondsFormat::Secs, true);
+            println!(
+                "{}",
+                serde_json::json!({
+                    "token": secret,
+                    "expires_at": expires_at,
+                })
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(
+            parse_permission("contents=read"),
+            Ok(("contents".into(), "read".into()))
+        );
+        assert!(parse_permission("contents").is_err());
+        assert!(parse_permission("=read").is_err());
+
+        assert_eq!(repo_name("owner/repo"), "repo");
+        assert_eq!(repo_name("repo"), "repo");
+    }
+}
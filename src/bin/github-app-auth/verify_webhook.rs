@@ -0,0 +1,34 @@
+use crate::BoxError;
+use clap::Args;
+use github_app_auth::webhook::verify_signature;
+use std::io::{self, Read};
+use std::path::PathBuf;
+
+#[derive(Args)]
+pub struct VerifyWebhookArgs {
+    /// Path to a file containing the webhook secret. A trailing newline
+    /// is ignored.
+    #[arg(long)]
+    secret_file: PathBuf,
+
+    /// Value of the X-Hub-Signature-256 header, `sha256=<hex digest>`.
+    #[arg(long)]
+    signature: String,
+}
+
+pub fn run(args: VerifyWebhookArgs) -> Result<(), BoxError> {
+    let secret = std::fs::read(&args.secret_file).map_err(|err| {
+        format!("failed to read {}: {}", args.secret_file.display(), err)
+    })?;
+    let secret = secret
+        .strip_suffix(b"\n")
+        .map(|secret| secret.strip_suffix(b"\r").unwrap_or(secret))
+        .unwrap_or(&secret);
+
+    let mut payload = Vec::new();
+    io::stdin().read_to_end(&mut payload)?;
+
+    verify_signature(secret, &payload, &args.signature)?;
+    println!("signature is valid");
+    Ok(())
+}
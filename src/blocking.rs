@@ -0,0 +1,118 @@
+//! A blocking interface to installation access tokens, for code that
+//! doesn't use async.
+//!
+//! Enabled by the `blocking` feature. Each token runs its own
+//! single-threaded Tokio runtime, which is only busy while a call is in
+//! progress, so the application doesn't need a runtime of its own.
+//! Don't call these methods from within an async runtime; use the
+//! async [`InstallationAccessToken`](crate::InstallationAccessToken)
+//! there instead.
+//!
+//! ```no_run
+//! use github_app_auth::blocking::InstallationAccessToken;
+//! use github_app_auth::GithubAuthParams;
+//!
+//! let token = InstallationAccessToken::new(
+//!     GithubAuthParams::builder()
+//!         .user_agent("my-cool-user-agent")
+//!         .private_key(b"my private key".to_vec())
+//!         .app_id(1234)
+//!         .installation_id(5678)
+//!         .build(),
+//! )
+//! .expect("failed to get installation access token");
+//! let header = token.header().expect("failed to get authentication header");
+//! ```
+
+use crate::{AuthError, GithubAuthParams};
+use reqwest::header::HeaderMap;
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::runtime::Runtime;
+
+/// A blocking wrapper around an
+/// [`InstallationAccessToken`](crate::InstallationAccessToken).
+///
+/// Cloning is cheap, and clones share the token and the runtime.
+#[derive(Clone)]
+pub struct InstallationAccessToken {
+    inner: crate::InstallationAccessToken,
+    runtime: Arc<Runtime>,
+}
+
+impl InstallationAccessToken {
+    /// Fetch an installation access token, see
+    /// [`InstallationAccessToken::new`](crate::InstallationAccessToken::new).
+    pub fn new(
+        params: GithubAuthParams,
+    ) -> Result<InstallationAccessToken, AuthError> {
+        let token = InstallationAccessToken::lazy(params)?;
+        token.connect()?;
+        Ok(token)
+    }
+
+    /// Create a token without fetching it, see
+    /// [`InstallationAccessToken::lazy`](crate::InstallationAccessToken::lazy).
+    pub fn lazy(
+        params: GithubAuthParams,
+    ) -> Result<InstallationAccessToken, AuthError> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        Ok(InstallationAccessToken {
+            inner: crate::InstallationAccessToken::lazy(params)?,
+            runtime: Arc::new(runtime),
+        })
+    }
+
+    /// Fetch the token now if it needs fetching.
+    pub fn connect(&self) -> Result<(), AuthError> {
+        self.runtime.block_on(self.inner.connect())
+    }
+
+    /// Get the installation access token itself, refreshing it if
+    /// necessary.
+    pub fn token(&self) -> Result<String, AuthError> {
+        self.runtime.block_on(self.inner.token())
+    }
+
+    /// Get an HTTP authentication header for the token, refreshing it
+    /// if necessary.
+    pub fn header(&self) -> Result<HeaderMap, AuthError> {
+        self.runtime.block_on(self.inner.header())
+    }
+
+    /// Revoke the current token, see
+    /// [`InstallationAccessToken::revoke`](crate::InstallationAccessToken::revoke).
+    pub fn revoke(&self) -> Result<(), AuthError> {
+        self.runtime.block_on(self.inner.revoke())
+    }
+
+    /// Get the time the current token expires.
+    pub fn expires_at(&self) -> SystemTime {
+        self.inner.expires_at()
+    }
+
+    /// Get the async token this wraps, for example to hand it to code
+    /// that does use async. Its methods must then be called from a
+    /// runtime.
+    pub fn as_async(&self) -> &crate::InstallationAccessToken {
+        &self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocking_error() {
+        let token = InstallationAccessToken::lazy(GithubAuthParams {
+            app_id: 1,
+            ..Default::default()
+        })
+        .unwrap();
+        // There is no key to sign the token request with.
+        assert!(matches!(token.token(), Err(AuthError::JwtError(_))));
+    }
+}
@@ -0,0 +1,404 @@
+//! An in-process token broker serving installation access tokens over
+//! a Unix domain socket or HTTP.
+//!
+//! The broker is a long-running task that owns the app's private key.
+//! Worker processes connect to its socket with a `BrokerClient` and
+//! receive installation access tokens without ever seeing the key.
+//! With the `broker-http` feature the same tokens can be served over
+//! HTTP instead, see the `http` module.
+//!
+//! The protocol is one line of JSON per request and response. A
+//! request names the installation, or uses the broker's default
+//! installation if `installation_id` is omitted:
+//!
+//! ```text
+//! {"installation_id": 1216616}
+//! ```
+//!
+//! and the response is either a token or an error:
+//!
+//! ```text
+//! {"token": "ghs_...", "expires_at": "2016-07-11T22:14:10Z"}
+//! {"error": "HTTP request failed: ..."}
+//! ```
+//!
+//! Anyone who can connect to the socket can get tokens, so it is
+//! created readable and writable only by its owner and group. It is
+//! bound in a directory only the owner can enter and moved into place
+//! once its permissions are set, so that nobody else can connect in
+//! between.
+
+use crate::manager::{CachePolicy, TokenManager};
+use crate::{telemetry, AuthError, GithubAuthParams};
+#[cfg(unix)]
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+#[cfg(unix)]
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+#[cfg(unix)]
+use std::sync::Arc;
+use std::time::SystemTime;
+#[cfg(unix)]
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+
+#[cfg(feature = "broker-http")]
+pub mod http;
+
+/// Requests are cut off after this many bytes, so a misbehaving client
+/// can't make the broker buffer unbounded input.
+#[cfg(unix)]
+const MAX_REQUEST_LEN: u64 = 4096;
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub(crate) struct BrokerRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    installation_id: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub(crate) enum BrokerResponse {
+    Token {
+        token: String,
+        #[serde(with = "crate::timestamp")]
+        expires_at: SystemTime,
+    },
+    Error {
+        error: String,
+    },
+}
+
+/// An installation access token handed out by a [`Broker`].
+#[derive(Clone, Eq, PartialEq)]
+pub struct BrokerToken {
+    /// The installation access token.
+    pub token: String,
+
+    /// When the token expires.
+    pub expires_at: SystemTime,
+}
+
+// Written by hand so that the token doesn't end up in logs.
+impl fmt::Debug for BrokerToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BrokerToken")
+            .field("token", &"<redacted>")
+            .field("expires_at", &self.expires_at)
+            .finish()
+    }
+}
+
+/// Mints and caches installation access tokens for one app and serves
+/// them over a Unix domain socket.
+///
+/// Tokens are kept by a [`TokenManager`], so they share its client and
+/// [cache policy](CachePolicy).
+pub struct Broker {
+    app_id: u64,
+    installation_id: u64,
+    manager: TokenManager,
+}
+
+impl Broker {
+    /// Create a broker for the app described by `params`, with a
+    /// client built from them. `params.installation_id` is used for
+    /// requests that don't name an installation.
+    pub fn new(params: GithubAuthParams) -> Result<Broker, AuthError> {
+        Ok(Broker {
+            app_id: params.app_id,
+            installation_id: params.installation_id,
+            manager: TokenManager::for_app(params)?,
+        })
+    }
+
+    /// Set limits on the tokens that are kept, as for a
+    /// [`TokenManager`]. They are applied the next time a token is
+    /// requested.
+    pub fn set_cache_policy(&mut self, policy: CachePolicy) {
+        self.manager.set_cache_policy(policy);
+    }
+
+    /// Get a cached token for an installation, minting or refreshing
+    /// it if necessary.
+    pub async fn token(
+        &self,
+        installation_id: u64,
+    ) -> Result<BrokerToken, AuthError> {
+        let token = self
+            .manager
+            .installation(self.app_id, installation_id)
+            .await?;
+        let current = token.current().await?;
+        Ok(BrokerToken {
+            token: current.token().to_string(),
+            expires_at: current.expires_at,
+        })
+    }
+
+    /// Get a token for a request's installation, or the default
+    /// installation, as a protocol response.
+    async fn respond(&self, installation_id: Option<u64>) -> BrokerResponse {
+        let installation_id = installation_id.unwrap_or(self.installation_id);
+        let result = self.token(installation_id).await;
+        telemetry::record_broker_request(result.is_ok());
+        match result {
+            Ok(token) => BrokerResponse::Token {
+                token: token.token,
+                expires_at: token.expires_at,
+            },
+            Err(err) => BrokerResponse::Error {
+                error: err.to_string(),
+            },
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Broker {
+    /// Bind a Unix domain socket at `path` and serve tokens until an
+    /// error occurs accepting connections. An existing file at `path`
+    /// is removed first.
+    pub async fn serve(
+        self: Arc<Self>,
+        path: impl AsRef<Path>,
+    ) -> std::io::Result<()> {
+        let path = path.as_ref();
+        match std::fs::remove_file(path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                return Err(err)
+            }
+            _ => {}
+        }
+        let listener = bind_private(path)?;
+        info!("token broker listening on {}", path.display());
+        self.serve_listener(listener).await
+    }
+
+    /// Serve tokens on an already bound listener.
+    pub async fn serve_listener(
+        self: Arc<Self>,
+        listener: UnixListener,
+    ) -> std::io::Result<()> {
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let broker = self.clone();
+            tokio::spawn(async move {
+                if let Err(err) = broker.handle(stream).await {
+                    error!("token broker connection failed: {}", err);
+                }
+            });
+        }
+    }
+
+    async fn handle(&self, stream: UnixStream) -> std::io::Result<()> {
+        let (read, mut write) = stream.into_split();
+        let mut read = BufReader::new(read);
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            (&mut read)
+                .take(MAX_REQUEST_LEN)
+                .read_until(b'\n', &mut line)
+                .await?;
+            if line.is_empty() {
+                return Ok(());
+            }
+            // The rest of an overlong request can't be told apart from
+            // the next one, so the connection is closed after it.
+            let too_long =
+                !line.ends_with(b"\n") && line.len() as u64 == MAX_REQUEST_LEN;
+            let response = if too_long {
+                BrokerResponse::Error {
+                    error: format!(
+                        "invalid request: longer than {} bytes",
+                        MAX_REQUEST_LEN
+                    ),
+                }
+            } else {
+                match serde_json::from_slice::<BrokerRequest>(&line) {
+                    Ok(request) => self.respond(request.installation_id).await,
+                    Err(err) => BrokerResponse::Error {
+                        error: format!("invalid request: {}", err),
+                    },
+                }
+            };
+            let mut response = serde_json::to_vec(&response)?;
+            response.push(b'\n');
+            write.write_all(&response).await?;
+            if too_long {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Bind a socket at `path` that only its owner and group can connect to.
+///
+/// The socket is bound in a new directory next to `path` that only the
+/// owner can enter, and renamed to `path` once its permissions are set.
+#[cfg(unix)]
+fn bind_private(path: &Path) -> std::io::Result<UnixListener> {
+    let file_name = path.file_name().ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "socket path has no file name",
+        )
+    })?;
+    let mut dir_name = std::ffi::OsString::from(".");
+    dir_name.push(file_name);
+    dir_name.push(format!(".{}", std::process::id()));
+    let dir = path.with_file_name(dir_name);
+    std::fs::DirBuilder::new().mode(0o700).create(&dir)?;
+    let bound = dir.join("broker.sock");
+    let result = UnixListener::bind(&bound).and_then(|listener| {
+        std::fs::set_permissions(
+            &bound,
+            std::fs::Permissions::from_mode(0o660),
+        )?;
+        std::fs::rename(&bound, path)?;
+        Ok(listener)
+    });
+    // The socket has been moved out, or binding failed.
+    let _ = std::fs::remove_file(&bound);
+    std::fs::remove_dir(&dir)?;
+    result
+}
+
+/// Client for getting tokens from a [`Broker`] over its socket.
+#[cfg(unix)]
+#[derive(Clone, Debug)]
+pub struct BrokerClient {
+    path: PathBuf,
+}
+
+#[cfg(unix)]
+impl BrokerClient {
+    /// Create a client for the broker listening at `path`. No
+    /// connection is made until a token is requested.
+    pub fn new(path: impl Into<PathBuf>) -> BrokerClient {
+        BrokerClient { path: path.into() }
+    }
+
+    /// Get a token for `installation_id`, or for the broker's default
+    /// installation if `None`.
+    pub async fn token(
+        &self,
+        installation_id: Option<u64>,
+    ) -> Result<BrokerToken, AuthError> {
+        let stream = UnixStream::connect(&self.path).await?;
+        let (read, mut write) = stream.into_split();
+        let mut request =
+            serde_json::to_vec(&BrokerRequest { installation_id })?;
+        request.push(b'\n');
+        write.write_all(&request).await?;
+
+        let mut line = String::new();
+        BufReader::new(read).read_line(&mut line).await?;
+        match serde_json::from_str(&line)? {
+            BrokerResponse::Token { token, expires_at } => {
+                Ok(BrokerToken { token, expires_at })
+            }
+            BrokerResponse::Error { error } => {
+                Err(AuthError::BrokerError(error))
+            }
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_invalid_request() {
+        let dir = std::env::temp_dir().join(format!(
+            "github-app-auth-broker-test-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("broker.sock");
+
+        let broker =
+            Arc::new(Broker::new(GithubAuthParams::default()).unwrap());
+        let listener = UnixListener::bind(&path).unwrap();
+        tokio::spawn(broker.serve_listener(listener));
+
+        let mut stream = UnixStream::connect(&path).await.unwrap();
+        stream.write_all(b"not json\n").await.unwrap();
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line).await.unwrap();
+        assert!(line.starts_with(r#"{"error":"invalid request: "#));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_request_limit() {
+        let dir = std::env::temp_dir().join(format!(
+            "github-app-auth-broker-limit-test-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("broker.sock");
+
+        let broker =
+            Arc::new(Broker::new(GithubAuthParams::default()).unwrap());
+        let listener = UnixListener::bind(&path).unwrap();
+        tokio::spawn(broker.serve_listener(listener));
+
+        // The limit applies to each request, not to the connection.
+        let request = b"{\"installation_id\": 1}\n";
+        let count = MAX_REQUEST_LEN as usize / request.len() + 10;
+        let (read, mut write) =
+            UnixStream::connect(&path).await.unwrap().into_split();
+        write.write_all(&request.repeat(count)).await.unwrap();
+        let mut lines = BufReader::new(read).lines();
+        for _ in 0..count {
+            let line = lines.next_line().await.unwrap().unwrap();
+            assert!(!line.contains("invalid request"), "{}", line);
+        }
+
+        let long = vec![b' '; MAX_REQUEST_LEN as usize + 1];
+        write.write_all(&long).await.unwrap();
+        let line = lines.next_line().await.unwrap().unwrap();
+        assert!(line.contains("longer than 4096 bytes"));
+        assert_eq!(lines.next_line().await.unwrap(), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_serve_permissions() {
+        let dir = std::env::temp_dir().join(format!(
+            "github-app-auth-broker-serve-test-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("broker.sock");
+
+        let broker =
+            Arc::new(Broker::new(GithubAuthParams::default()).unwrap());
+        tokio::spawn(broker.clone().serve(path.clone()));
+        let client = BrokerClient::new(&path);
+        // Minting fails without a key, so the slot isn't kept.
+        let result = loop {
+            match client.token(Some(1)).await {
+                Err(AuthError::IoError(_)) => tokio::task::yield_now().await,
+                result => break result,
+            }
+        };
+        assert!(matches!(result, Err(AuthError::BrokerError(_))));
+        assert_eq!(broker.manager.cached_installations().await, 0);
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o660);
+        // Only the socket is left in the directory.
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,142 @@
+//! Serving broker tokens over HTTP with [axum].
+//!
+//! [`router`] exposes `GET /token?installation_id=...`, returning the
+//! same JSON as the socket protocol. Callers authenticate with
+//! `Authorization: Bearer <caller token>` using one of a configured set
+//! of caller tokens. `installation_id` is optional and defaults to the
+//! broker's installation.
+//!
+//! ```no_run
+//! use github_app_auth::broker::{http, Broker};
+//! use github_app_auth::GithubAuthParams;
+//! use std::sync::Arc;
+//!
+//! # async fn wrapper(params: GithubAuthParams) {
+//! let broker = Arc::new(Broker::new(params).unwrap());
+//! let app = http::router(broker, vec!["caller-secret".into()]);
+//! let listener = tokio::net::TcpListener::bind("127.0.0.1:8080").await.unwrap();
+//! axum::serve(listener, app).await.unwrap();
+//! # }
+//! ```
+
+use super::{Broker, BrokerRequest, BrokerResponse};
+use axum::extract::{Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use ring::hmac;
+use ring::rand::SystemRandom;
+use std::sync::Arc;
+
+#[derive(Clone)]
+struct HttpBroker {
+    broker: Arc<Broker>,
+    /// A random key that caller tokens are authenticated with, so that
+    /// they are compared as HMAC tags in constant time, like webhook
+    /// signatures.
+    key: hmac::Key,
+    caller_tags: Arc<Vec<hmac::Tag>>,
+}
+
+impl HttpBroker {
+    fn is_authorized(&self, headers: &HeaderMap) -> bool {
+        let Some(token) = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+        else {
+            return false;
+        };
+        // Check every caller token so that timing doesn't reveal which
+        // one (if any) matched.
+        self.caller_tags.iter().fold(false, |found, tag| {
+            let matches =
+                hmac::verify(&self.key, token.as_bytes(), tag.as_ref()).is_ok();
+            found | matches
+        })
+    }
+}
+
+/// Build a router serving tokens from `broker` at `GET /token`.
+///
+/// Requests must carry one of `caller_tokens` as a bearer token; if
+/// `caller_tokens` is empty every request is rejected.
+///
+/// # Panics
+///
+/// If the system's random number generator fails.
+pub fn router(broker: Arc<Broker>, caller_tokens: Vec<String>) -> Router {
+    let key = hmac::Key::generate(hmac::HMAC_SHA256, &SystemRandom::new())
+        .expect("system random number generator failed");
+    let caller_tags = caller_tokens
+        .iter()
+        .map(|caller_token| hmac::sign(&key, caller_token.as_bytes()))
+        .collect();
+    Router::new()
+        .route("/token", get(token))
+        .with_state(HttpBroker {
+            broker,
+            key,
+            caller_tags: Arc::new(caller_tags),
+        })
+}
+
+async fn token(
+    State(state): State<HttpBroker>,
+    headers: HeaderMap,
+    Query(request): Query<BrokerRequest>,
+) -> Response {
+    let no_store = [(header::CACHE_CONTROL, "no-store")];
+    if !state.is_authorized(&headers) {
+        return (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+            Json(BrokerResponse::Error {
+                error: "missing or invalid caller token".into(),
+            }),
+        )
+            .into_response();
+    }
+    let response = state.broker.respond(request.installation_id).await;
+    let status = match response {
+        BrokerResponse::Token { .. } => StatusCode::OK,
+        BrokerResponse::Error { .. } => StatusCode::BAD_GATEWAY,
+    };
+    (status, no_store, Json(response)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GithubAuthParams;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_unauthorized() {
+        let broker =
+            Arc::new(Broker::new(GithubAuthParams::default()).unwrap());
+        let app = router(broker, vec!["secret".into()]);
+        for authorization in [None, Some("Bearer wrong"), Some("secret")] {
+            let mut request = Request::get("/token?installation_id=1");
+            if let Some(authorization) = authorization {
+                request = request.header("Authorization", authorization);
+            }
+            let response = app
+                .clone()
+                .oneshot(request.body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+        // Minting fails without a key, but the caller is let through.
+        let request = Request::get("/token?installation_id=1")
+            .header("Authorization", "Bearer secret")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+    }
+}
@@ -0,0 +1,269 @@
+//! Building [`GithubAuthParams`], which can't be constructed with a
+//! struct literal outside this crate so that options can be added to it
+//! without breaking callers.
+
+use crate::hooks::ExtraHeaders;
+use crate::{
+    AppKey, AuditLog, ClientOptions, Endpoints, GithubAuthParams, HttpHooks,
+    JwtSigner, MintLimiter, ReloadableKey, RetryPolicy, TokenCache,
+};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Builds [`GithubAuthParams`], see [`GithubAuthParams::builder`].
+/// Each method sets the field of the same name.
+///
+/// ```
+/// use github_app_auth::GithubAuthParams;
+///
+/// let params = GithubAuthParams::builder()
+///     .user_agent("my-cool-user-agent")
+///     .private_key(b"my private key".to_vec())
+///     .app_id(1234)
+///     .installation_id(5678)
+///     .build();
+/// assert_eq!(params.app_id, 1234);
+/// ```
+#[derive(Clone, Default)]
+#[must_use]
+pub struct GithubAuthParamsBuilder(GithubAuthParams);
+
+impl GithubAuthParams {
+    /// Start building parameters from the defaults.
+    pub fn builder() -> GithubAuthParamsBuilder {
+        GithubAuthParamsBuilder::default()
+    }
+}
+
+impl From<GithubAuthParams> for GithubAuthParamsBuilder {
+    /// Start building parameters from existing ones, for example to
+    /// change the installation.
+    fn from(params: GithubAuthParams) -> GithubAuthParamsBuilder {
+        GithubAuthParamsBuilder(params)
+    }
+}
+
+impl GithubAuthParamsBuilder {
+    /// Finish building.
+    pub fn build(self) -> GithubAuthParams {
+        self.0
+    }
+
+    /// See [`GithubAuthParams::user_agent`].
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.0.user_agent = user_agent.into();
+        self
+    }
+
+    /// See [`GithubAuthParams::append_crate_version`].
+    pub fn append_crate_version(mut self, append: bool) -> Self {
+        self.0.append_crate_version = append;
+        self
+    }
+
+    /// See [`GithubAuthParams::private_key`].
+    pub fn private_key(mut self, private_key: impl Into<Vec<u8>>) -> Self {
+        self.0.private_key = private_key.into();
+        self
+    }
+
+    /// See [`GithubAuthParams::key_id`].
+    pub fn key_id(mut self, key_id: impl Into<String>) -> Self {
+        self.0.key_id = Some(key_id.into());
+        self
+    }
+
+    /// Add one of [`GithubAuthParams::fallback_keys`].
+    pub fn fallback_key(mut self, key: AppKey) -> Self {
+        self.0.fallback_keys.push(key);
+        self
+    }
+
+    /// See [`GithubAuthParams::reloadable_key`].
+    pub fn reloadable_key(mut self, key: ReloadableKey) -> Self {
+        self.0.reloadable_key = Some(key);
+        self
+    }
+
+    /// See [`GithubAuthParams::signer`].
+    pub fn signer(mut self, signer: Arc<dyn JwtSigner>) -> Self {
+        self.0.signer = Some(signer);
+        self
+    }
+
+    /// See [`GithubAuthParams::secret_private_key`].
+    #[cfg(feature = "secrecy")]
+    pub fn secret_private_key(
+        mut self,
+        private_key: secrecy::SecretSlice<u8>,
+    ) -> Self {
+        self.0.secret_private_key = Some(private_key);
+        self
+    }
+
+    /// See [`GithubAuthParams::installation_id`].
+    pub fn installation_id(mut self, installation_id: u64) -> Self {
+        self.0.installation_id = installation_id;
+        self
+    }
+
+    /// See [`GithubAuthParams::app_id`].
+    pub fn app_id(mut self, app_id: u64) -> Self {
+        self.0.app_id = app_id;
+        self
+    }
+
+    /// See [`GithubAuthParams::app_slug`].
+    pub fn app_slug(mut self, app_slug: impl Into<String>) -> Self {
+        self.0.app_slug = Some(app_slug.into());
+        self
+    }
+
+    /// Add one of [`GithubAuthParams::repositories`].
+    pub fn repository(mut self, name: impl Into<String>) -> Self {
+        self.0.repositories.push(name.into());
+        self
+    }
+
+    /// Add one of [`GithubAuthParams::permissions`], such as
+    /// `"contents"` with `"read"`.
+    pub fn permission(
+        mut self,
+        name: impl Into<String>,
+        level: impl Into<String>,
+    ) -> Self {
+        self.0.permissions.insert(name.into(), level.into());
+        self
+    }
+
+    /// Add one of [`GithubAuthParams::jwt_claims`].
+    pub fn jwt_claim(
+        mut self,
+        name: impl Into<String>,
+        value: impl Into<serde_json::Value>,
+    ) -> Self {
+        self.0.jwt_claims.insert(name.into(), value.into());
+        self
+    }
+
+    /// See [`GithubAuthParams::fetch_installation`].
+    pub fn fetch_installation(mut self, fetch: bool) -> Self {
+        self.0.fetch_installation = fetch;
+        self
+    }
+
+    /// See [`GithubAuthParams::capture_error_bodies`].
+    pub fn capture_error_bodies(mut self, capture: bool) -> Self {
+        self.0.capture_error_bodies = capture;
+        self
+    }
+
+    /// See [`GithubAuthParams::max_clock_skew`].
+    pub fn max_clock_skew(mut self, max: Duration) -> Self {
+        self.0.max_clock_skew = Some(max);
+        self
+    }
+
+    /// See [`GithubAuthParams::max_mints_per_minute`].
+    pub fn max_mints_per_minute(mut self, max: u32) -> Self {
+        self.0.max_mints_per_minute = Some(max);
+        self
+    }
+
+    /// See [`GithubAuthParams::mint_limiter`].
+    pub fn mint_limiter(mut self, limiter: Arc<MintLimiter>) -> Self {
+        self.0.mint_limiter = Some(limiter);
+        self
+    }
+
+    /// See [`GithubAuthParams::stale_if_error`].
+    pub fn stale_if_error(mut self, min_validity: Duration) -> Self {
+        self.0.stale_if_error = Some(min_validity);
+        self
+    }
+
+    /// Add one of [`GithubAuthParams::previews`].
+    pub fn preview(mut self, media_type: impl Into<String>) -> Self {
+        self.0.previews.push(media_type.into());
+        self
+    }
+
+    /// See [`GithubAuthParams::endpoints`].
+    pub fn endpoints(mut self, endpoints: Endpoints) -> Self {
+        self.0.endpoints = endpoints;
+        self
+    }
+
+    /// See [`GithubAuthParams::proxy`].
+    pub fn proxy(mut self, proxy: impl Into<String>) -> Self {
+        self.0.proxy = Some(proxy.into());
+        self
+    }
+
+    /// See [`GithubAuthParams::timeout`].
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.0.timeout = Some(timeout);
+        self
+    }
+
+    /// See [`GithubAuthParams::client_options`].
+    pub fn client_options(mut self, options: ClientOptions) -> Self {
+        self.0.client_options = options;
+        self
+    }
+
+    /// See [`GithubAuthParams::retry_policy`].
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.0.retry_policy = policy;
+        self
+    }
+
+    /// See [`GithubAuthParams::hooks`].
+    pub fn hooks(mut self, hooks: Arc<dyn HttpHooks>) -> Self {
+        self.0.hooks = Some(hooks);
+        self
+    }
+
+    /// See [`GithubAuthParams::extra_headers`].
+    pub fn extra_headers(mut self, extra_headers: ExtraHeaders) -> Self {
+        self.0.extra_headers = extra_headers;
+        self
+    }
+
+    /// See [`GithubAuthParams::audit_log`].
+    pub fn audit_log(mut self, audit_log: Arc<AuditLog>) -> Self {
+        self.0.audit_log = Some(audit_log);
+        self
+    }
+
+    /// See [`GithubAuthParams::token_cache`].
+    pub fn token_cache(mut self, cache: Arc<dyn TokenCache>) -> Self {
+        self.0.token_cache = Some(cache);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder() {
+        let params = GithubAuthParams::builder()
+            .app_id(1)
+            .installation_id(2)
+            .repository("octo-repo")
+            .permission("contents", "read")
+            .timeout(Duration::from_secs(10))
+            .build();
+        assert_eq!(params.app_id, 1);
+        assert_eq!(params.repositories, ["octo-repo"]);
+        assert_eq!(params.permissions["contents"], "read");
+        assert_eq!(params.timeout, Some(Duration::from_secs(10)));
+
+        let other = GithubAuthParamsBuilder::from(params)
+            .installation_id(3)
+            .build();
+        assert_eq!((other.app_id, other.installation_id), (1, 3));
+    }
+}
@@ -0,0 +1,287 @@
+//! Recording HTTP interactions to replay them in offline tests.
+//!
+//! Enabled by the `cassette` feature. A [`Cassette`] is registered as
+//! the [`hooks`](crate::GithubAuthParams::hooks) of some parameters.
+//! In record mode, requests are sent and their responses are kept until
+//! [`Cassette::save`] writes them to a JSON file. In replay mode,
+//! nothing is sent and each request gets the recorded response for the
+//! same method and URL, in the order they were recorded.
+//!
+//! ```no_run
+//! use github_app_auth::cassette::Cassette;
+//! use github_app_auth::{
+//!     GithubAuthParams, GithubAuthParamsBuilder, InstallationAccessToken,
+//! };
+//! use std::sync::Arc;
+//!
+//! # async fn wrapper(params: GithubAuthParams) {
+//! let cassette = Arc::new(if std::env::var_os("RECORD").is_some() {
+//!     Cassette::record("tests/cassettes/token.json")
+//! } else {
+//!     Cassette::replay("tests/cassettes/token.json").unwrap()
+//! });
+//! let params = GithubAuthParamsBuilder::from(params)
+//!     .hooks(cassette.clone())
+//!     .build();
+//! let token = InstallationAccessToken::new(params).await.unwrap();
+//! cassette.save().unwrap();
+//! # }
+//! ```
+//!
+//! Secrets are scrubbed before recording: only the method and URL of
+//! requests are kept, and tokens in JSON response bodies are replaced.
+//! The `Date` and `Set-Cookie` response headers are dropped too, so
+//! that replays don't depend on when they were recorded.
+
+use crate::{AuthError, HttpHooks};
+use reqwest::{Client, Request, Response};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Keys of JSON response fields whose values are replaced.
+const SCRUBBED_FIELDS: &[&str] = &["token", "access_token", "refresh_token"];
+
+/// Response headers that aren't recorded.
+const DROPPED_HEADERS: &[&str] = &["date", "set-cookie"];
+
+/// Whether a [`Cassette`] sends requests or answers them itself.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Mode {
+    /// Send requests and record their responses.
+    Record,
+
+    /// Answer requests with recorded responses.
+    Replay,
+}
+
+/// One recorded request and its response.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Interaction {
+    /// Request method, such as `"POST"`.
+    pub method: String,
+
+    /// Request URL.
+    pub url: String,
+
+    /// Response status code.
+    pub status: u16,
+
+    /// Response headers.
+    pub headers: Vec<(String, String)>,
+
+    /// Response body.
+    pub body: String,
+}
+
+/// Records or replays HTTP interactions.
+#[derive(Debug)]
+pub struct Cassette {
+    path: PathBuf,
+    mode: Mode,
+    /// For replays, whether each interaction has been used.
+    interactions: Mutex<Vec<(Interaction, bool)>>,
+}
+
+fn scrub(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(object) => {
+            for (key, value) in object {
+                if SCRUBBED_FIELDS.contains(&key.as_str()) && value.is_string()
+                {
+                    *value = "<scrubbed>".into();
+                } else {
+                    scrub(value);
+                }
+            }
+        }
+        serde_json::Value::Array(array) => array.iter_mut().for_each(scrub),
+        _ => {}
+    }
+}
+
+impl Cassette {
+    /// Start recording, to be saved to `path`.
+    pub fn record(path: impl Into<PathBuf>) -> Cassette {
+        Cassette {
+            path: path.into(),
+            mode: Mode::Record,
+            interactions: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Load the interactions recorded at `path` for replaying.
+    pub fn replay(path: impl Into<PathBuf>) -> Result<Cassette, AuthError> {
+        let path = path.into();
+        let interactions: Vec<Interaction> =
+            serde_json::from_slice(&std::fs::read(&path)?)?;
+        Ok(Cassette {
+            path,
+            mode: Mode::Replay,
+            interactions: Mutex::new(
+                interactions.into_iter().map(|i| (i, false)).collect(),
+            ),
+        })
+    }
+
+    /// Whether this cassette records or replays.
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Write the recorded interactions to the file. Does nothing in
+    /// replay mode.
+    pub fn save(&self) -> Result<(), AuthError> {
+        if self.mode == Mode::Replay {
+            return Ok(());
+        }
+        let interactions: Vec<_> = self
+            .interactions
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(interaction, _)| interaction.clone())
+            .collect();
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.path, serde_json::to_vec_pretty(&interactions)?)?;
+        Ok(())
+    }
+
+    /// Send or replay a request.
+    pub(crate) async fn execute(
+        &self,
+        client: &Client,
+        request: Request,
+    ) -> Result<Response, AuthError> {
+        let method = request.method().to_string();
+        let url = request.url().to_string();
+        let interaction = match self.mode {
+            Mode::Replay => {
+                let mut interactions = self.interactions.lock().unwrap();
+                let (interaction, used) = interactions
+                    .iter_mut()
+                    .find(|(interaction, used)| {
+                        !used
+                            && interaction.method == method
+                            && interaction.url == url
+                    })
+                    .ok_or_else(|| {
+                        AuthError::ReplayError(format!("{} {}", method, url))
+                    })?;
+                *used = true;
+                interaction.clone()
+            }
+            Mode::Record => {
+                let response = client.execute(request).await?;
+                let status = response.status().as_u16();
+                let headers = response
+                    .headers()
+                    .iter()
+                    .filter(|(name, _)| {
+                        !DROPPED_HEADERS.contains(&name.as_str())
+                    })
+                    .filter_map(|(name, value)| {
+                        Some((name.to_string(), value.to_str().ok()?.into()))
+                    })
+                    .collect();
+                let body = response.text().await?;
+                let body = match serde_json::from_str(&body) {
+                    Ok(mut json) => {
+                        scrub(&mut json);
+                        json.to_string()
+                    }
+                    Err(_) => body,
+                };
+                let interaction = Interaction {
+                    method,
+                    url,
+                    status,
+                    headers,
+                    body,
+                };
+                self.interactions
+                    .lock()
+                    .unwrap()
+                    .push((interaction.clone(), true));
+                interaction
+            }
+        };
+        let mut response = http::Response::builder().status(interaction.status);
+        for (name, value) in &interaction.headers {
+            response = response.header(name, value);
+        }
+        let response = response.body(interaction.body).map_err(|err| {
+            AuthError::ReplayError(format!("invalid recording: {}", err))
+        })?;
+        Ok(response.into())
+    }
+}
+
+impl HttpHooks for Cassette {
+    fn cassette(&self) -> Option<&Cassette> {
+        Some(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{timestamp, GithubAuthParams, InstallationAccessToken};
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn test_scrub() {
+        let mut json = serde_json::json!({
+            "token": "ghs_secret",
+            "nested": [{"access_token": "gho_secret"}],
+            "expires_at": "2024-01-01T00:00:00Z",
+        });
+        scrub(&mut json);
+        assert_eq!(json["token"], "<scrubbed>");
+        assert_eq!(json["nested"][0]["access_token"], "<scrubbed>");
+        assert_eq!(json["expires_at"], "2024-01-01T00:00:00Z");
+    }
+
+    #[tokio::test]
+    async fn test_replay() {
+        let path = std::env::temp_dir().join(format!(
+            "github-app-auth-cassette-test-{}.json",
+            std::process::id()
+        ));
+        let expires_at =
+            timestamp::format(SystemTime::now() + Duration::from_secs(3600));
+        let interactions = vec![Interaction {
+            method: "POST".into(),
+            url: "https://api.github.com/app/installations/2/access_tokens"
+                .into(),
+            status: 201,
+            headers: vec![("content-type".into(), "application/json".into())],
+            body: format!(
+                r#"{{"token": "<scrubbed>", "expires_at": "{}"}}"#,
+                expires_at
+            ),
+        }];
+        std::fs::write(&path, serde_json::to_vec(&interactions).unwrap())
+            .unwrap();
+
+        let cassette = Arc::new(Cassette::replay(&path).unwrap());
+        let token = InstallationAccessToken::new(GithubAuthParams {
+            private_key: include_bytes!("../testdata/private-key.pem").to_vec(),
+            installation_id: 2,
+            hooks: Some(cassette.clone()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        assert_eq!(token.token().await.unwrap(), "<scrubbed>");
+        // Each interaction is only replayed once.
+        assert!(matches!(
+            token.revoke().await,
+            Err(AuthError::ReplayError(_))
+        ));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
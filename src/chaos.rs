@@ -0,0 +1,131 @@
+//! Injecting failures into the token machinery, for testing how a
+//! service copes when authentication breaks.
+//!
+//! Enabled by the `chaos` feature, which is meant for test builds
+//! only. A [`FailureInjector`] is registered as the
+//! [`hooks`](crate::GithubAuthParams::hooks) of some parameters, and
+//! its faults can be switched on and off while the tokens are in use:
+//!
+//! ```no_run
+//! use github_app_auth::chaos::FailureInjector;
+//! use github_app_auth::GithubAuthParams;
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! let injector = Arc::new(FailureInjector::default());
+//! let params = GithubAuthParams::builder()
+//!     .hooks(injector.clone())
+//!     .build();
+//! // The next two token refreshes fail with a 500 response.
+//! injector.fail_refreshes(2);
+//! // Every request takes an extra second.
+//! injector.set_latency(Some(Duration::from_secs(1)));
+//! ```
+//!
+//! To combine faults with other hooks, return the injector from
+//! [`HttpHooks::failure_injector`] of those hooks.
+
+use crate::HttpHooks;
+use http::StatusCode;
+use reqwest::{Request, Response};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Faults to inject into requests and tokens.
+#[derive(Debug, Default)]
+pub struct FailureInjector(Mutex<Faults>);
+
+#[derive(Debug, Default)]
+struct Faults {
+    refresh_failures: u32,
+    expire_immediately: bool,
+    latency: Option<Duration>,
+}
+
+impl FailureInjector {
+    /// Make the next `count` token refreshes fail with
+    /// `500 Internal Server Error` without sending them.
+    pub fn fail_refreshes(&self, count: u32) {
+        self.0.lock().unwrap().refresh_failures = count;
+    }
+
+    /// Whether newly fetched tokens are treated as already expired, so
+    /// that every use fetches another one.
+    pub fn set_expire_immediately(&self, expire_immediately: bool) {
+        self.0.lock().unwrap().expire_immediately = expire_immediately;
+    }
+
+    /// Delay every request by `latency`, or stop delaying them.
+    pub fn set_latency(&self, latency: Option<Duration>) {
+        self.0.lock().unwrap().latency = latency;
+    }
+
+    /// Clear all faults.
+    pub fn reset(&self) {
+        *self.0.lock().unwrap() = Faults::default();
+    }
+
+    pub(crate) fn expire_immediately(&self) -> bool {
+        self.0.lock().unwrap().expire_immediately
+    }
+
+    /// Apply the faults to a request about to be sent. Returns a
+    /// response to use instead of sending it, if it is made to fail.
+    pub(crate) async fn intercept(
+        &self,
+        request: &Request,
+    ) -> Option<Response> {
+        let (latency, fail) = {
+            let mut faults = self.0.lock().unwrap();
+            let fail = faults.refresh_failures > 0
+                && request.url().path().ends_with("/access_tokens");
+            if fail {
+                faults.refresh_failures -= 1;
+            }
+            (faults.latency, fail)
+        };
+        if let Some(latency) = latency {
+            tokio::time::sleep(latency).await;
+        }
+        fail.then(|| {
+            http::Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body("injected failure")
+                .unwrap()
+                .into()
+        })
+    }
+}
+
+impl HttpHooks for FailureInjector {
+    fn failure_injector(&self) -> Option<&FailureInjector> {
+        Some(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AuthError, GithubAuthParams, InstallationAccessToken};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_fail_refreshes() {
+        let injector = Arc::new(FailureInjector::default());
+        injector.fail_refreshes(1);
+        let token = InstallationAccessToken::lazy(GithubAuthParams {
+            private_key: include_bytes!("../testdata/private-key.pem").to_vec(),
+            hooks: Some(injector.clone()),
+            ..Default::default()
+        })
+        .unwrap();
+        match token.connect().await {
+            Err(AuthError::ReqwestError(err)) => assert_eq!(
+                err.status(),
+                Some(StatusCode::INTERNAL_SERVER_ERROR)
+            ),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(injector.0.lock().unwrap().refresh_failures, 0);
+    }
+}
@@ -0,0 +1,296 @@
+//! Options for the HTTP client built from [`GithubAuthParams`].
+
+use crate::{AuthError, GithubAuthParams};
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Settings of the [`reqwest::Client`] that is built from
+/// [`GithubAuthParams`]. The defaults are reqwest's.
+#[derive(Clone, Debug, Default)]
+pub struct ClientOptions {
+    /// Addresses to connect to for specific hostnames instead of
+    /// resolving them through DNS, for example to pin
+    /// `ghe.example` to an internal IP. The port of each address is
+    /// ignored in favor of the one in the request URL.
+    pub resolve: Vec<(String, SocketAddr)>,
+
+    /// Which IP versions to connect over. Some networks have broken
+    /// IPv6, which can make connections hang before falling back to
+    /// IPv4.
+    pub ip_family: IpFamily,
+
+    /// Local address to send requests from, for hosts with several
+    /// addresses whose egress is allowlisted by source IP.
+    pub local_address: Option<IpAddr>,
+
+    /// Maximum number of idle connections kept open per host.
+    pub pool_max_idle_per_host: Option<usize>,
+
+    /// How long idle connections are kept open.
+    pub pool_idle_timeout: Option<Duration>,
+
+    /// Use HTTP/2 without negotiating it first. Only use this with
+    /// servers that are known to support HTTP/2.
+    pub http2_prior_knowledge: bool,
+
+    /// Interval for sending HTTP/2 pings to keep connections alive.
+    pub http2_keep_alive_interval: Option<Duration>,
+
+    /// How long to wait for a reply to an HTTP/2 keep-alive ping
+    /// before closing the connection.
+    pub http2_keep_alive_timeout: Option<Duration>,
+
+    /// Size of the HTTP/2 flow control window of each stream, in bytes.
+    pub http2_initial_stream_window_size: Option<u32>,
+
+    /// Size of the HTTP/2 flow control window of each connection, in
+    /// bytes.
+    pub http2_initial_connection_window_size: Option<u32>,
+
+    /// Size HTTP/2 flow control windows to the measured bandwidth,
+    /// overriding the initial window sizes. This can speed up large
+    /// listings.
+    pub http2_adaptive_window: bool,
+
+    /// Whether to ask for gzip-compressed responses and decompress
+    /// them. Requires the `gzip` feature, which enables this by
+    /// default.
+    #[cfg(feature = "gzip")]
+    pub gzip: Option<bool>,
+
+    /// Whether to ask for brotli-compressed responses and decompress
+    /// them. Requires the `brotli` feature, which enables this by
+    /// default.
+    #[cfg(feature = "brotli")]
+    pub brotli: Option<bool>,
+
+    /// Only trust specific certificate authorities. Requires the
+    /// `rustls-tls` feature.
+    #[cfg(feature = "rustls-tls")]
+    pub tls_pinning: Option<TlsPinning>,
+}
+
+/// IP versions to connect over, for [`ClientOptions::ip_family`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum IpFamily {
+    /// Both IPv4 and IPv6, whichever the resolver returns.
+    #[default]
+    Any,
+
+    /// Only IPv4.
+    V4,
+
+    /// Only IPv6.
+    V6,
+}
+
+impl IpFamily {
+    fn allows(self, ip: IpAddr) -> bool {
+        match self {
+            IpFamily::Any => true,
+            IpFamily::V4 => ip.is_ipv4(),
+            IpFamily::V6 => ip.is_ipv6(),
+        }
+    }
+}
+
+/// Resolves hostnames with the system resolver, keeping only the
+/// addresses of one IP family.
+struct FamilyResolver(IpFamily);
+
+impl Resolve for FamilyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let family = self.0;
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+            let addrs = tokio::task::spawn_blocking(move || {
+                (host.as_str(), 0)
+                    .to_socket_addrs()
+                    .map(|addrs| addrs.collect::<Vec<_>>())
+            })
+            .await??;
+            let addrs: Vec<_> = addrs
+                .into_iter()
+                .filter(|addr| family.allows(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!(
+                    "no {:?} address for {}",
+                    family,
+                    name.as_str()
+                )
+                .into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Certificate authorities to trust instead of the built-in roots, for
+/// [`ClientOptions::tls_pinning`].
+///
+/// To pin the chain of `api.github.com`, list the root it is issued
+/// from. Connections to servers whose chain doesn't lead to one of the
+/// listed roots then fail, even if the system trusts them.
+#[cfg(feature = "rustls-tls")]
+#[derive(Clone, Debug, Default)]
+pub struct TlsPinning {
+    /// PEM-encoded CA certificates to trust.
+    pub root_certificates: Vec<Vec<u8>>,
+
+    /// Whether to trust the built-in roots as well, for example to add
+    /// a corporate CA for GitHub Enterprise Server.
+    pub built_in_roots: bool,
+}
+
+impl GithubAuthParams {
+    /// The user agent sent with requests, with
+    /// [`append_crate_version`](Self::append_crate_version) applied.
+    pub(crate) fn full_user_agent(&self) -> String {
+        if self.append_crate_version {
+            format!(
+                "{} {}/{}",
+                self.user_agent,
+                env!("CARGO_PKG_NAME"),
+                env!("CARGO_PKG_VERSION")
+            )
+        } else {
+            self.user_agent.clone()
+        }
+    }
+
+    pub(crate) fn build_client(&self) -> Result<reqwest::Client, AuthError> {
+        let mut builder =
+            reqwest::Client::builder().user_agent(self.full_user_agent());
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+        }
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        let options = &self.client_options;
+        for (hostname, addr) in &options.resolve {
+            builder = builder.resolve(hostname, *addr);
+        }
+        if options.ip_family != IpFamily::Any {
+            builder = builder
+                .dns_resolver(Arc::new(FamilyResolver(options.ip_family)));
+        }
+        if let Some(addr) = options.local_address {
+            builder = builder.local_address(addr);
+        }
+        if let Some(max) = options.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+        if let Some(timeout) = options.pool_idle_timeout {
+            builder = builder.pool_idle_timeout(timeout);
+        }
+        if options.http2_prior_knowledge {
+            builder = builder.http2_prior_knowledge();
+        }
+        if let Some(interval) = options.http2_keep_alive_interval {
+            builder = builder.http2_keep_alive_interval(interval);
+        }
+        if let Some(timeout) = options.http2_keep_alive_timeout {
+            builder = builder.http2_keep_alive_timeout(timeout);
+        }
+        if let Some(size) = options.http2_initial_stream_window_size {
+            builder = builder.http2_initial_stream_window_size(size);
+        }
+        if let Some(size) = options.http2_initial_connection_window_size {
+            builder = builder.http2_initial_connection_window_size(size);
+        }
+        if options.http2_adaptive_window {
+            builder = builder.http2_adaptive_window(true);
+        }
+        #[cfg(feature = "gzip")]
+        if let Some(enable) = options.gzip {
+            builder = builder.gzip(enable);
+        }
+        #[cfg(feature = "brotli")]
+        if let Some(enable) = options.brotli {
+            builder = builder.brotli(enable);
+        }
+        #[cfg(feature = "rustls-tls")]
+        if let Some(pinning) = &options.tls_pinning {
+            builder = builder.tls_built_in_root_certs(pinning.built_in_roots);
+            for pem in &pinning.root_certificates {
+                // reqwest ignores text without certificates, which
+                // would leave nothing trusted without saying why.
+                let is_certificate = pem::parse_many(pem).is_ok_and(|items| {
+                    !items.is_empty()
+                        && items.iter().all(|item| item.tag == "CERTIFICATE")
+                });
+                if !is_certificate {
+                    return Err(AuthError::ConfigError(
+                        "TLS root is not a PEM-encoded certificate".into(),
+                    ));
+                }
+                builder = builder
+                    .add_root_certificate(reqwest::Certificate::from_pem(pem)?);
+            }
+        }
+        Ok(builder.build()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_full_user_agent() {
+        let mut params = GithubAuthParams {
+            user_agent: "my-cool-user-agent".into(),
+            ..Default::default()
+        };
+        assert_eq!(params.full_user_agent(), "my-cool-user-agent");
+        params.append_crate_version = true;
+        assert_eq!(
+            params.full_user_agent(),
+            format!(
+                "my-cool-user-agent github-app-auth/{}",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ip_family() {
+        let resolve = |family| {
+            FamilyResolver(family).resolve("localhost".parse().unwrap())
+        };
+        for addr in resolve(IpFamily::V4).await.unwrap() {
+            assert!(addr.is_ipv4());
+        }
+        if let Ok(addrs) = resolve(IpFamily::V6).await {
+            for addr in addrs {
+                assert!(addr.is_ipv6());
+            }
+        }
+    }
+
+    #[cfg(feature = "rustls-tls")]
+    #[test]
+    fn test_tls_pinning() {
+        let mut params = GithubAuthParams::default();
+        params.client_options.tls_pinning = Some(TlsPinning {
+            root_certificates: vec![
+                include_bytes!("../testdata/ca.pem").to_vec()
+            ],
+            built_in_roots: false,
+        });
+        assert!(params.build_client().is_ok());
+        params.client_options.tls_pinning = Some(TlsPinning {
+            root_certificates: vec![b"not a certificate".to_vec()],
+            built_in_roots: false,
+        });
+        assert!(matches!(
+            params.build_client(),
+            Err(AuthError::ConfigError(_))
+        ));
+    }
+}
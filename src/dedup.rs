@@ -0,0 +1,97 @@
+//! A process-wide registry of installation tokens, see
+//! [`InstallationAccessToken::shared`].
+
+use crate::{
+    AuthError, GithubAuthParams, InstallationAccessToken, SharedToken,
+    DEFAULT_REFRESH_SAFETY_MARGIN,
+};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, Weak};
+
+/// What a token is for: the API, app, installation and scope.
+type Key = (String, u64, u64, Vec<String>, BTreeMap<String, String>);
+
+struct Entry {
+    client: reqwest::Client,
+    params: Arc<GithubAuthParams>,
+    shared: Weak<SharedToken>,
+}
+
+/// Tokens created with [`InstallationAccessToken::shared`]. Entries
+/// don't keep their tokens alive, and are removed once they are gone.
+static TOKENS: Mutex<BTreeMap<Key, Entry>> = Mutex::new(BTreeMap::new());
+
+fn key(params: &GithubAuthParams) -> Key {
+    (
+        params.api_base().to_string(),
+        params.app_id,
+        params.installation_id,
+        params.repositories.clone(),
+        params.permissions.clone(),
+    )
+}
+
+impl InstallationAccessToken {
+    /// Like [`lazy`](Self::lazy), but if a token created this way for
+    /// the same API URL, app, installation, repositories and
+    /// permissions still exists anywhere in the process, return a clone
+    /// of it instead, so that parts of an application that create
+    /// tokens independently share one token and its refreshes.
+    ///
+    /// The first token's parameters and client are used; the other
+    /// settings of `params`, such as its key and hooks, are ignored
+    /// when an existing token is returned.
+    pub fn shared(
+        params: GithubAuthParams,
+    ) -> Result<InstallationAccessToken, AuthError> {
+        let key = key(&params);
+        let mut tokens = TOKENS.lock().unwrap();
+        tokens.retain(|_, entry| entry.shared.strong_count() > 0);
+        if let Some(entry) = tokens.get(&key) {
+            if let Some(shared) = entry.shared.upgrade() {
+                return Ok(InstallationAccessToken {
+                    client: entry.client.clone(),
+                    refresh_safety_margin: DEFAULT_REFRESH_SAFETY_MARGIN,
+                    params: entry.params.clone(),
+                    shared,
+                });
+            }
+        }
+        let token = InstallationAccessToken::lazy(params)?;
+        tokens.insert(
+            key,
+            Entry {
+                client: token.client.clone(),
+                params: token.params.clone(),
+                shared: Arc::downgrade(&token.shared),
+            },
+        );
+        Ok(token)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shared() {
+        let params = |installation_id| GithubAuthParams {
+            app_id: 194,
+            installation_id,
+            ..Default::default()
+        };
+        let a = InstallationAccessToken::shared(params(1)).unwrap();
+        let b = InstallationAccessToken::shared(params(1)).unwrap();
+        let c = InstallationAccessToken::shared(params(2)).unwrap();
+        assert!(Arc::ptr_eq(&a.shared, &b.shared));
+        assert!(!Arc::ptr_eq(&a.shared, &c.shared));
+
+        // Once every clone is gone, a new token is created.
+        let weak = Arc::downgrade(&a.shared);
+        drop((a, b));
+        assert!(weak.upgrade().is_none());
+        let d = InstallationAccessToken::shared(params(1)).unwrap();
+        assert_eq!(Arc::strong_count(&d.shared), 1);
+    }
+}
@@ -0,0 +1,131 @@
+//! Base URLs of the GitHub API.
+
+use crate::GITHUB_API_URL;
+
+/// Base URLs of the REST API, the GraphQL API, and the host that
+/// release assets are uploaded to.
+///
+/// These are separate hosts on github.com and separate paths on GitHub
+/// Enterprise Server. The default is github.com.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Endpoints {
+    /// Base URL of the REST API, such as `https://api.github.com`.
+    pub rest: String,
+
+    /// URL of the GraphQL endpoint, such as
+    /// `https://api.github.com/graphql`.
+    pub graphql: String,
+
+    /// Base URL for uploading release assets, such as
+    /// `https://uploads.github.com`.
+    pub uploads: String,
+}
+
+impl Endpoints {
+    /// Endpoints of github.com.
+    pub fn github_com() -> Endpoints {
+        Endpoints {
+            rest: GITHUB_API_URL.into(),
+            graphql: format!("{}/graphql", GITHUB_API_URL),
+            uploads: "https://uploads.github.com".into(),
+        }
+    }
+
+    /// Endpoints of a GitHub Enterprise Cloud tenant with data
+    /// residency, given its subdomain of `ghe.com`. For example, the
+    /// tenant `octocorp` is at `octocorp.ghe.com` and its REST API at
+    /// `https://api.octocorp.ghe.com`.
+    pub fn ghe_com(tenant: &str) -> Endpoints {
+        let rest = format!("https://api.{}.ghe.com", tenant);
+        Endpoints {
+            graphql: format!("{}/graphql", rest),
+            uploads: format!("https://uploads.{}.ghe.com", tenant),
+            rest,
+        }
+    }
+
+    /// Endpoints of a GitHub Enterprise Server instance, given its
+    /// hostname such as `ghe.example`.
+    pub fn enterprise_server(hostname: &str) -> Endpoints {
+        Endpoints::from_api_url(&format!("https://{}/api/v3", hostname))
+    }
+
+    /// Derive all endpoints from a REST API base URL.
+    ///
+    /// `https://api.github.com` gives the github.com endpoints,
+    /// `https://api.TENANT.ghe.com` the data residency ones, and
+    /// `https://HOSTNAME/api/v3` the GitHub Enterprise Server ones.
+    /// For any other URL, such as a mock server, the GraphQL endpoint
+    /// is `/graphql` under it and uploads go to it directly.
+    pub fn from_api_url(api_url: &str) -> Endpoints {
+        let rest = api_url.trim_end_matches('/');
+        if rest == GITHUB_API_URL {
+            return Endpoints::github_com();
+        }
+        if let Some(tenant) = rest
+            .strip_prefix("https://api.")
+            .and_then(|host| host.strip_suffix(".ghe.com"))
+        {
+            return Endpoints::ghe_com(tenant);
+        }
+        match rest.strip_suffix("/api/v3") {
+            Some(base) => Endpoints {
+                rest: rest.into(),
+                graphql: format!("{}/api/graphql", base),
+                uploads: format!("{}/api/uploads", base),
+            },
+            None => Endpoints {
+                rest: rest.into(),
+                graphql: format!("{}/graphql", rest),
+                uploads: rest.into(),
+            },
+        }
+    }
+
+    pub(crate) fn rest_base(&self) -> &str {
+        self.rest.trim_end_matches('/')
+    }
+
+    pub(crate) fn uploads_base(&self) -> &str {
+        self.uploads.trim_end_matches('/')
+    }
+}
+
+impl Default for Endpoints {
+    fn default() -> Endpoints {
+        Endpoints::github_com()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_api_url() {
+        assert_eq!(
+            Endpoints::from_api_url("https://api.github.com/"),
+            Endpoints::github_com()
+        );
+        assert_eq!(
+            Endpoints::enterprise_server("ghe.example"),
+            Endpoints {
+                rest: "https://ghe.example/api/v3".into(),
+                graphql: "https://ghe.example/api/graphql".into(),
+                uploads: "https://ghe.example/api/uploads".into(),
+            }
+        );
+        assert_eq!(
+            Endpoints::from_api_url("https://api.octocorp.ghe.com"),
+            Endpoints {
+                rest: "https://api.octocorp.ghe.com".into(),
+                graphql: "https://api.octocorp.ghe.com/graphql".into(),
+                uploads: "https://uploads.octocorp.ghe.com".into(),
+            }
+        );
+        assert_eq!(
+            Endpoints::from_api_url("http://127.0.0.1:8080").graphql,
+            "http://127.0.0.1:8080/graphql"
+        );
+    }
+}
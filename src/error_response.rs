@@ -0,0 +1,127 @@
+//! Keeping failed responses in errors, for
+//! [`GithubAuthParams::capture_error_bodies`].
+
+use crate::{AuthError, GithubAuthParams};
+use reqwest::header::{HeaderMap, SET_COOKIE};
+use reqwest::{Response, StatusCode};
+
+/// How much of a failed response's body is kept.
+const MAX_BODY_LEN: usize = 4096;
+
+/// Prefixes of GitHub tokens, which are removed from captured bodies.
+const TOKEN_PREFIXES: &[&str] =
+    &["ghs_", "ghp_", "gho_", "ghu_", "ghr_", "github_pat_", "v1."];
+
+/// A failed response, as kept by
+/// [`capture_error_bodies`](GithubAuthParams::capture_error_bodies).
+#[derive(Clone, Debug)]
+pub struct ErrorResponse {
+    /// Status of the response.
+    pub status: StatusCode,
+
+    /// URL of the request.
+    pub url: url::Url,
+
+    /// Response headers, without `Set-Cookie`.
+    pub headers: HeaderMap,
+
+    /// Response body, truncated to 4 KiB and with anything that looks
+    /// like a GitHub token replaced.
+    pub body: String,
+}
+
+/// Replace GitHub tokens in `body`.
+fn scrub(body: &str) -> String {
+    let mut scrubbed = String::with_capacity(body.len());
+    let mut rest = body;
+    while let Some((start, prefix)) = TOKEN_PREFIXES
+        .iter()
+        .filter_map(|prefix| Some((rest.find(prefix)?, prefix)))
+        .min()
+    {
+        scrubbed.push_str(&rest[..start]);
+        scrubbed.push_str("<scrubbed>");
+        rest = rest[start + prefix.len()..].trim_start_matches(|c: char| {
+            c.is_ascii_alphanumeric() || c == '_' || c == '.'
+        });
+    }
+    scrubbed.push_str(rest);
+    scrubbed
+}
+
+fn truncate(mut body: String) -> String {
+    if body.len() > MAX_BODY_LEN {
+        let mut end = MAX_BODY_LEN;
+        while !body.is_char_boundary(end) {
+            end -= 1;
+        }
+        body.truncate(end);
+        body.push_str("...");
+    }
+    body
+}
+
+/// Turn an unsuccessful response into an error, keeping the response
+/// if `params` asks for it.
+pub(crate) async fn check_status(
+    params: &GithubAuthParams,
+    response: Response,
+) -> Result<Response, AuthError> {
+    if !params.capture_error_bodies {
+        return Ok(response.error_for_status()?);
+    }
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let url = response.url().clone();
+    let mut headers = response.headers().clone();
+    headers.remove(SET_COOKIE);
+    let body = response.text().await.unwrap_or_default();
+    Err(AuthError::ResponseError(Box::new(ErrorResponse {
+        status,
+        url,
+        headers,
+        body: scrub(&truncate(body)),
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scrub() {
+        assert_eq!(
+            scrub(r#"{"token": "ghs_16C7e42F292c6912E7710c8"} v1.abc"#),
+            r#"{"token": "<scrubbed>"} <scrubbed>"#
+        );
+        assert_eq!(
+            scrub("<html>Bad gateway</html>"),
+            "<html>Bad gateway</html>"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_status() {
+        let params = GithubAuthParams {
+            capture_error_bodies: true,
+            ..Default::default()
+        };
+        let response = Response::from(
+            http::Response::builder()
+                .status(502)
+                .header("set-cookie", "session=1")
+                .body(format!("<html>{}</html>", "x".repeat(5000)))
+                .unwrap(),
+        );
+        match check_status(&params, response).await {
+            Err(AuthError::ResponseError(response)) => {
+                assert_eq!(response.status, StatusCode::BAD_GATEWAY);
+                assert!(response.headers.get(SET_COOKIE).is_none());
+                assert_eq!(response.body.len(), MAX_BODY_LEN + 3);
+            }
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
+}
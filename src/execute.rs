@@ -0,0 +1,324 @@
+//! Sending authenticated requests with automatic refresh and retry.
+
+use crate::retry::Outcome;
+use crate::{hooks, AuthError, AuthEvent, InstallationAccessToken};
+use log::warn;
+use reqwest::header::{HeaderMap, AUTHORIZATION};
+use reqwest::{Request, Response, StatusCode};
+use std::time::{Duration, SystemTime};
+
+/// Rate limit state reported by the most recent API response. See
+/// [Rate limits for the REST API](https://docs.github.com/en/rest/overview/rate-limits-for-the-rest-api).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RateLimit {
+    /// Maximum number of requests allowed per hour.
+    pub limit: u64,
+
+    /// Number of requests remaining in the current window.
+    pub remaining: u64,
+
+    /// Number of requests made in the current window.
+    pub used: u64,
+
+    /// When the current window resets.
+    pub reset: SystemTime,
+}
+
+impl RateLimit {
+    /// Parse the `x-ratelimit-*` response headers. Returns `None` if
+    /// any required header is missing or malformed.
+    pub fn from_headers(headers: &HeaderMap) -> Option<RateLimit> {
+        let get = |name: &str| -> Option<u64> {
+            headers.get(name)?.to_str().ok()?.parse().ok()
+        };
+        let limit = get("x-ratelimit-limit")?;
+        let remaining = get("x-ratelimit-remaining")?;
+        Some(RateLimit {
+            limit,
+            remaining,
+            used: get("x-ratelimit-used")
+                .unwrap_or(limit.saturating_sub(remaining)),
+            reset: SystemTime::UNIX_EPOCH
+                + Duration::from_secs(get("x-ratelimit-reset")?),
+        })
+    }
+}
+
+/// How much a token has been used, see
+/// [`InstallationAccessToken::usage`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct TokenUsage {
+    /// Number of requests the token was used to authenticate, counting
+    /// each retry and each [`header`](InstallationAccessToken::header)
+    /// call. Refreshes don't reset it.
+    pub requests: u64,
+
+    /// When the token was last used, if ever.
+    pub last_used: Option<SystemTime>,
+}
+
+impl InstallationAccessToken {
+    /// Send a request authenticated with the installation access
+    /// token.
+    ///
+    /// This is a minimal hardened client built on the token: the
+    /// token is refreshed if necessary before sending, a `401` causes
+    /// the token to be refreshed and the request to be retried once,
+    /// reporting an [`AuthEvent::TokenInvalidated`] to the hooks,
+    /// and other failures are retried according to the
+    /// [`retry_policy`](crate::GithubAuthParams::retry_policy), which
+    /// by default retries rate-limited responses (`429`, or `403` with
+    /// the rate limit exhausted) after waiting for the limit to reset
+    /// if that happens within a minute. Requests with a streaming body
+    /// can't be retried.
+    ///
+    /// Concurrent requests that are rejected with the same token share
+    /// one new token, so that a revoked token is only replaced once.
+    ///
+    /// Like [`reqwest::Client::execute`], unsuccessful responses are
+    /// returned rather than turned into errors once retries are
+    /// exhausted.
+    pub async fn execute(
+        &self,
+        mut request: Request,
+    ) -> Result<Response, AuthError> {
+        let mut refreshed = false;
+        let mut attempts = 1;
+        loop {
+            let authorization = self.authorization().await?;
+            request
+                .headers_mut()
+                .insert(AUTHORIZATION, authorization.clone());
+            // Keep a copy of the request for retrying. This fails for
+            // streaming bodies, which then get a single attempt.
+            let retry = request.try_clone();
+            let result = hooks::execute(
+                &self.client,
+                &self.params.hooks,
+                &self.params.extra_headers,
+                request,
+            )
+            .await;
+            if let Ok(response) = &result {
+                self.record_rate_limit(response);
+            }
+            let Some(retry) = retry else {
+                return result;
+            };
+
+            if !refreshed
+                && matches!(
+                    &result,
+                    Ok(response) if response.status() == StatusCode::UNAUTHORIZED
+                )
+            {
+                warn!("request was unauthorized, refreshing token");
+                if let Ok(response) = result {
+                    self.report_invalidated(response).await;
+                }
+                self.replace_rejected(&authorization).await?;
+                refreshed = true;
+                request = retry;
+                continue;
+            }
+            let outcome = match &result {
+                Ok(response) => Outcome::Response(response),
+                Err(err) => Outcome::Error(err),
+            };
+            let Some(wait) = self.params.retry_policy.delay(
+                attempts,
+                outcome,
+                self.params.now(),
+            ) else {
+                return result;
+            };
+            warn!("request failed, retrying in {:?}", wait);
+            tokio::time::sleep(wait).await;
+            attempts += 1;
+            request = retry;
+        }
+    }
+
+    /// Tell the hooks that `response` rejected a token that hadn't
+    /// expired.
+    async fn report_invalidated(&self, response: Response) {
+        let Some(hooks) = &self.params.hooks else {
+            return;
+        };
+        let expires_at = self.expires_at();
+        let message = response
+            .json::<serde_json::Value>()
+            .await
+            .ok()
+            .and_then(|body| Some(body.get("message")?.as_str()?.to_string()));
+        hooks.on_event(&AuthEvent::TokenInvalidated {
+            installation_id: self.params.installation_id,
+            expires_at,
+            message,
+        });
+    }
+
+    /// Get the rate limit state reported by the most recent response
+    /// to a request sent with [`execute`](Self::execute), if any.
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        *self.shared.rate_limit.lock().unwrap()
+    }
+
+    /// Get how much the token has been used by this crate's request
+    /// helpers, across all clones.
+    pub fn usage(&self) -> TokenUsage {
+        *self.shared.usage.lock().unwrap()
+    }
+
+    pub(crate) fn record_use(&self) {
+        let mut usage = self.shared.usage.lock().unwrap();
+        usage.requests += 1;
+        usage.last_used = Some(self.params.now());
+    }
+
+    pub(crate) fn record_rate_limit(&self, response: &Response) {
+        if let Some(rate_limit) = RateLimit::from_headers(response.headers()) {
+            *self.shared.rate_limit.lock().unwrap() = Some(rate_limit);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limit_from_headers() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(RateLimit::from_headers(&headers), None);
+
+        headers.insert("x-ratelimit-limit", "5000".parse().unwrap());
+        headers.insert("x-ratelimit-remaining", "4999".parse().unwrap());
+        headers.insert("x-ratelimit-reset", "1372700873".parse().unwrap());
+        assert_eq!(
+            RateLimit::from_headers(&headers),
+            Some(RateLimit {
+                limit: 5000,
+                remaining: 4999,
+                used: 1,
+                reset: SystemTime::UNIX_EPOCH + Duration::from_secs(1372700873),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_usage() {
+        let token =
+            InstallationAccessToken::for_test("t", Duration::from_secs(60));
+        assert_eq!(token.usage(), TokenUsage::default());
+        token.header().await.unwrap();
+        token.clone().header().await.unwrap();
+        let usage = token.usage();
+        assert_eq!(usage.requests, 2);
+        assert!(usage.last_used.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_invalidated_token_is_reminted() {
+        use crate::simulation::Simulation;
+        use crate::GithubAuthParams;
+        use std::sync::Arc;
+
+        let simulation = Arc::new(Simulation::new());
+        let token = InstallationAccessToken::lazy(
+            GithubAuthParams::builder()
+                .private_key(
+                    include_bytes!("../testdata/private-key.pem").to_vec(),
+                )
+                .installation_id(2)
+                .hooks(simulation.clone())
+                .build(),
+        )
+        .unwrap();
+        simulation.respond_token("revoked", Duration::from_secs(3600));
+        simulation.respond_error(401, "Bad credentials");
+        simulation.respond_token("new", Duration::from_secs(3600));
+        simulation.respond(200, serde_json::json!({}));
+        let request = token
+            .client
+            .get("https://api.github.com/installation/repositories")
+            .build()
+            .unwrap();
+        let response = token.execute(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(token.token().await.unwrap(), "new");
+        assert_eq!(token.usage().last_used, Some(simulation.now()));
+        assert!(matches!(
+            &simulation.events()[..],
+            [AuthEvent::TokenInvalidated {
+                installation_id: 2,
+                message: Some(message),
+                ..
+            }] if message == "Bad credentials"
+        ));
+    }
+    #[cfg(feature = "wiremock")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_concurrent_rejections_mint_once() {
+        use crate::wiremock::{access_token_request, token_created};
+        use crate::{Endpoints, GithubAuthParams};
+        use futures_util::future::join_all;
+        use wiremock::matchers::{header, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        access_token_request(2)
+            .respond_with(token_created("revoked"))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        access_token_request(2)
+            .respond_with(token_created("new"))
+            .mount(&server)
+            .await;
+        // Delay the rejections so that every request has been sent with
+        // the revoked token before the first one is rejected.
+        Mock::given(method("GET"))
+            .and(path("/installation/repositories"))
+            .and(header("authorization", "token revoked"))
+            .respond_with(
+                ResponseTemplate::new(401)
+                    .set_delay(Duration::from_millis(200)),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/installation/repositories"))
+            .and(header("authorization", "token new"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+
+        let token = InstallationAccessToken::new(GithubAuthParams {
+            private_key: include_bytes!("../testdata/private-key.pem").to_vec(),
+            installation_id: 2,
+            endpoints: Endpoints::from_api_url(&server.uri()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let url = format!("{}/installation/repositories", server.uri());
+        let responses = join_all((0..5).map(|_| {
+            let request = token.client.get(&url).build().unwrap();
+            token.execute(request)
+        }))
+        .await;
+        for response in responses {
+            assert_eq!(response.unwrap().status(), StatusCode::OK);
+        }
+        let mints = server
+            .received_requests()
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|request| request.method.as_str() == "POST")
+            .count();
+        // The first token and one replacement.
+        assert_eq!(mints, 2);
+    }
+}
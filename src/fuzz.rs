@@ -0,0 +1,38 @@
+//! Entry points for fuzzing the parsers of GitHub responses.
+//!
+//! Enabled by the `fuzzing` feature. Each function takes arbitrary
+//! bytes, as a fuzzer generates them, and parses them the way the
+//! crate parses the corresponding response. None of them may panic.
+//! For example, a [cargo-fuzz](https://github.com/rust-fuzz/cargo-fuzz)
+//! target:
+//!
+//! ```ignore
+//! #![no_main]
+//! libfuzzer_sys::fuzz_target!(|data: &[u8]| {
+//!     let _ = github_app_auth::fuzz::installation_token(data);
+//! });
+//! ```
+
+use crate::{app, timestamp, AuthError, RawInstallationAccessToken};
+use std::time::SystemTime;
+
+/// Parse a response to `POST /app/installations/{id}/access_tokens`,
+/// returning when the token expires.
+pub fn installation_token(data: &[u8]) -> Result<SystemTime, AuthError> {
+    RawInstallationAccessToken::parse(data).map(|raw| raw.expires_at)
+}
+
+/// Parse a response to `GET /app/installations/{id}`.
+pub fn installation(data: &[u8]) -> Result<app::Installation, AuthError> {
+    Ok(serde_json::from_slice(data)?)
+}
+
+/// Parse an RFC 3339 timestamp, as in `expires_at` fields.
+pub fn timestamp(data: &[u8]) -> Option<SystemTime> {
+    timestamp::parse(std::str::from_utf8(data).ok()?)
+}
+
+/// Parse an HTTP date, as in the `Date` header.
+pub fn http_date(data: &[u8]) -> Option<SystemTime> {
+    timestamp::parse_http_date(std::str::from_utf8(data).ok()?)
+}
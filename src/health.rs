@@ -0,0 +1,96 @@
+//! Checking that a token works, for readiness and liveness probes.
+
+use crate::error_response::check_status;
+use crate::{
+    hooks, AuthError, InstallationAccessToken, RateLimit, GITHUB_JSON,
+};
+use reqwest::header::AUTHORIZATION;
+use std::time::{Duration, Instant, SystemTime};
+
+/// The result of a successful [`InstallationAccessToken::healthy`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct HealthReport {
+    /// How long ago the current token was fetched.
+    pub token_age: Duration,
+
+    /// When the current token expires.
+    pub expires_at: SystemTime,
+
+    /// How long the request to the API took.
+    pub api_latency: Duration,
+
+    /// The rate limit state reported by the API, if any.
+    pub rate_limit: Option<RateLimit>,
+}
+
+impl InstallationAccessToken {
+    /// Check that the token can be fetched and is accepted by the API,
+    /// refreshing it if necessary.
+    ///
+    /// This sends `GET /rate_limit`, which doesn't count against the
+    /// rate limit. Unlike [`execute`](Self::execute) the request is
+    /// not retried, so a probe gets a quick answer. Fails if the token
+    /// can't be fetched, the API can't be reached or the API rejects
+    /// the token.
+    ///
+    /// The probe doesn't count as a use of the token in
+    /// [`usage`](Self::usage).
+    pub async fn healthy(&self) -> Result<HealthReport, AuthError> {
+        let current = self.current().await?;
+        let request = self
+            .client
+            .get(format!("{}/rate_limit", self.params.api_base()))
+            .header("Accept", self.params.accept(GITHUB_JSON))
+            .header(AUTHORIZATION, current.header_value()?)
+            .build()?;
+        let start = Instant::now();
+        let response = hooks::execute(
+            &self.client,
+            &self.params.hooks,
+            &self.params.extra_headers,
+            request,
+        )
+        .await?;
+        let api_latency = start.elapsed();
+        self.record_rate_limit(&response);
+        let rate_limit = RateLimit::from_headers(response.headers());
+        check_status(&self.params, response).await?;
+        Ok(HealthReport {
+            token_age: current.fetched_at.elapsed().unwrap_or_default(),
+            expires_at: current.expires_at,
+            api_latency,
+            rate_limit,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation::Simulation;
+    use crate::{GithubAuthParams, InstallationAccessToken};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_healthy_is_not_a_use() {
+        let simulation = Arc::new(Simulation::new());
+        let token = InstallationAccessToken::lazy(
+            GithubAuthParams::builder()
+                .private_key(
+                    include_bytes!("../testdata/private-key.pem").to_vec(),
+                )
+                .hooks(simulation.clone())
+                .build(),
+        )
+        .unwrap();
+        simulation.respond_token("ghs_test", Duration::from_secs(3600));
+        simulation.respond(200, serde_json::json!({"resources": {}}));
+        let report = token.healthy().await.unwrap();
+        assert_eq!(report.expires_at, token.expires_at());
+        assert_eq!(
+            simulation.requests(),
+            ["POST /app/installations/0/access_tokens", "GET /rate_limit"]
+        );
+        assert_eq!(token.usage().requests, 0);
+    }
+}
@@ -0,0 +1,179 @@
+//! Remediation hints and detailed messages for errors.
+
+use crate::{AuthError, AuthErrorKind, GithubAuthParams};
+use std::fmt::Write;
+
+/// Whether the error came from the request for an installation token.
+fn is_token_request(err: &AuthError) -> bool {
+    match err {
+        AuthError::ReqwestError(err) => err
+            .url()
+            .is_some_and(|url| url.path().ends_with("/access_tokens")),
+        AuthError::ResponseError(response) => {
+            response.url.path().ends_with("/access_tokens")
+        }
+        _ => false,
+    }
+}
+
+impl AuthError {
+    /// A suggestion for fixing the error, if there is one.
+    ///
+    /// Hints are fixed English sentences. To show them in another
+    /// language, translate by [`code`](Self::code) instead.
+    pub fn hint(&self) -> Option<&'static str> {
+        Some(match self.kind() {
+            AuthErrorKind::InvalidKey => {
+                "check that the private key is the PEM file downloaded \
+                 from the app settings"
+            }
+            AuthErrorKind::Unauthorized if is_token_request(self) => {
+                "check that the private key belongs to the app and \
+                 hasn't been revoked, and that the local clock is correct"
+            }
+            AuthErrorKind::Unauthorized => {
+                "the token may have expired or been revoked"
+            }
+            AuthErrorKind::NotFound if is_token_request(self) => {
+                "check the installation ID, and that the app is still \
+                 installed"
+            }
+            AuthErrorKind::Forbidden => {
+                "check that the installation isn't suspended and has the \
+                 permissions needed"
+            }
+            AuthErrorKind::RateLimited => {
+                "wait for the rate limit to reset, or make fewer requests"
+            }
+            AuthErrorKind::ServerError => {
+                "GitHub may be having problems; retry later"
+            }
+            AuthErrorKind::Network => {
+                "check network access to the API and the proxy settings"
+            }
+            AuthErrorKind::Configuration
+                if matches!(self, AuthError::ClockSkew(_)) =>
+            {
+                "synchronize the system clock, for example with NTP"
+            }
+            AuthErrorKind::Configuration
+                if matches!(self, AuthError::MintRateExceeded(_)) =>
+            {
+                "check for code that refreshes tokens in a loop"
+            }
+            AuthErrorKind::Configuration => {
+                "check the parameters or configuration file"
+            }
+            _ => return None,
+        })
+    }
+
+    /// The error with context for logs: its code, the endpoint if the
+    /// error came from a request, the app and installation IDs of
+    /// `params`, and a [`hint`](Self::hint).
+    pub fn detailed_message(&self, params: &GithubAuthParams) -> String {
+        let mut message = format!("[{}] {}", self.code(), self);
+        if let AuthError::ReqwestError(err) = self {
+            if let Some(url) = err.url() {
+                let _ = write!(message, " (endpoint: {})", url);
+            }
+        }
+        // The URL of a captured response is already in its message.
+        let _ = write!(
+            message,
+            " (app {}, installation {})",
+            params.app_id, params.installation_id
+        );
+        if let Some(hint) = self.hint() {
+            let _ = write!(message, "; {}", hint);
+        }
+        message
+    }
+}
+
+/// Links to GitHub's documentation for each kind of error.
+#[cfg(feature = "miette")]
+fn documentation_url(kind: AuthErrorKind) -> Option<&'static str> {
+    Some(match kind {
+        AuthErrorKind::InvalidKey | AuthErrorKind::Unauthorized => {
+            "https://docs.github.com/en/apps/creating-github-apps/authenticating-with-a-github-app/generating-a-json-web-token-jwt-for-a-github-app"
+        }
+        AuthErrorKind::Forbidden | AuthErrorKind::NotFound => {
+            "https://docs.github.com/en/apps/creating-github-apps/authenticating-with-a-github-app/generating-an-installation-access-token-for-a-github-app"
+        }
+        AuthErrorKind::RateLimited => {
+            "https://docs.github.com/en/rest/using-the-rest-api/rate-limits-for-the-rest-api"
+        }
+        AuthErrorKind::ServerError => "https://www.githubstatus.com/",
+        _ => return None,
+    })
+}
+
+/// With the `miette` feature, errors carry their
+/// [`code`](AuthError::code), [`hint`](AuthError::hint) and a link to
+/// GitHub's documentation.
+#[cfg(feature = "miette")]
+impl miette::Diagnostic for AuthError {
+    fn code<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+        Some(Box::new(format!("github_app_auth::{}", self.kind())))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+        self.hint()
+            .map(|hint| Box::new(hint) as Box<dyn std::fmt::Display>)
+    }
+
+    fn url<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+        documentation_url(self.kind())
+            .map(|url| Box::new(url) as Box<dyn std::fmt::Display>)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn http_error(status: u16, url: &str) -> AuthError {
+        use reqwest::ResponseBuilderExt;
+        let response = reqwest::Response::from(
+            http::Response::builder()
+                .status(status)
+                .url(url.parse().unwrap())
+                .body("")
+                .unwrap(),
+        );
+        response.error_for_status().unwrap_err().into()
+    }
+
+    #[test]
+    fn test_detailed_message() {
+        let params = GithubAuthParams {
+            app_id: 1234,
+            installation_id: 5678,
+            ..Default::default()
+        };
+        let err = http_error(
+            401,
+            "https://api.github.com/app/installations/5678/access_tokens",
+        );
+        let message = err.detailed_message(&params);
+        assert!(message.starts_with("[unauthorized] "), "{}", message);
+        assert!(message.contains("app 1234, installation 5678"));
+        assert!(message.contains("endpoint: https://api.github.com/app"));
+        assert!(message.contains("private key belongs to the app"));
+        assert_eq!(AuthError::Timeout.hint(), None);
+    }
+
+    #[cfg(feature = "miette")]
+    #[test]
+    fn test_diagnostic() {
+        use miette::Diagnostic;
+        let err = AuthError::KeyRejected("old".into());
+        assert_eq!(
+            Diagnostic::code(&err).unwrap().to_string(),
+            "github_app_auth::invalid_key"
+        );
+        assert!(err.help().is_some());
+        assert!(err.url().is_some());
+    }
+}
@@ -0,0 +1,226 @@
+//! Hooks called for every HTTP request the crate sends.
+
+use crate::AuthError;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, Request, RequestBuilder, Response};
+use std::fmt;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Callbacks for observing or changing the HTTP requests the crate
+/// sends, including token refreshes, for example for audit logging or
+/// adding tracing headers.
+///
+/// Register an implementation with
+/// [`GithubAuthParams::hooks`](crate::GithubAuthParams::hooks) or
+/// [`OAuthAppParams::hooks`](crate::user::OAuthAppParams::hooks). Both
+/// methods do nothing by default.
+pub trait HttpHooks: Send + Sync {
+    /// Called before a request is sent. Retries call this again.
+    fn on_request(&self, request: &mut Request) {
+        let _ = request;
+    }
+
+    /// Called when response headers have been received, before the
+    /// status is checked.
+    fn on_response(&self, response: &Response) {
+        let _ = response;
+    }
+
+    /// Called when something happens to a token that services may want
+    /// to log or alert on.
+    fn on_event(&self, event: &AuthEvent) {
+        let _ = event;
+    }
+
+    /// Faults to inject into the requests and tokens these hooks are
+    /// used for. Returns `None` by default.
+    #[cfg(feature = "chaos")]
+    fn failure_injector(&self) -> Option<&crate::chaos::FailureInjector> {
+        None
+    }
+
+    /// The cassette to record requests to or replay them from. Returns
+    /// `None` by default.
+    #[cfg(feature = "cassette")]
+    fn cassette(&self) -> Option<&crate::cassette::Cassette> {
+        None
+    }
+
+    /// The simulation that answers requests and provides the time.
+    /// Returns `None` by default.
+    #[cfg(any(test, feature = "test-util"))]
+    fn simulation(&self) -> Option<&crate::simulation::Simulation> {
+        None
+    }
+}
+
+/// Something that happened to an installation access token, passed to
+/// [`HttpHooks::on_event`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum AuthEvent {
+    /// GitHub rejected the token with `401 Unauthorized` before it
+    /// expired, for example because the app's permissions changed,
+    /// which revokes existing tokens. A new token is minted and the
+    /// request is retried.
+    TokenInvalidated {
+        /// The installation the token is for.
+        installation_id: u64,
+
+        /// When the token would have expired.
+        expires_at: SystemTime,
+
+        /// The message GitHub gave, if any.
+        message: Option<String>,
+    },
+}
+
+/// Hooks as stored in parameters.
+pub(crate) type Hooks = Option<Arc<dyn HttpHooks>>;
+
+/// Produces the value of a header for each request, for example a new
+/// request ID.
+pub type HeaderGenerator = Arc<dyn Fn() -> HeaderValue + Send + Sync>;
+
+/// Headers added to every request the crate sends, including token
+/// requests, set with
+/// [`GithubAuthParams::extra_headers`](crate::GithubAuthParams::extra_headers)
+/// or
+/// [`OAuthAppParams::extra_headers`](crate::user::OAuthAppParams::extra_headers),
+/// for example an API key required by a corporate gateway. Headers the
+/// crate sets itself, such as `Authorization`, are not replaced.
+#[derive(Clone, Default)]
+pub struct ExtraHeaders {
+    /// Headers with a fixed value.
+    pub headers: HeaderMap,
+
+    /// Headers whose value is generated for each request, including
+    /// each retry. These are added after [`headers`](Self::headers).
+    pub generated: Vec<(HeaderName, HeaderGenerator)>,
+}
+
+// Written by hand so that header values, which may be API keys, don't
+// end up in logs.
+impl fmt::Debug for ExtraHeaders {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExtraHeaders")
+            .field("headers", &self.headers.keys().collect::<Vec<_>>())
+            .field(
+                "generated",
+                &self
+                    .generated
+                    .iter()
+                    .map(|(name, _)| name)
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl ExtraHeaders {
+    fn apply(&self, headers: &mut HeaderMap) {
+        for (name, value) in &self.headers {
+            headers.entry(name).or_insert_with(|| value.clone());
+        }
+        for (name, generate) in &self.generated {
+            headers.entry(name).or_insert_with(|| generate());
+        }
+    }
+}
+
+/// Send a request with `extra_headers`, calling `hooks` around it.
+pub(crate) async fn execute(
+    client: &Client,
+    hooks: &Hooks,
+    extra_headers: &ExtraHeaders,
+    mut request: Request,
+) -> Result<Response, AuthError> {
+    extra_headers.apply(request.headers_mut());
+    if let Some(hooks) = hooks {
+        hooks.on_request(&mut request);
+    }
+    #[cfg(feature = "chaos")]
+    if let Some(injector) =
+        hooks.as_ref().and_then(|hooks| hooks.failure_injector())
+    {
+        if let Some(response) = injector.intercept(&request).await {
+            if let Some(hooks) = hooks {
+                hooks.on_response(&response);
+            }
+            return Ok(response);
+        }
+    }
+    #[cfg(any(test, feature = "test-util"))]
+    if let Some(simulation) =
+        hooks.as_ref().and_then(|hooks| hooks.simulation())
+    {
+        let response = simulation.respond_to(&request)?;
+        if let Some(hooks) = hooks {
+            hooks.on_response(&response);
+        }
+        return Ok(response);
+    }
+    #[cfg(feature = "cassette")]
+    let response = match hooks.as_ref().and_then(|hooks| hooks.cassette()) {
+        Some(cassette) => cassette.execute(client, request).await?,
+        None => client.execute(request).await?,
+    };
+    #[cfg(not(feature = "cassette"))]
+    let response = client.execute(request).await?;
+    if let Some(hooks) = hooks {
+        hooks.on_response(&response);
+    }
+    Ok(response)
+}
+
+/// The current time, from the simulation if `hooks` have one.
+pub(crate) fn now(hooks: &Hooks) -> SystemTime {
+    #[cfg(any(test, feature = "test-util"))]
+    if let Some(simulation) =
+        hooks.as_ref().and_then(|hooks| hooks.simulation())
+    {
+        return simulation.now();
+    }
+    let _ = hooks;
+    SystemTime::now()
+}
+
+/// Build and send a request with `extra_headers`, calling `hooks`
+/// around it.
+pub(crate) async fn send(
+    hooks: &Hooks,
+    extra_headers: &ExtraHeaders,
+    builder: RequestBuilder,
+) -> Result<Response, AuthError> {
+    let (client, request) = builder.build_split();
+    execute(&client, hooks, extra_headers, request?).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extra_headers() {
+        let mut extra_headers = ExtraHeaders::default();
+        extra_headers
+            .headers
+            .insert("x-api-key", HeaderValue::from_static("gateway-key"));
+        extra_headers.headers.insert(
+            "authorization",
+            HeaderValue::from_static("Bearer gateway"),
+        );
+        extra_headers.generated.push((
+            HeaderName::from_static("x-request-id"),
+            Arc::new(|| HeaderValue::from_static("1")),
+        ));
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", HeaderValue::from_static("token t"));
+        extra_headers.apply(&mut headers);
+        assert_eq!(headers["x-api-key"], "gateway-key");
+        assert_eq!(headers["x-request-id"], "1");
+        assert_eq!(headers["authorization"], "token t");
+        assert!(!format!("{:?}", extra_headers).contains("gateway-key"));
+    }
+}
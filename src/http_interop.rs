@@ -0,0 +1,91 @@
+//! Interop with version 1 of the [`http`](http1) crate.
+//!
+//! reqwest 0.11 is built on `http` 0.2, but hyper 1.0 stacks use
+//! `http` 1.x. The types are identical in shape but distinct to the
+//! compiler, so these helpers convert between them.
+
+use crate::{AuthError, InstallationAccessToken};
+use reqwest::header::HeaderMap;
+
+/// Convert a header map from `http` 0.2 (as used by reqwest) to
+/// `http` 1.x. Sensitive values stay marked as sensitive.
+pub fn to_http1_headers(headers: &HeaderMap) -> http1::HeaderMap {
+    let mut converted = http1::HeaderMap::with_capacity(headers.len());
+    for (name, value) in headers {
+        // Names and values were already validated by `http` 0.2, which
+        // has the same rules as `http` 1.x.
+        let name = http1::HeaderName::from_bytes(name.as_str().as_bytes())
+            .expect("header name is valid in both http versions");
+        let mut converted_value =
+            http1::HeaderValue::from_bytes(value.as_bytes())
+                .expect("header value is valid in both http versions");
+        converted_value.set_sensitive(value.is_sensitive());
+        converted.append(name, converted_value);
+    }
+    converted
+}
+
+/// Convert a header map from `http` 1.x to `http` 0.2, for passing
+/// headers from a hyper 1.0 stack to reqwest.
+pub fn from_http1_headers(headers: &http1::HeaderMap) -> HeaderMap {
+    let mut converted = HeaderMap::with_capacity(headers.len());
+    for (name, value) in headers {
+        let name = http::HeaderName::from_bytes(name.as_str().as_bytes())
+            .expect("header name is valid in both http versions");
+        let mut converted_value =
+            http::HeaderValue::from_bytes(value.as_bytes())
+                .expect("header value is valid in both http versions");
+        converted_value.set_sensitive(value.is_sensitive());
+        converted.append(name, converted_value);
+    }
+    converted
+}
+
+impl InstallationAccessToken {
+    /// Like [`header`](Self::header), but returns an `http` 1.x header
+    /// map.
+    pub async fn header_http1(&self) -> Result<http1::HeaderMap, AuthError> {
+        Ok(to_http1_headers(&self.header().await?))
+    }
+
+    /// Set the `Authorization` header of an `http` 1.x request,
+    /// refreshing the token if necessary. Any existing
+    /// `Authorization` header is replaced.
+    pub async fn authorize_http1_request<B>(
+        &self,
+        request: &mut http1::Request<B>,
+    ) -> Result<(), AuthError> {
+        let headers = self.header_http1().await?;
+        for (name, value) in headers {
+            if let Some(name) = name {
+                request.headers_mut().insert(name, value);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_http1_headers() {
+        let mut headers = HeaderMap::new();
+        let mut value: reqwest::header::HeaderValue =
+            "token abc".parse().unwrap();
+        value.set_sensitive(true);
+        headers.insert("authorization", value);
+        headers.append("accept", "a".parse().unwrap());
+        headers.append("accept", "b".parse().unwrap());
+
+        let converted = to_http1_headers(&headers);
+        assert_eq!(converted["authorization"], "token abc");
+        assert!(converted["authorization"].is_sensitive());
+        assert_eq!(
+            converted.get_all("accept").iter().collect::<Vec<_>>(),
+            ["a", "b"]
+        );
+        assert_eq!(from_http1_headers(&converted), headers);
+    }
+}
@@ -0,0 +1,130 @@
+//! Loading the private key from a file that may be replaced, such as a
+//! mounted Kubernetes secret.
+//!
+//! Enabled by the `key-file` feature. When a mounted secret is updated,
+//! Kubernetes atomically swaps the symlink the file is reached through,
+//! so the file has to be read again rather than kept open. A
+//! [`KeyFile`] keeps the key in a [`ReloadableKey`] and
+//! [`KeyFile::watch`] checks the file for changes. A new key is only
+//! used once it parses, so that a bad rotation doesn't break signing.
+//! App JWTs are created for every request, so the next one is signed
+//! with the new key.
+//!
+//! ```no_run
+//! use github_app_auth::key_file::KeyFile;
+//! use github_app_auth::{GithubAuthParams, InstallationAccessToken};
+//! use std::time::Duration;
+//!
+//! # async fn wrapper() {
+//! let key_file = KeyFile::open("/var/run/secrets/github-app/private-key.pem").unwrap();
+//! let params = GithubAuthParams::builder()
+//!     .reloadable_key(key_file.key())
+//!     .build();
+//! key_file.watch(Duration::from_secs(30));
+//! let token = InstallationAccessToken::new(params).await.unwrap();
+//! # }
+//! ```
+
+use crate::{AuthError, ReloadableKey};
+use log::{info, warn};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// A private key read from a file.
+#[derive(Debug)]
+pub struct KeyFile {
+    path: PathBuf,
+    key: ReloadableKey,
+}
+
+/// Read a PEM-encoded key, checking that it can sign JWTs.
+fn read_key(path: &Path) -> Result<Vec<u8>, AuthError> {
+    let pem = std::fs::read(path)?;
+    jsonwebtoken::EncodingKey::from_rsa_pem(&pem)?;
+    Ok(pem)
+}
+
+impl KeyFile {
+    /// Read the key from `path`.
+    pub fn open(path: impl Into<PathBuf>) -> Result<KeyFile, AuthError> {
+        let path = path.into();
+        let key = ReloadableKey::new(read_key(&path)?);
+        Ok(KeyFile { path, key })
+    }
+
+    /// The key, to put into
+    /// [`GithubAuthParams::reloadable_key`](crate::GithubAuthParams::reloadable_key).
+    /// It is updated by [`reload`](Self::reload).
+    pub fn key(&self) -> ReloadableKey {
+        self.key.clone()
+    }
+
+    /// Read the file again, and use its key if it changed. Returns
+    /// whether it changed. If the file can't be read or doesn't contain
+    /// a valid key, the previous key is kept.
+    pub fn reload(&self) -> Result<bool, AuthError> {
+        let pem = read_key(&self.path)?;
+        if pem == self.key.get() {
+            return Ok(false);
+        }
+        self.key.set(pem);
+        Ok(true)
+    }
+
+    /// [`reload`](Self::reload) every `interval` in a background task.
+    /// Abort the returned task to stop.
+    pub fn watch(self, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                interval.tick().await;
+                match self.reload() {
+                    Ok(true) => {
+                        info!("reloaded private key {}", self.path.display())
+                    }
+                    Ok(false) => {}
+                    Err(err) => warn!(
+                        "failed to reload private key {}: {}",
+                        self.path.display(),
+                        err
+                    ),
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &[u8] = include_bytes!("../testdata/private-key.pem");
+
+    #[test]
+    fn test_reload() {
+        let dir = std::env::temp_dir().join(format!(
+            "github-app-auth-key-file-test-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("private-key.pem");
+        std::fs::write(&path, KEY).unwrap();
+
+        let key_file = KeyFile::open(&path).unwrap();
+        assert!(!key_file.reload().unwrap());
+
+        // A broken key is not used.
+        std::fs::write(&path, b"not a key").unwrap();
+        assert!(key_file.reload().is_err());
+        assert_eq!(key_file.key().get(), KEY);
+
+        let mut rotated = KEY.to_vec();
+        rotated.push(b'\n');
+        std::fs::write(&path, &rotated).unwrap();
+        assert!(key_file.reload().unwrap());
+        assert_eq!(key_file.key().get(), rotated);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -9,15 +9,17 @@
 //! use github_app_auth::{GithubAuthParams, InstallationAccessToken};
 //!
 //! # async fn wrapper() {
-//! // The token is mutable because the installation access token must be
-//! // periodically refreshed. See the `GithubAuthParams` documentation
-//! // for details on how to get the private key and the two IDs.
-//! let mut token = InstallationAccessToken::new(GithubAuthParams {
-//!     user_agent: "my-cool-user-agent".into(),
-//!     private_key: b"my private key".to_vec(),
-//!     app_id: 1234,
-//!     installation_id: 5678,
-//! }).await.expect("failed to get installation access token");
+//! // See the `GithubAuthParams` documentation for details on how to
+//! // get the private key and the two IDs.
+//! let params = GithubAuthParams::builder()
+//!     .user_agent("my-cool-user-agent")
+//!     .private_key(b"my private key".to_vec())
+//!     .app_id(1234)
+//!     .installation_id(5678)
+//!     .build();
+//! let token = InstallationAccessToken::new(params)
+//!     .await
+//!     .expect("failed to get installation access token");
 //!
 //! // Getting the authentication header will automatically refresh
 //! // the token if necessary, but of course this operation can fail.
@@ -26,17 +28,142 @@
 //! token.client.post("https://some-github-api-url").headers(header).send().await;
 //! # }  // End wrapper
 //! ```
+//!
+//! # Minimal builds
+//!
+//! The default features pull in chrono and reqwest's rustls backend.
+//! For constrained environments, disable them and enable `minimal`:
+//!
+//! ```toml
+//! github-app-auth = { version = "3", default-features = false, features = ["minimal"] }
+//! ```
+//!
+//! Timestamps are then parsed with the time crate, and reqwest is built
+//! without a TLS backend, so clients built from [`GithubAuthParams`]
+//! can't connect to GitHub. Supply your own [`reqwest::Client`],
+//! configured with the TLS backend of your choice, through
+//! [`InstallationAccessToken::with_client`],
+//! [`InstallationAccessToken::lazy_with_client`] or
+//! [`TokenManager::new`].
+//!
+//! # Async runtimes
+//!
+//! Requests are sent with reqwest, which needs a Tokio reactor, and
+//! waits such as for rate limits use Tokio's timer, so the futures of
+//! this crate have to run in a Tokio context. Other parts of an
+//! application can use async-std or smol: wrap calls into this crate
+//! with a compatibility layer such as the `async-compat` crate, which
+//! provides that context without the application driving a Tokio
+//! runtime itself, or use the `blocking` feature's
+//! `blocking::InstallationAccessToken` from a blocking thread.
+//!
+//! # Short-lived processes
+//!
+//! Serverless functions such as AWS Lambda would mint a token on every
+//! cold start. To reuse tokens across invocations, set
+//! [`GithubAuthParams::token_cache`] to a store that outlives them,
+//! create tokens with [`InstallationAccessToken::lazy`] or a
+//! [`TokenManager`], and call [`InstallationAccessToken::persist`] or
+//! [`TokenManager::persist`] when a new token may have been minted.
+//! See the [`token_cache`] module.
 #![warn(missing_docs)]
 
-use chrono::{DateTime, Duration, Utc};
-use log::info;
-use reqwest::header::HeaderMap;
+pub mod app;
+pub mod audit;
+mod auth_headers;
+#[cfg(feature = "aws")]
+pub mod aws;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "broker")]
+pub mod broker;
+mod builder;
+#[cfg(feature = "cassette")]
+pub mod cassette;
+#[cfg(feature = "chaos")]
+pub mod chaos;
+pub mod client;
+pub mod config;
+mod dedup;
+pub mod endpoints;
+mod error_response;
+mod execute;
+#[cfg(feature = "fuzzing")]
+pub mod fuzz;
+pub mod git;
+pub mod health;
+mod hints;
+pub mod hooks;
+#[cfg(feature = "http1")]
+pub mod http_interop;
+#[cfg(feature = "key-file")]
+pub mod key_file;
+pub mod manager;
+mod mint_limit;
+mod pagination;
+pub mod permission;
+pub mod registry;
+pub mod releases;
+pub mod retry;
+pub mod rotation;
+pub mod runners;
+pub mod signer;
+#[cfg(any(test, feature = "test-util"))]
+pub mod simulation;
+pub mod telemetry;
+mod timestamp;
+pub mod token_cache;
+pub mod user;
+pub mod webhook;
+#[cfg(feature = "wiremock")]
+pub mod wiremock;
+
+pub use app::{
+    Account, GithubApp, Installation, RepositoryChanges, RepositorySelection,
+};
+pub use audit::AuditLog;
+pub use auth_headers::{AuthHeaders, ExpiredUse};
+pub use builder::GithubAuthParamsBuilder;
+#[cfg(feature = "rustls-tls")]
+pub use client::TlsPinning;
+pub use client::{ClientOptions, IpFamily};
+pub use endpoints::Endpoints;
+pub use error_response::ErrorResponse;
+pub use execute::{RateLimit, TokenUsage};
+pub use health::HealthReport;
+pub use hooks::{AuthEvent, ExtraHeaders, HttpHooks};
+pub use manager::{CachePolicy, TokenManager};
+pub use mint_limit::MintLimiter;
+pub use permission::{Access, Permission};
+pub use retry::RetryPolicy;
+pub use signer::JwtSigner;
+pub use token_cache::TokenCache;
+
+use error_response::check_status;
+use log::{debug, info, warn};
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex as StdMutex, RwLock as StdRwLock};
 use std::time;
 
 const MACHINE_MAN_PREVIEW: &str =
     "application/vnd.github.machine-man-preview+json";
 
+const GITHUB_API_URL: &str = "https://api.github.com";
+
+const GITHUB_JSON: &str = "application/vnd.github+json";
+
+/// The default [`InstallationAccessToken::refresh_safety_margin`].
+const DEFAULT_REFRESH_SAFETY_MARGIN: time::Duration =
+    time::Duration::from_secs(60);
+
+/// How long app JWTs are valid for.
+const JWT_LIFETIME: time::Duration = time::Duration::from_secs(60);
+
 /// Authentication error enum.
 #[derive(thiserror::Error, Debug)]
 pub enum AuthError {
@@ -55,40 +182,528 @@ pub enum AuthError {
     /// Something very unexpected happened with time itself.
     #[error("system time error: {0}")]
     TimeError(#[from] time::SystemTimeError),
+
+    /// A response body could not be decoded as JSON of the expected
+    /// shape.
+    #[error("JSON decoding failed: {0}")]
+    JsonError(#[from] serde_json::Error),
+
+    /// An I/O error occurred, for example when talking to a token
+    /// broker.
+    #[error("I/O error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    /// A token broker could not provide a token.
+    #[error("token broker error: {0}")]
+    BrokerError(String),
+
+    /// A token was requested for an app that was not registered with
+    /// the [`TokenManager`].
+    #[error("unknown app ID: {0}")]
+    UnknownApp(u64),
+
+    /// A configuration file could not be loaded.
+    #[error("invalid configuration: {0}")]
+    ConfigError(String),
+
+    /// A URL could not be parsed.
+    #[error("invalid URL: {0}")]
+    InvalidUrl(#[from] url::ParseError),
+
+    /// A URL cannot carry git credentials, for example because it is
+    /// not an HTTPS URL.
+    #[error("URL cannot be used with git credentials: {0}")]
+    InvalidGitUrl(String),
+
+    /// A claim in [`GithubAuthParams::jwt_claims`] would replace one
+    /// that is set by this crate.
+    #[error("JWT claim is reserved: {0}")]
+    ReservedClaim(String),
+
+    /// The system random number generator failed.
+    #[error("random number generation failed")]
+    RandomError,
+
+    /// GitHub rejected JWTs signed with a new private key. Contains the
+    /// key's ID, if it has one.
+    #[error("private key was rejected: {0}")]
+    KeyRejected(String),
+
+    /// A secret could not be fetched from a secret store.
+    #[error("failed to load secret: {0}")]
+    SecretError(String),
+
+    /// A [`TokenCache`] failed to load or store a token.
+    #[error("token cache error: {0}")]
+    CacheError(String),
+
+    /// A [`JwtSigner`] failed to sign a JWT.
+    #[error("signing failed: {0}")]
+    SignerError(String),
+
+    /// The installation lacks permissions that were declared as
+    /// required, listed in the message.
+    #[error("missing permissions: {0}")]
+    MissingPermissions(String),
+
+    /// The app isn't subscribed to webhook events that were declared as
+    /// required, listed in the message.
+    #[error("app is not subscribed to events: {0}")]
+    MissingEvents(String),
+
+    /// More tokens would have been minted for the installation in the
+    /// last minute than
+    /// [`max_mints_per_minute`](GithubAuthParams::max_mints_per_minute)
+    /// allows.
+    #[error("too many tokens minted for installation {0} in the last minute")]
+    MintRateExceeded(u64),
+
+    /// Even a new token would expire before the deadline passed to
+    /// [`InstallationAccessToken::header_with_deadline`].
+    #[error("token would expire before the deadline")]
+    ExpiresBeforeDeadline,
+
+    /// An operation didn't finish within its time limit.
+    #[error("timed out")]
+    Timeout,
+
+    /// [`AuthHeaders`] were used after the token in them expired.
+    #[error("authentication headers were used after the token expired")]
+    HeadersExpired,
+
+    /// A [`Cassette`](cassette::Cassette) has no recorded response left
+    /// for a request, or its recording is invalid. Also returned when a
+    /// [`Simulation`](simulation::Simulation) has no scripted response
+    /// left.
+    #[cfg(any(feature = "cassette", test, feature = "test-util"))]
+    #[error("no recorded interaction: {0}")]
+    ReplayError(String),
+
+    /// The local clock is further off from GitHub's than
+    /// [`GithubAuthParams::max_clock_skew`] allows, by the given number
+    /// of seconds. Positive values mean the local clock is behind.
+    #[error("local clock is {0}s off from GitHub's")]
+    ClockSkew(i64),
+
+    /// A request failed, and
+    /// [`capture_error_bodies`](GithubAuthParams::capture_error_bodies)
+    /// is set. Otherwise these are [`AuthError::ReqwestError`]s.
+    #[error("HTTP status {} from {}: {}", .0.status, .0.url, .0.body)]
+    ResponseError(Box<ErrorResponse>),
+}
+
+/// The category of an [`AuthError`], for branching on failures without
+/// matching on variants or messages. Categories and their
+/// [`code`](Self::code)s stay the same across releases; new ones may be
+/// added.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum AuthErrorKind {
+    /// The private key or signer can't sign JWTs, or GitHub rejected
+    /// them.
+    InvalidKey,
+
+    /// GitHub answered `401 Unauthorized`.
+    Unauthorized,
+
+    /// GitHub answered `403 Forbidden` for a reason other than rate
+    /// limits, or the installation lacks required permissions.
+    Forbidden,
+
+    /// GitHub answered `404 Not Found`, for example for an unknown
+    /// installation.
+    NotFound,
+
+    /// A rate limit was hit.
+    RateLimited,
+
+    /// GitHub answered with a server error.
+    ServerError,
+
+    /// GitHub couldn't be reached, or the connection failed.
+    Network,
+
+    /// A response didn't have the expected shape.
+    InvalidResponse,
+
+    /// The parameters or configuration are invalid.
+    Configuration,
+
+    /// A secret, file or other local resource couldn't be read.
+    Io,
+
+    /// An operation took too long, or a token wouldn't last long
+    /// enough.
+    Timeout,
+
+    /// Something else failed, such as the system clock or random
+    /// number generator.
+    Other,
+}
+
+impl AuthErrorKind {
+    /// A stable `snake_case` code for the kind, for logs and support
+    /// tooling.
+    pub fn code(self) -> &'static str {
+        match self {
+            AuthErrorKind::InvalidKey => "invalid_key",
+            AuthErrorKind::Unauthorized => "unauthorized",
+            AuthErrorKind::Forbidden => "forbidden",
+            AuthErrorKind::NotFound => "not_found",
+            AuthErrorKind::RateLimited => "rate_limited",
+            AuthErrorKind::ServerError => "server_error",
+            AuthErrorKind::Network => "network",
+            AuthErrorKind::InvalidResponse => "invalid_response",
+            AuthErrorKind::Configuration => "configuration",
+            AuthErrorKind::Io => "io",
+            AuthErrorKind::Timeout => "timeout",
+            AuthErrorKind::Other => "other",
+        }
+    }
+}
+
+impl fmt::Display for AuthErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+impl AuthError {
+    /// The category of the error.
+    pub fn kind(&self) -> AuthErrorKind {
+        match self {
+            AuthError::JwtError(_)
+            | AuthError::KeyRejected(_)
+            | AuthError::SignerError(_) => AuthErrorKind::InvalidKey,
+            AuthError::ReqwestError(err) => reqwest_error_kind(err),
+            AuthError::ResponseError(response) => status_kind(response.status),
+            AuthError::JsonError(_) => AuthErrorKind::InvalidResponse,
+            AuthError::IoError(_)
+            | AuthError::SecretError(_)
+            | AuthError::CacheError(_) => AuthErrorKind::Io,
+            AuthError::InvalidHeaderValue(_)
+            | AuthError::UnknownApp(_)
+            | AuthError::ConfigError(_)
+            | AuthError::InvalidUrl(_)
+            | AuthError::InvalidGitUrl(_)
+            | AuthError::ReservedClaim(_)
+            | AuthError::ClockSkew(_)
+            | AuthError::MissingEvents(_)
+            | AuthError::MintRateExceeded(_) => AuthErrorKind::Configuration,
+            AuthError::MissingPermissions(_) => AuthErrorKind::Forbidden,
+            AuthError::ExpiresBeforeDeadline
+            | AuthError::Timeout
+            | AuthError::HeadersExpired => AuthErrorKind::Timeout,
+            AuthError::BrokerError(_)
+            | AuthError::TimeError(_)
+            | AuthError::RandomError => AuthErrorKind::Other,
+            #[cfg(any(feature = "cassette", test, feature = "test-util"))]
+            AuthError::ReplayError(_) => AuthErrorKind::Other,
+        }
+    }
+
+    /// The stable code of the error's [`kind`](Self::kind).
+    pub fn code(&self) -> &'static str {
+        self.kind().code()
+    }
+}
+
+fn status_kind(status: StatusCode) -> AuthErrorKind {
+    match status {
+        StatusCode::UNAUTHORIZED => AuthErrorKind::Unauthorized,
+        StatusCode::FORBIDDEN => AuthErrorKind::Forbidden,
+        StatusCode::NOT_FOUND => AuthErrorKind::NotFound,
+        StatusCode::TOO_MANY_REQUESTS => AuthErrorKind::RateLimited,
+        status if status.is_server_error() => AuthErrorKind::ServerError,
+        _ => AuthErrorKind::Other,
+    }
+}
+
+fn reqwest_error_kind(err: &reqwest::Error) -> AuthErrorKind {
+    if let Some(status) = err.status() {
+        return status_kind(status);
+    }
+    if err.is_timeout() {
+        AuthErrorKind::Timeout
+    } else if err.is_decode() {
+        AuthErrorKind::InvalidResponse
+    } else if err.is_builder() {
+        AuthErrorKind::Configuration
+    } else {
+        AuthErrorKind::Network
+    }
 }
 
+/// Claims set on every app JWT, which can't be replaced through
+/// [`GithubAuthParams::jwt_claims`].
+const RESERVED_CLAIMS: &[&str] = &["iat", "exp", "iss", "jti"];
+
 #[derive(Debug, Serialize)]
-struct JwtClaims {
+struct JwtClaims<'a> {
     /// The time that this JWT was issued
     iat: u64,
     // JWT expiration time
     exp: u64,
     // GitHub App's identifier number
     iss: u64,
+    // Unique ID of this JWT
+    jti: String,
+    #[serde(flatten)]
+    extra: &'a BTreeMap<String, serde_json::Value>,
 }
 
-impl JwtClaims {
-    fn new(params: &GithubAuthParams) -> Result<JwtClaims, AuthError> {
+impl<'a> JwtClaims<'a> {
+    /// Create claims for the current time, corrected by `clock_offset`
+    /// seconds.
+    fn new(
+        params: &'a GithubAuthParams,
+        clock_offset: i64,
+    ) -> Result<JwtClaims<'a>, AuthError> {
+        if let Some(name) = params
+            .jwt_claims
+            .keys()
+            .find(|name| RESERVED_CLAIMS.contains(&name.as_str()))
+        {
+            return Err(AuthError::ReservedClaim(name.clone()));
+        }
         let now = time::SystemTime::now()
             .duration_since(time::UNIX_EPOCH)?
-            .as_secs();
+            .as_secs()
+            .saturating_add_signed(clock_offset);
         Ok(JwtClaims {
             // The time that this JWT was issued (now)
             iat: now,
             // JWT expiration time (1 minute from now)
-            exp: now + 60,
+            exp: now + JWT_LIFETIME.as_secs(),
             // GitHub App's identifier number
             iss: params.app_id,
+            jti: new_jti()?,
+            extra: &params.jwt_claims,
         })
     }
 }
 
+/// Generate a random JWT ID.
+fn new_jti() -> Result<String, AuthError> {
+    use base64::Engine;
+    let bytes: [u8; 16] =
+        ring::rand::generate(&ring::rand::SystemRandom::new())
+            .map_err(|_| AuthError::RandomError)?
+            .expose();
+    Ok(base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes))
+}
+
+/// A signed app JWT and its ID.
+struct AppJwt {
+    token: String,
+    jti: String,
+}
+
+/// Request body for scoping an installation access token.
+#[derive(Serialize)]
+struct AccessTokenRequest<'a> {
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    repositories: &'a [String],
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    permissions: &'a BTreeMap<String, String>,
+}
+
 /// This is the structure of the JSON object returned when requesting
 /// an installation access token.
 #[derive(Debug, Deserialize, Eq, PartialEq)]
 struct RawInstallationAccessToken {
     token: String,
-    expires_at: DateTime<Utc>,
+    #[serde(with = "timestamp")]
+    expires_at: time::SystemTime,
+}
+
+impl RawInstallationAccessToken {
+    /// Parse the body of a response to
+    /// `POST /app/installations/{id}/access_tokens`.
+    fn parse(body: &[u8]) -> Result<RawInstallationAccessToken, AuthError> {
+        Ok(serde_json::from_slice(body)?)
+    }
+
+    /// Parse a response, converting the expiry from GitHub's clock to
+    /// the local one using the `Date` header. Otherwise a skewed local
+    /// clock would make the token be refreshed too late, or constantly.
+    async fn from_response(
+        response: reqwest::Response,
+    ) -> Result<RawInstallationAccessToken, AuthError> {
+        let skew = clock_skew(&response);
+        let mut raw =
+            RawInstallationAccessToken::parse(&response.bytes().await?)?;
+        if let Some(skew) = skew {
+            let skew_abs = time::Duration::from_secs(skew.unsigned_abs());
+            let local = if skew > 0 {
+                raw.expires_at.checked_sub(skew_abs)
+            } else {
+                raw.expires_at.checked_add(skew_abs)
+            };
+            raw.expires_at = local.unwrap_or(raw.expires_at);
+        }
+        Ok(raw)
+    }
+}
+
+/// One of the keys that app JWTs can be signed with.
+struct SigningKey<'a> {
+    id: Option<&'a str>,
+    material: KeyMaterial<'a>,
+}
+
+enum KeyMaterial<'a> {
+    Pem(Cow<'a, [u8]>),
+    Signer(&'a dyn JwtSigner),
+}
+
+fn jwt_header(key: &SigningKey) -> jsonwebtoken::Header {
+    jsonwebtoken::Header {
+        alg: jsonwebtoken::Algorithm::RS256,
+        kid: key.id.map(String::from),
+        ..Default::default()
+    }
+}
+
+/// Use an app private key to generate a JWT for authenticating as
+/// the app itself.
+fn create_app_jwt(
+    params: &GithubAuthParams,
+    key: &SigningKey,
+    clock_offset: i64,
+) -> Result<AppJwt, AuthError> {
+    let claims = JwtClaims::new(params, clock_offset)?;
+    let header = jwt_header(key);
+    let token = match &key.material {
+        KeyMaterial::Pem(pem) => {
+            signer::encode(&*params.parsed_keys.get(pem)?, &header, &claims)?
+        }
+        KeyMaterial::Signer(signer) => {
+            signer::encode(*signer, &header, &claims)?
+        }
+    };
+    debug!("created app JWT {}", claims.jti);
+    Ok(AppJwt {
+        token,
+        jti: claims.jti,
+    })
+}
+
+/// How far the local clock may be from GitHub's before a rejected JWT
+/// is blamed on it, in seconds. The `Date` header only has whole
+/// seconds.
+const MAX_CLOCK_SKEW: i64 = 2;
+
+/// How many seconds GitHub's clock is ahead of the local one, according
+/// to the `Date` header of `response`, if that is enough to make GitHub
+/// reject JWTs.
+fn clock_skew(response: &reqwest::Response) -> Option<i64> {
+    let date = response.headers().get("date")?.to_str().ok()?;
+    let server_time = timestamp::parse_http_date(date)?;
+    let now = time::SystemTime::now();
+    let skew = match server_time.duration_since(now) {
+        Ok(ahead) => ahead.as_secs() as i64,
+        Err(behind) => -(behind.duration().as_secs() as i64),
+    };
+    (skew.abs() >= MAX_CLOCK_SKEW).then_some(skew)
+}
+
+/// Skew beyond which a warning is logged even if GitHub accepted the
+/// JWT, in seconds. JWTs are rejected once the skew approaches their
+/// lifetime of a minute.
+const CLOCK_SKEW_WARNING: i64 = 30;
+
+/// Warn about or reject a skewed local clock, according to the `Date`
+/// header of a response to a request authenticated as the app.
+fn check_clock(
+    params: &GithubAuthParams,
+    response: &reqwest::Response,
+) -> Result<(), AuthError> {
+    let Some(skew) = clock_skew(response) else {
+        return Ok(());
+    };
+    if let Some(max) = params.max_clock_skew {
+        if skew.unsigned_abs() > max.as_secs() {
+            return Err(AuthError::ClockSkew(skew));
+        }
+    }
+    if skew.abs() >= CLOCK_SKEW_WARNING {
+        warn!(
+            "the local clock is {}s off from GitHub's, which can make \
+             GitHub reject app JWTs; synchronize it, for example with NTP",
+            skew
+        );
+    }
+    Ok(())
+}
+
+/// Send a request authenticated as the app, with the JWT passed to
+/// `request`.
+///
+/// If GitHub rejects the JWT and its `Date` header shows that the local
+/// clock is off, which makes the `iat` and `exp` claims invalid, the
+/// request is repeated once with GitHub's time. If it is still rejected
+/// and there are fallback keys, the request is repeated with each of
+/// them in turn.
+///
+/// Also returns the `jti` of the last JWT that was created.
+async fn send_as_app(
+    params: &GithubAuthParams,
+    request: impl Fn(&str) -> reqwest::RequestBuilder,
+) -> (Option<String>, Result<reqwest::Response, AuthError>) {
+    let keys = params.signing_keys();
+    let mut jti = None;
+    let mut key_index = 0;
+    let mut clock_offset = None;
+    loop {
+        let key = &keys[key_index];
+        // JWTs are short-lived, so create one for each attempt.
+        let result = retry::send(
+            &params.hooks,
+            &params.extra_headers,
+            &params.retry_policy,
+            || {
+                let jwt =
+                    create_app_jwt(params, key, clock_offset.unwrap_or(0))?;
+                jti = Some(jwt.jti);
+                Ok(request(&jwt.token))
+            },
+        )
+        .await;
+        let response = match result {
+            Ok(response) => response,
+            Err(err) => return (jti, Err(err)),
+        };
+        if response.status() == StatusCode::UNAUTHORIZED
+            && clock_offset.is_none()
+        {
+            if let Some(skew) = clock_skew(&response) {
+                warn!(
+                    "app JWT was rejected and the local clock is {}s off \
+                     from GitHub's, retrying with GitHub's time",
+                    skew
+                );
+                clock_offset = Some(skew);
+                continue;
+            }
+        }
+        // Only checked now, so that a JWT rejected because of the skew
+        // has been retried with GitHub's time first.
+        if let Err(err) = check_clock(params, &response) {
+            return (jti, Err(err));
+        }
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return (jti, Ok(response));
+        }
+        if key_index + 1 == keys.len() {
+            return (jti, Ok(response));
+        }
+        warn!(
+            "app JWT signed with key {} was rejected, trying the next key",
+            key.id.unwrap_or("<unnamed>")
+        );
+        key_index += 1;
+    }
 }
 
 /// Use the app private key to generate a JWT and use the JWT to get
@@ -100,48 +715,213 @@ async fn get_installation_token(
     client: &reqwest::Client,
     params: &GithubAuthParams,
 ) -> Result<RawInstallationAccessToken, AuthError> {
-    let claims = JwtClaims::new(params)?;
-    let header = jsonwebtoken::Header {
-        alg: jsonwebtoken::Algorithm::RS256,
-        ..Default::default()
-    };
-    let private_key =
-        jsonwebtoken::EncodingKey::from_rsa_pem(&params.private_key)?;
-    let token = jsonwebtoken::encode(&header, &claims, &private_key)?;
+    mint_limit::check(params)?;
+    let start = time::Instant::now();
+    let (jti, result) = request_installation_token(client, params).await;
+    telemetry::record_mint(result.is_ok(), start.elapsed());
+    if let Some(audit_log) = &params.audit_log {
+        audit_log.record(
+            params,
+            jti,
+            match &result {
+                Ok(raw) => Ok(raw.expires_at),
+                Err(err) => Err(err.to_string()),
+            },
+        );
+    }
+    result
+}
 
+async fn request_installation_token(
+    client: &reqwest::Client,
+    params: &GithubAuthParams,
+) -> (
+    Option<String>,
+    Result<RawInstallationAccessToken, AuthError>,
+) {
     let url = format!(
-        "https://api.github.com/app/installations/{}/access_tokens",
+        "{}/app/installations/{}/access_tokens",
+        params.api_base(),
         params.installation_id
     );
-    Ok(client
-        .post(&url)
-        .bearer_auth(token)
-        .header("Accept", MACHINE_MAN_PREVIEW)
-        .send()
-        .await?
-        .error_for_status()?
-        .json()
-        .await?)
+    let (jti, response) = send_as_app(params, |jwt| {
+        let request = client
+            .post(&url)
+            .bearer_auth(jwt)
+            .header("Accept", params.accept(MACHINE_MAN_PREVIEW));
+        if params.repositories.is_empty() && params.permissions.is_empty() {
+            return request;
+        }
+        request.json(&AccessTokenRequest {
+            repositories: &params.repositories,
+            permissions: &params.permissions,
+        })
+    })
+    .await;
+    let result = match response {
+        Ok(response) => match check_status(params, response).await {
+            Ok(response) => {
+                RawInstallationAccessToken::from_response(response).await
+            }
+            Err(err) => Err(err),
+        },
+        Err(err) => Err(err),
+    };
+    (jti, result)
 }
 
 /// An installation access token is the primary method for
 /// authenticating with the GitHub API as an application.
+///
+/// Cloning is cheap: clones share the token and refresh it together,
+/// so a token can be stored in shared application state and cloned
+/// into each request handler.
+///
+/// # Concurrency
+///
+/// The type is `Send` and `Sync`, and every method takes `&self`, so
+/// one token can also be shared by reference, for example in an
+/// [`Arc`]. The futures its methods return are `Send`, so they can be
+/// spawned on a multi-threaded runtime.
+///
+/// The current token is kept behind a mutex that is only held to copy
+/// it, never across an `.await`. When the token needs refreshing, one
+/// caller fetches a new one while the others wait for it, so a burst of
+/// requests causes a single refresh. A failed refresh is returned to
+/// the callers that waited for it, and the next caller tries again.
+/// Refreshes are sent with the async [`client`](Self::client), so
+/// waiting for one never blocks the runtime; to not wait at all, use
+/// [`header_nonblocking`](Self::header_nonblocking).
+#[derive(Clone)]
 pub struct InstallationAccessToken {
     /// The [`reqwest::Client`] used to periodically refresh
     /// the token.
     ///
     /// This is made public so that users of the library can re-use
     /// this client for sending requests, but this is not required.
+    /// Requests sent with it share a connection pool with token
+    /// refreshes, which saves a TLS handshake per refresh.
     pub client: reqwest::Client,
 
     /// This time is subtracted from the expiration time to make it less
     /// likely that the token goes out of date just as a request is
-    /// sent.
-    pub refresh_safety_margin: Duration,
+    /// sent. Each clone has its own margin.
+    pub refresh_safety_margin: time::Duration,
 
-    token: String,
-    expires_at: DateTime<Utc>,
-    params: GithubAuthParams,
+    params: Arc<GithubAuthParams>,
+    shared: Arc<SharedToken>,
+}
+
+/// State shared by all clones of an [`InstallationAccessToken`].
+struct SharedToken {
+    current: StdMutex<CurrentToken>,
+    rate_limit: StdMutex<Option<RateLimit>>,
+    installation: StdMutex<Option<app::Installation>>,
+    /// The repositories last listed with
+    /// [`InstallationAccessToken::repositories`].
+    repositories: StdMutex<Option<Vec<app::Repository>>>,
+    usage: StdMutex<TokenUsage>,
+    /// Sends the current token to subscribers whenever it changes.
+    updates: tokio::sync::watch::Sender<TokenInfo>,
+    /// Held while fetching a new token, so that clones that need a
+    /// refresh at the same time only fetch one.
+    refresh_lock: tokio::sync::Mutex<()>,
+}
+
+/// With the `secrecy` feature, the cached token is zeroed when it is
+/// dropped.
+#[cfg(feature = "secrecy")]
+type TokenString = secrecy::SecretString;
+#[cfg(not(feature = "secrecy"))]
+type TokenString = String;
+
+/// A token as sent to subscribers of
+/// [`InstallationAccessToken::subscribe`].
+#[derive(Clone, Eq, PartialEq)]
+pub struct TokenInfo {
+    /// The installation access token. Empty if no token has been
+    /// fetched yet or it was revoked.
+    pub token: String,
+
+    /// When the token expires.
+    pub expires_at: time::SystemTime,
+
+    /// When the token was fetched.
+    pub fetched_at: time::SystemTime,
+}
+
+// Written by hand so that the token doesn't end up in logs.
+impl fmt::Debug for TokenInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenInfo")
+            .field("token", &"<redacted>")
+            .field("expires_at", &self.expires_at)
+            .field("fetched_at", &self.fetched_at)
+            .finish()
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct CurrentToken {
+    token: TokenString,
+    pub(crate) expires_at: time::SystemTime,
+    fetched_at: time::SystemTime,
+    /// The `Authorization` header value, built once per token rather
+    /// than for every request. `None` if the token can't be used in a
+    /// header, which is reported when it is used.
+    authorization: Option<HeaderValue>,
+}
+
+impl CurrentToken {
+    fn new(
+        token: String,
+        expires_at: time::SystemTime,
+        fetched_at: time::SystemTime,
+    ) -> CurrentToken {
+        let authorization = HeaderValue::try_from(format!("token {}", token))
+            .ok()
+            .map(|mut value| {
+                value.set_sensitive(true);
+                value
+            });
+        CurrentToken {
+            authorization,
+            #[cfg(feature = "secrecy")]
+            token: token.into(),
+            #[cfg(not(feature = "secrecy"))]
+            token,
+            expires_at,
+            fetched_at,
+        }
+    }
+
+    fn info(&self) -> TokenInfo {
+        TokenInfo {
+            token: self.token().to_string(),
+            expires_at: self.expires_at,
+            fetched_at: self.fetched_at,
+        }
+    }
+
+    pub(crate) fn token(&self) -> &str {
+        #[cfg(feature = "secrecy")]
+        {
+            use secrecy::ExposeSecret;
+            self.token.expose_secret()
+        }
+        #[cfg(not(feature = "secrecy"))]
+        {
+            &self.token
+        }
+    }
+
+    /// The `Authorization` header value for the token.
+    pub(crate) fn header_value(&self) -> Result<HeaderValue, AuthError> {
+        match &self.authorization {
+            Some(value) => Ok(value.clone()),
+            None => Ok(format!("token {}", self.token()).parse()?),
+        }
+    }
 }
 
 impl InstallationAccessToken {
@@ -150,52 +930,527 @@ impl InstallationAccessToken {
     pub async fn new(
         params: GithubAuthParams,
     ) -> Result<InstallationAccessToken, AuthError> {
-        let client = reqwest::Client::builder()
-            .user_agent(&params.user_agent)
-            .build()?;
-        let raw = get_installation_token(&client, &params).await?;
-        Ok(InstallationAccessToken {
+        let token = InstallationAccessToken::lazy(params)?;
+        token.connect().await?;
+        Ok(token)
+    }
+
+    /// Like [`new`](Self::new), but use an existing client instead of
+    /// building one from `params`. The client settings in `params`,
+    /// such as [`proxy`](GithubAuthParams::proxy), are not used.
+    pub async fn with_client(
+        client: reqwest::Client,
+        params: GithubAuthParams,
+    ) -> Result<InstallationAccessToken, AuthError> {
+        let token = InstallationAccessToken::lazy_with_client(client, params);
+        token.connect().await?;
+        Ok(token)
+    }
+
+    /// Create an installation access token without fetching it. The
+    /// token is fetched the first time it is used, for example by
+    /// [`header`](Self::header), or by calling
+    /// [`connect`](Self::connect).
+    ///
+    /// This does no network I/O, so it is suitable for constructors.
+    /// Until the first fetch, [`expires_at`](Self::expires_at) returns
+    /// the Unix epoch.
+    pub fn lazy(
+        params: GithubAuthParams,
+    ) -> Result<InstallationAccessToken, AuthError> {
+        let client = params.build_client()?;
+        Ok(InstallationAccessToken::lazy_with_client(client, params))
+    }
+
+    /// Like [`lazy`](Self::lazy), but use an existing client instead of
+    /// building one from `params`.
+    pub fn lazy_with_client(
+        client: reqwest::Client,
+        params: GithubAuthParams,
+    ) -> InstallationAccessToken {
+        InstallationAccessToken::from_parts(
             client,
-            token: raw.token,
-            expires_at: raw.expires_at,
             params,
-            refresh_safety_margin: Duration::minutes(1),
-        })
+            CurrentToken::new(
+                String::new(),
+                time::UNIX_EPOCH,
+                time::UNIX_EPOCH,
+            ),
+        )
     }
 
-    /// Get an HTTP authentication header for the installation access
-    /// token.
+    fn from_parts(
+        client: reqwest::Client,
+        params: GithubAuthParams,
+        current: CurrentToken,
+    ) -> InstallationAccessToken {
+        InstallationAccessToken {
+            client,
+            refresh_safety_margin: DEFAULT_REFRESH_SAFETY_MARGIN,
+            params: Arc::new(params),
+            shared: Arc::new(SharedToken {
+                updates: tokio::sync::watch::channel(current.info()).0,
+                current: StdMutex::new(current),
+                rate_limit: StdMutex::new(None),
+                installation: StdMutex::new(None),
+                repositories: StdMutex::new(None),
+                usage: StdMutex::new(TokenUsage::default()),
+                refresh_lock: tokio::sync::Mutex::new(()),
+            }),
+        }
+    }
+
+    /// Open a connection to the REST API so that the first real
+    /// request doesn't have to wait for connection setup and the TLS
+    /// handshake. The connection is kept in the pool of
+    /// [`client`](Self::client).
     ///
-    /// This method is mutable because the installation access token
-    /// must be periodically refreshed.
-    pub async fn header(&mut self) -> Result<HeaderMap, AuthError> {
+    /// Only the connection matters, so the response is ignored.
+    pub async fn warm_up(&self) -> Result<(), AuthError> {
+        self.params.warm_up(&self.client).await
+    }
+
+    /// Fetch the token now if it hasn't been fetched yet or needs
+    /// refreshing. Call this on a [`lazy`](Self::lazy) token to find
+    /// out early whether the parameters are valid.
+    pub async fn connect(&self) -> Result<(), AuthError> {
+        self.refresh().await
+    }
+
+    /// Revoke the current token with `DELETE /installation/token`, so
+    /// that it can't be used even if it leaked. Does nothing if no
+    /// token has been fetched or it has expired. A new token is fetched
+    /// the next time one is needed.
+    pub async fn revoke(&self) -> Result<(), AuthError> {
+        let _guard = self.shared.refresh_lock.lock().await;
+        let current = self.snapshot();
+        if current.expires_at <= self.params.now() {
+            return Ok(());
+        }
+        let request = self
+            .client
+            .delete(format!("{}/installation/token", self.params.api_base()))
+            .header("Accept", self.params.accept(GITHUB_JSON))
+            .header("Authorization", format!("token {}", current.token()));
+        let response = hooks::send(
+            &self.params.hooks,
+            &self.params.extra_headers,
+            request,
+        )
+        .await?;
+        check_status(&self.params, response).await?;
+        self.set_current(CurrentToken::new(
+            String::new(),
+            time::UNIX_EPOCH,
+            time::UNIX_EPOCH,
+        ));
+        info!("revoked installation token");
+        Ok(())
+    }
+
+    /// Get the installation access token itself, refreshing it if
+    /// necessary.
+    pub async fn token(&self) -> Result<String, AuthError> {
+        Ok(self.current().await?.token().to_string())
+    }
+
+    /// Like [`token`](Self::token), but return the token wrapped in a
+    /// [`SecretString`](secrecy::SecretString).
+    #[cfg(feature = "secrecy")]
+    pub async fn secret_token(
+        &self,
+    ) -> Result<secrecy::SecretString, AuthError> {
+        Ok(self.current().await?.token.clone())
+    }
+
+    /// Get the time the current token expires.
+    pub fn expires_at(&self) -> time::SystemTime {
+        self.shared.current.lock().unwrap().expires_at
+    }
+
+    /// Get the installation the token belongs to, as it was when the
+    /// token was last fetched. This is only available if
+    /// [`fetch_installation`](GithubAuthParams::fetch_installation) is
+    /// set, and once the token has been fetched.
+    pub fn installation(&self) -> Option<app::Installation> {
+        self.shared.installation.lock().unwrap().clone()
+    }
+
+    /// Get the records of the [`audit_log`](GithubAuthParams::audit_log)
+    /// the token's parameters were created with, oldest first. This is
+    /// empty if there is no audit log.
+    pub fn audit_log(&self) -> Vec<audit::TokenIssuance> {
+        self.params
+            .audit_log
+            .as_ref()
+            .map(|log| log.entries())
+            .unwrap_or_default()
+    }
+
+    /// Get the API endpoints the token is used with.
+    pub fn endpoints(&self) -> &Endpoints {
+        &self.params.endpoints
+    }
+
+    /// Whether the current token has expired. This doesn't take
+    /// [`refresh_safety_margin`](Self::refresh_safety_margin) into
+    /// account.
+    pub fn is_expired(&self) -> bool {
+        self.expires_at() <= self.params.now()
+    }
+
+    /// Get how long the current token remains valid, or zero if it has
+    /// expired.
+    pub fn expires_in(&self) -> time::Duration {
+        self.expires_at()
+            .duration_since(self.params.now())
+            .unwrap_or_default()
+    }
+
+    /// Get an HTTP authentication header for the installation access
+    /// token, refreshing the token if necessary.
+    pub async fn header(&self) -> Result<HeaderMap, AuthError> {
+        let mut headers = HeaderMap::new();
+        self.apply_auth(&mut headers).await?;
+        Ok(headers)
+    }
+
+    /// Like [`header`](Self::header), but wrapped in [`AuthHeaders`],
+    /// which refuses to hand out the headers once the token in them has
+    /// expired. Use this where headers might be kept around instead of
+    /// being fetched for each request.
+    pub async fn auth_headers(&self) -> Result<AuthHeaders, AuthError> {
         self.refresh().await?;
+        let (value, expires_at) = self.cached_authorization_until()?;
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, value);
+        Ok(AuthHeaders::new(headers, expires_at))
+    }
+
+    /// Insert the `Authorization` header for the installation access
+    /// token into `headers`, replacing any existing one and refreshing
+    /// the token if necessary. Unlike [`header`](Self::header), this
+    /// doesn't allocate a new map.
+    pub async fn apply_auth(
+        &self,
+        headers: &mut HeaderMap,
+    ) -> Result<(), AuthError> {
+        headers.insert(AUTHORIZATION, self.authorization().await?);
+        Ok(())
+    }
+
+    /// Like [`header`](Self::header), but don't wait for a refresh
+    /// while the current token is still valid. If it needs refreshing,
+    /// it is used anyway and a refresh is started in the background,
+    /// so that latency-critical handlers such as webhook receivers
+    /// never wait for GitHub. Only waits if there is no valid token,
+    /// for example on first use.
+    ///
+    /// Must be called from within a Tokio runtime. Failed background
+    /// refreshes are logged, and retried on the next call.
+    pub async fn header_nonblocking(&self) -> Result<HeaderMap, AuthError> {
+        if self.needs_refresh() && !self.is_expired() {
+            // Only start a refresh if none is in progress.
+            if self.shared.refresh_lock.try_lock().is_ok() {
+                let token = self.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = token.refresh().await {
+                        warn!("background token refresh failed: {}", err);
+                    }
+                });
+            }
+        } else {
+            self.refresh().await?;
+        }
         let mut headers = HeaderMap::new();
-        let val = format!("token {}", self.token);
-        headers.insert("Authorization", val.parse()?);
+        headers.insert(AUTHORIZATION, self.cached_authorization()?);
         Ok(headers)
     }
 
+    async fn authorization(&self) -> Result<HeaderValue, AuthError> {
+        self.refresh().await?;
+        self.cached_authorization()
+    }
+
+    /// The `Authorization` header value for the current token, without
+    /// refreshing it.
+    fn cached_authorization(&self) -> Result<HeaderValue, AuthError> {
+        Ok(self.cached_authorization_until()?.0)
+    }
+
+    /// Like [`cached_authorization`](Self::cached_authorization), but
+    /// also get when the token in the header expires.
+    fn cached_authorization_until(
+        &self,
+    ) -> Result<(HeaderValue, time::SystemTime), AuthError> {
+        self.record_use();
+        // Only the header value is copied, which doesn't allocate.
+        let current = self.shared.current.lock().unwrap();
+        telemetry::record_token_use(
+            current.fetched_at.elapsed().unwrap_or_default(),
+        );
+        Ok((current.header_value()?, current.expires_at))
+    }
+
+    /// Refresh the token if necessary and get a copy of it.
+    pub(crate) async fn current(&self) -> Result<CurrentToken, AuthError> {
+        self.refresh().await?;
+        Ok(self.snapshot())
+    }
+
+    fn set_current(&self, current: CurrentToken) {
+        let info = current.info();
+        *self.shared.current.lock().unwrap() = current;
+        self.shared.updates.send_replace(info);
+    }
+
+    /// Replace the current token with `token`, valid until
+    /// `expires_at`, without any requests to GitHub. Use this to seed
+    /// tokens in tests, or to use tokens obtained some other way, such
+    /// as from a token broker. The token is still refreshed from GitHub
+    /// once it needs refreshing.
+    ///
+    /// Enabled by the `inject-token` feature.
+    #[cfg(feature = "inject-token")]
+    pub fn set_raw_token(
+        &self,
+        token: impl Into<String>,
+        expires_at: time::SystemTime,
+    ) {
+        self.set_current(CurrentToken::new(
+            token.into(),
+            expires_at,
+            self.params.now(),
+        ));
+    }
+
+    /// Get a receiver that sees the current token, and is notified
+    /// whenever it is refreshed or revoked. Use this to pass new tokens
+    /// on to other components, such as a git credential cache, without
+    /// polling. The token is not refreshed by subscribing.
+    pub fn subscribe(&self) -> tokio::sync::watch::Receiver<TokenInfo> {
+        self.shared.updates.subscribe()
+    }
+
+    fn snapshot(&self) -> CurrentToken {
+        self.shared.current.lock().unwrap().clone()
+    }
+
     fn needs_refresh(&self) -> bool {
-        let expires_at = self.expires_at - self.refresh_safety_margin;
-        expires_at <= Utc::now()
+        match self.params.now().checked_add(self.refresh_safety_margin) {
+            Some(deadline) => self.expires_at() <= deadline,
+            None => true,
+        }
+    }
+
+    async fn refresh(&self) -> Result<(), AuthError> {
+        match self.refresh_if(Self::needs_refresh).await {
+            Err(err) if self.can_serve_stale(&err) => {
+                warn!(
+                    "token refresh failed, using the current token until it \
+                     expires in {:?}: {}",
+                    self.expires_in(),
+                    err
+                );
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    /// Whether the current token may still be used after a refresh
+    /// failed with `err`, see [`GithubAuthParams::stale_if_error`].
+    fn can_serve_stale(&self, err: &AuthError) -> bool {
+        err.kind() == AuthErrorKind::ServerError
+            && self
+                .params
+                .stale_if_error
+                .is_some_and(|min| self.is_valid_for(min))
+    }
+
+    /// Make sure the token stays valid for at least `duration`,
+    /// refreshing it now if it would expire sooner.
+    ///
+    /// Call this before a long operation that can't refresh the token
+    /// partway through. New tokens are valid for an hour, so a longer
+    /// `duration` can't be guaranteed; the token is refreshed and this
+    /// still succeeds.
+    pub async fn ensure_valid_for(
+        &self,
+        duration: time::Duration,
+    ) -> Result<(), AuthError> {
+        self.refresh_if(|token| !token.is_valid_for(duration)).await
+    }
+
+    fn is_valid_for(&self, duration: time::Duration) -> bool {
+        match self.params.now().checked_add(duration) {
+            Some(deadline) => self.expires_at() > deadline,
+            None => false,
+        }
+    }
+
+    /// Like [`header`](Self::header), but make sure the token is still
+    /// valid at `deadline`, refreshing it now if it would expire sooner.
+    ///
+    /// Use this for long uploads or paginated crawls that must not see
+    /// the token expire. Fails with [`AuthError::ExpiresBeforeDeadline`]
+    /// if even a new token would expire first, since new tokens are
+    /// only valid for an hour.
+    pub async fn header_with_deadline(
+        &self,
+        deadline: time::SystemTime,
+    ) -> Result<HeaderMap, AuthError> {
+        self.refresh_if(|token| token.expires_at() <= deadline)
+            .await?;
+        if self.expires_at() <= deadline {
+            return Err(AuthError::ExpiresBeforeDeadline);
+        }
+        self.header().await
+    }
+
+    async fn refresh_if(
+        &self,
+        needed: impl Fn(&Self) -> bool,
+    ) -> Result<(), AuthError> {
+        if needed(self) {
+            let _guard = self.shared.refresh_lock.lock().await;
+            // Another clone may have refreshed while we waited.
+            if needed(self) {
+                self.load_from_cache().await;
+            }
+            if needed(self) {
+                info!("refreshing installation token");
+                self.fetch().await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Replace the current token with the one in
+    /// [`token_cache`](GithubAuthParams::token_cache), if there is one
+    /// that expires later. A failing cache is logged and otherwise
+    /// treated as empty, so that a token is minted instead.
+    async fn load_from_cache(&self) {
+        let Some(cache) = &self.params.token_cache else {
+            return;
+        };
+        let key = token_cache::cache_key(&self.params);
+        match cache.load(&key).await {
+            Ok(Some(info)) if info.expires_at > self.expires_at() => {
+                info!("loaded installation token from cache");
+                self.set_current(CurrentToken::new(
+                    info.token,
+                    info.expires_at,
+                    info.fetched_at,
+                ));
+            }
+            Ok(_) => {}
+            Err(err) => warn!("failed to load token from cache: {}", err),
+        }
+    }
+
+    /// Store the current token in
+    /// [`token_cache`](GithubAuthParams::token_cache), so that other
+    /// processes can use it instead of minting their own. Does nothing
+    /// if no cache is set or the token has expired.
+    ///
+    /// Call this before a short-lived process exits, for example at
+    /// the end of an AWS Lambda invocation.
+    pub async fn persist(&self) -> Result<(), AuthError> {
+        let Some(cache) = &self.params.token_cache else {
+            return Ok(());
+        };
+        let current = self.snapshot();
+        if current.expires_at <= self.params.now() {
+            return Ok(());
+        }
+        let key = token_cache::cache_key(&self.params);
+        cache.store(&key, &current.info()).await
     }
 
-    async fn refresh(&mut self) -> Result<(), AuthError> {
-        if self.needs_refresh() {
-            info!("refreshing installation token");
-            let raw =
-                get_installation_token(&self.client, &self.params).await?;
-            self.token = raw.token;
-            self.expires_at = raw.expires_at;
+    /// Mint a new token because GitHub rejected the one sent as
+    /// `rejected`, unless another request rejected with it has already
+    /// replaced it while this one waited for the lock.
+    async fn replace_rejected(
+        &self,
+        rejected: &HeaderValue,
+    ) -> Result<(), AuthError> {
+        let _guard = self.shared.refresh_lock.lock().await;
+        let current = self.shared.current.lock().unwrap().authorization.clone();
+        if current.as_ref() != Some(rejected) {
+            return Ok(());
+        }
+        self.fetch().await
+    }
+
+    async fn fetch(&self) -> Result<(), AuthError> {
+        let raw = get_installation_token(&self.client, &self.params).await?;
+        #[cfg(feature = "chaos")]
+        let raw = match self
+            .params
+            .hooks
+            .as_ref()
+            .and_then(|hooks| hooks.failure_injector())
+        {
+            Some(injector) if injector.expire_immediately() => {
+                RawInstallationAccessToken {
+                    expires_at: self.params.now(),
+                    ..raw
+                }
+            }
+            _ => raw,
+        };
+        self.set_current(CurrentToken::new(
+            raw.token,
+            raw.expires_at,
+            self.params.now(),
+        ));
+        if self.params.fetch_installation {
+            // The token is usable without the installation, so a
+            // failure only leaves the previous record in place.
+            match app::get_installation(
+                &self.client,
+                &self.params,
+                self.params.installation_id,
+            )
+            .await
+            {
+                Ok(installation) => {
+                    *self.shared.installation.lock().unwrap() =
+                        Some(installation);
+                }
+                Err(err) => warn!("failed to fetch installation: {}", err),
+            }
         }
         Ok(())
     }
 }
 
+#[cfg(test)]
+impl InstallationAccessToken {
+    /// Create a token that is valid for `valid_for` without any
+    /// network access.
+    pub(crate) fn for_test(token: &str, valid_for: time::Duration) -> Self {
+        let now = time::SystemTime::now();
+        let mut token = InstallationAccessToken::from_parts(
+            reqwest::Client::new(),
+            GithubAuthParams::default(),
+            CurrentToken::new(token.into(), now + valid_for, now),
+        );
+        token.refresh_safety_margin = time::Duration::ZERO;
+        token
+    }
+}
+
 /// Input parameters for authenticating as a GitHub app. This is used
 /// to get an installation access token.
+///
+/// Create parameters with [`builder`](Self::builder), or start from
+/// [`Default::default`] and set fields. They can't be created with a
+/// struct literal, so that new options can be added without a major
+/// release.
 #[derive(Clone, Default)]
+#[non_exhaustive]
 pub struct GithubAuthParams {
     /// User agent set for all requests to GitHub. The API requires
     /// that a user agent is set:
@@ -205,11 +1460,42 @@ pub struct GithubAuthParams {
     /// of your application".
     pub user_agent: String,
 
+    /// Whether to append `github-app-auth/<version>` to the user agent,
+    /// which helps GitHub support identify requests made through this
+    /// library.
+    pub append_crate_version: bool,
+
     /// Private key used to sign access token requests. You can
     /// generate a private key at the bottom of the application's
     /// settings page.
     pub private_key: Vec<u8>,
 
+    /// Identifier of the private key, sent as the `kid` header of app
+    /// JWTs.
+    pub key_id: Option<String>,
+
+    /// Further private keys, tried in order if GitHub rejects a JWT
+    /// signed with the private key. While rotating keys, set the new
+    /// key as the private key and the old one as a fallback until the
+    /// new key is registered with the app.
+    pub fallback_keys: Vec<AppKey>,
+
+    /// Private key that can be replaced while in use. If set, it is
+    /// used instead of [`private_key`](Self::private_key), which can be
+    /// left empty.
+    pub reloadable_key: Option<ReloadableKey>,
+
+    /// Signer for app JWTs, for keys that can't be exported such as
+    /// ones in a hardware security module. If set, it is used instead
+    /// of the private key.
+    pub signer: Option<Arc<dyn JwtSigner>>,
+
+    /// Private key held as a secret, which is zeroed when dropped and
+    /// can't be printed by accident. If set, it is used instead of
+    /// [`private_key`](Self::private_key), which can be left empty.
+    #[cfg(feature = "secrecy")]
+    pub secret_private_key: Option<secrecy::SecretSlice<u8>>,
+
     /// GitHub application installation ID. To find this value you can
     /// look at the app installation's configuration URL.
     ///
@@ -227,12 +1513,240 @@ pub struct GithubAuthParams {
     /// GitHub application ID. You can find this in the application
     /// settings page on GitHub under "App ID".
     pub app_id: u64,
+
+    /// The app's slug, the name in its URL such as `my-deploy-bot`. If
+    /// it is set, [`resolve_app_id`](Self::resolve_app_id) looks up
+    /// [`app_id`](Self::app_id) from it.
+    pub app_slug: Option<String>,
+
+    /// Names of repositories (without the owner) that the token is
+    /// restricted to. If empty, the token can access every repository
+    /// the installation can.
+    pub repositories: Vec<String>,
+
+    /// Permissions that the token is restricted to, mapping permission
+    /// names such as `"contents"` to access levels such as `"read"`.
+    /// If empty, the token has all of the installation's permissions.
+    pub permissions: BTreeMap<String, String>,
+
+    /// Extra claims added to every app JWT, for example to correlate
+    /// GitHub's audit log with your own. The claims `iat`, `exp`, `iss`
+    /// and `jti` are set by this crate and can't be replaced.
+    pub jwt_claims: BTreeMap<String, serde_json::Value>,
+
+    /// Whether to also fetch the installation record whenever an
+    /// installation token is fetched, for
+    /// [`InstallationAccessToken::installation`]. This costs one more
+    /// request per refresh.
+    pub fetch_installation: bool,
+
+    /// Whether failed requests keep the response in the error, as
+    /// [`AuthError::ResponseError`], to help diagnose proxies that
+    /// answer with HTML error pages. The body is truncated and tokens
+    /// in it are scrubbed, but it may still contain other details of
+    /// your setup, so only enable this for debugging.
+    pub capture_error_bodies: bool,
+
+    /// How far the local clock may be off from GitHub's, according to
+    /// the `Date` header of responses to requests authenticated as the
+    /// app, before failing with [`AuthError::ClockSkew`]. If `None`,
+    /// a skew of more than 30 seconds is only logged as a warning.
+    ///
+    /// Either way, a JWT that GitHub rejects because of the skew is
+    /// retried once with GitHub's time. The skew is checked against
+    /// this after that retry, so a skewed clock fails with
+    /// [`AuthError::ClockSkew`] rather than `401 Unauthorized`, even
+    /// if the retry was accepted.
+    pub max_clock_skew: Option<time::Duration>,
+
+    /// Refuse to mint more than this many tokens per minute for the
+    /// installation, failing with [`AuthError::MintRateExceeded`] and
+    /// logging an error instead. Tokens are valid for an hour, so many
+    /// mints point to a bug such as a refresh loop. The count is shared
+    /// by all tokens in the process for the same app and installation,
+    /// unless they set a separate [`mint_limiter`](Self::mint_limiter).
+    pub max_mints_per_minute: Option<u32>,
+
+    /// Where mints are counted for
+    /// [`max_mints_per_minute`](Self::max_mints_per_minute). By default
+    /// one limiter is shared by the whole process.
+    pub mint_limiter: Option<Arc<MintLimiter>>,
+
+    /// Keep using the current token if refreshing it fails with a
+    /// server error, such as during a GitHub incident, as long as it is
+    /// still valid for at least this long. The failure is logged and
+    /// the refresh is tried again on the next use. Tokens are only
+    /// refreshed within the
+    /// [`refresh_safety_margin`](InstallationAccessToken::refresh_safety_margin)
+    /// of expiring, so this needs a larger margin to have an effect.
+    pub stale_if_error: Option<time::Duration>,
+
+    /// Further media types to accept, such as
+    /// `"application/vnd.github.machine-man-preview+json"`, for preview
+    /// APIs on older GitHub Enterprise Server versions. They are added
+    /// to the `Accept` header of the token request and of the requests
+    /// sent by the request helpers.
+    pub previews: Vec<String>,
+
+    /// Base URLs of the API. Defaults to github.com; for GitHub
+    /// Enterprise Server use [`Endpoints::enterprise_server`].
+    pub endpoints: Endpoints,
+
+    /// URL of a proxy to send all requests through.
+    pub proxy: Option<String>,
+
+    /// Timeout for each request, from connecting until the response
+    /// body has been read. By default there is no timeout.
+    pub timeout: Option<time::Duration>,
+
+    /// Further settings of the HTTP client.
+    pub client_options: ClientOptions,
+
+    /// When failed requests are retried. By default only rate-limited
+    /// requests are.
+    pub retry_policy: RetryPolicy,
+
+    /// Callbacks for every request sent with these parameters.
+    pub hooks: Option<Arc<dyn HttpHooks>>,
+
+    /// Headers added to every request sent with these parameters,
+    /// including token requests, such as an API key for a gateway.
+    pub extra_headers: ExtraHeaders,
+
+    /// Where to record attempts to mint tokens with these parameters.
+    /// Several parameters can share a log.
+    pub audit_log: Option<Arc<AuditLog>>,
+
+    /// An external store that tokens are loaded from before minting new
+    /// ones, see [`token_cache`]. Tokens are only stored in it by
+    /// [`InstallationAccessToken::persist`].
+    pub token_cache: Option<Arc<dyn TokenCache>>,
+
+    /// The PEM keys above, parsed when they are first used.
+    parsed_keys: signer::ParsedKeys,
+}
+
+impl GithubAuthParams {
+    fn api_base(&self) -> &str {
+        self.endpoints.rest_base()
+    }
+
+    /// The current time, which is the simulated time if the hooks are
+    /// a [`Simulation`](simulation::Simulation).
+    fn now(&self) -> time::SystemTime {
+        hooks::now(&self.hooks)
+    }
+
+    /// Open a connection to the REST API with `client`, see
+    /// [`InstallationAccessToken::warm_up`].
+    async fn warm_up(&self, client: &reqwest::Client) -> Result<(), AuthError> {
+        let request = client
+            .head(self.api_base())
+            .header("Accept", self.accept(GITHUB_JSON));
+        hooks::send(&self.hooks, &self.extra_headers, request).await?;
+        Ok(())
+    }
+
+    /// The `Accept` header for `media_type` with the
+    /// [`previews`](Self::previews) added.
+    fn accept(&self, media_type: &str) -> String {
+        let mut accept = media_type.to_string();
+        for preview in &self.previews {
+            accept.push_str(", ");
+            accept.push_str(preview);
+        }
+        accept
+    }
+
+    /// The private key or signer followed by the fallback keys.
+    fn signing_keys(&self) -> Vec<SigningKey<'_>> {
+        let primary = SigningKey {
+            id: self.key_id.as_deref(),
+            material: match &self.signer {
+                Some(signer) => KeyMaterial::Signer(signer.as_ref()),
+                None => KeyMaterial::Pem(self.private_key_pem()),
+            },
+        };
+        std::iter::once(primary)
+            .chain(self.fallback_keys.iter().map(|key| SigningKey {
+                id: key.id.as_deref(),
+                material: KeyMaterial::Pem(Cow::Borrowed(&key.private_key)),
+            }))
+            .collect()
+    }
+
+    fn private_key_pem(&self) -> Cow<'_, [u8]> {
+        if let Some(key) = &self.reloadable_key {
+            return Cow::Owned(key.get());
+        }
+        #[cfg(feature = "secrecy")]
+        if let Some(key) = &self.secret_private_key {
+            use secrecy::ExposeSecret;
+            return Cow::Borrowed(key.expose_secret());
+        }
+        Cow::Borrowed(&self.private_key)
+    }
+}
+
+/// An additional private key of an app, see
+/// [`GithubAuthParams::fallback_keys`].
+#[derive(Clone, Default)]
+pub struct AppKey {
+    /// Identifier of the key, sent as the `kid` header of app JWTs.
+    pub id: Option<String>,
+
+    /// The PEM-encoded private key.
+    pub private_key: Vec<u8>,
+}
+
+// Written by hand so that the key doesn't end up in logs.
+impl fmt::Debug for AppKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AppKey")
+            .field("id", &self.id)
+            .field("private_key", &"<redacted>")
+            .finish()
+    }
+}
+
+/// A private key that can be replaced while parameters holding it are
+/// in use, for example after fetching a rotated key from a secret
+/// store. Clones share the key.
+#[derive(Clone, Default)]
+pub struct ReloadableKey(Arc<StdRwLock<Vec<u8>>>);
+
+impl ReloadableKey {
+    /// Create a reloadable key from a PEM-encoded private key.
+    pub fn new(private_key: Vec<u8>) -> ReloadableKey {
+        ReloadableKey(Arc::new(StdRwLock::new(private_key)))
+    }
+
+    /// Replace the key. JWTs created from now on are signed with the
+    /// new key.
+    pub fn set(&self, private_key: Vec<u8>) {
+        *self.0.write().unwrap() = private_key;
+    }
+
+    pub(crate) fn get(&self) -> Vec<u8> {
+        self.0.read().unwrap().clone()
+    }
+}
+
+// Written by hand so that the key doesn't end up in logs.
+impl fmt::Debug for ReloadableKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ReloadableKey(<redacted>)")
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use chrono::TimeZone;
+    use proptest::prelude::*;
+
+    fn secs(secs: u64) -> time::Duration {
+        time::Duration::from_secs(secs)
+    }
 
     #[test]
     fn test_raw_installation_access_token_parse() {
@@ -240,31 +1754,393 @@ mod tests {
             "token": "v1.1f699f1069f60xxx",
             "expires_at": "2016-07-11T22:14:10Z"
             }"#;
-        let token =
-            serde_json::from_str::<RawInstallationAccessToken>(resp).unwrap();
+        let token = RawInstallationAccessToken::parse(resp.as_bytes()).unwrap();
         assert_eq!(
             token,
             RawInstallationAccessToken {
                 token: "v1.1f699f1069f60xxx".into(),
-                expires_at: Utc.ymd(2016, 7, 11).and_hms(22, 14, 10),
+                expires_at: time::UNIX_EPOCH
+                    + time::Duration::from_secs(1468275250),
             }
         );
     }
 
+    proptest! {
+        #[test]
+        fn test_raw_installation_access_token_parse_any(
+            body in proptest::collection::vec(any::<u8>(), 0..256),
+        ) {
+            let _ = RawInstallationAccessToken::parse(&body);
+        }
+
+        #[test]
+        fn test_raw_installation_access_token_parse_fields(
+            token_char in any::<char>(),
+            token_len in 0usize..100_000,
+            expires_at in concat!(
+                "[0-9]{4}-[0-9]{2}-[0-9]{2}T[0-9]{2}:[0-9]{2}:[0-9]{2}",
+                "(\\.[0-9]{1,9})?(Z|[+-][0-9]{2}:[0-9]{2})",
+            ),
+        ) {
+            let token = token_char.to_string().repeat(token_len);
+            let body = serde_json::json!({
+                "token": token,
+                "expires_at": expires_at,
+            });
+            let parsed =
+                RawInstallationAccessToken::parse(body.to_string().as_bytes());
+            match timestamp::parse(&expires_at) {
+                Some(t) => {
+                    let parsed = parsed.unwrap();
+                    prop_assert_eq!(&parsed.token, &token);
+                    prop_assert_eq!(parsed.expires_at, t);
+                }
+                None => prop_assert!(parsed.is_err()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_jwt_claims() {
+        let mut params = GithubAuthParams {
+            app_id: 1234,
+            jwt_claims: BTreeMap::from([(
+                "deployment".to_string(),
+                serde_json::json!("eu-1"),
+            )]),
+            ..Default::default()
+        };
+        let behind = JwtClaims::new(&params, -30).unwrap();
+        let claims = JwtClaims::new(&params, 0).unwrap();
+        let json = serde_json::to_value(&claims).unwrap();
+        assert_eq!(json["iss"], 1234);
+        assert_eq!(json["deployment"], "eu-1");
+        assert_eq!(json["jti"], claims.jti.as_str());
+        assert_ne!(claims.jti, JwtClaims::new(&params, 0).unwrap().jti);
+        assert!(claims.iat - behind.iat >= 30);
+
+        params.jwt_claims.insert("iss".into(), serde_json::json!(1));
+        assert!(matches!(
+            JwtClaims::new(&params, 0),
+            Err(AuthError::ReservedClaim(name)) if name == "iss"
+        ));
+    }
+
+    #[test]
+    fn test_clock_skew() {
+        let response = |date: time::SystemTime| {
+            reqwest::Response::from(
+                http::Response::builder()
+                    .status(401)
+                    .header("date", timestamp::format_http_date(date))
+                    .body("")
+                    .unwrap(),
+            )
+        };
+        let now = time::SystemTime::now();
+        assert_eq!(clock_skew(&response(now)), None);
+        let skew = clock_skew(&response(now + secs(120))).unwrap();
+        assert!((119..=120).contains(&skew));
+        let skew = clock_skew(&response(now - secs(120))).unwrap();
+        assert!((-121..=-120).contains(&skew));
+
+        let mut params = GithubAuthParams::default();
+        assert!(check_clock(&params, &response(now + secs(120))).is_ok());
+        params.max_clock_skew = Some(secs(60));
+        assert!(check_clock(&params, &response(now + secs(30))).is_ok());
+        assert!(matches!(
+            check_clock(&params, &response(now - secs(120))),
+            Err(AuthError::ClockSkew(skew)) if skew < 0
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_expiry_in_local_time() {
+        let now = time::SystemTime::now();
+        let response = reqwest::Response::from(
+            http::Response::builder()
+                .header("date", timestamp::format_http_date(now + secs(7200)))
+                .body(format!(
+                    r#"{{"token": "t", "expires_at": "{}"}}"#,
+                    timestamp::format(now + secs(3 * 3600))
+                ))
+                .unwrap(),
+        );
+        let raw = RawInstallationAccessToken::from_response(response)
+            .await
+            .unwrap();
+        let expires_in = raw.expires_at.duration_since(now).unwrap();
+        assert!(expires_in > secs(3590) && expires_in <= secs(3601));
+    }
+
+    #[test]
+    fn test_signing_keys() {
+        let params = GithubAuthParams {
+            private_key: b"new".to_vec(),
+            key_id: Some("2024".into()),
+            fallback_keys: vec![AppKey {
+                id: None,
+                private_key: b"old".to_vec(),
+            }],
+            ..Default::default()
+        };
+        let keys = params.signing_keys();
+        assert_eq!(keys.len(), 2);
+        assert!(matches!(
+            &keys[0].material,
+            KeyMaterial::Pem(pem) if **pem == *b"new"
+        ));
+        assert_eq!(jwt_header(&keys[0]).kid.as_deref(), Some("2024"));
+        assert!(matches!(
+            &keys[1].material,
+            KeyMaterial::Pem(pem) if **pem == *b"old"
+        ));
+        assert_eq!(jwt_header(&keys[1]).kid, None);
+
+        let key = ReloadableKey::new(b"loaded".to_vec());
+        let params = GithubAuthParams {
+            reloadable_key: Some(key.clone()),
+            ..params
+        };
+        assert_eq!(*params.private_key_pem(), *b"loaded");
+        key.set(b"reloaded".to_vec());
+        assert_eq!(*params.private_key_pem(), *b"reloaded");
+    }
+
+    #[test]
+    fn test_access_token_request_serialize() {
+        let permissions =
+            BTreeMap::from([("contents".to_string(), "read".to_string())]);
+        let request = AccessTokenRequest {
+            repositories: &[],
+            permissions: &permissions,
+        };
+        assert_eq!(
+            serde_json::to_string(&request).unwrap(),
+            r#"{"permissions":{"contents":"read"}}"#
+        );
+    }
+
     #[test]
     fn test_needs_refresh() {
         use std::thread::sleep;
-        let mut token = InstallationAccessToken {
-            client: reqwest::Client::new(),
-            token: "myToken".into(),
-            expires_at: Utc::now() + Duration::seconds(2),
-            params: GithubAuthParams::default(),
-            refresh_safety_margin: Duration::seconds(0),
-        };
+        let mut token = InstallationAccessToken::for_test("myToken", secs(2));
         assert!(!token.needs_refresh());
-        sleep(Duration::milliseconds(1500).to_std().unwrap());
+        sleep(time::Duration::from_millis(1500));
         assert!(!token.needs_refresh());
-        token.refresh_safety_margin = Duration::seconds(1);
+        token.refresh_safety_margin = secs(1);
         assert!(token.needs_refresh());
     }
+
+    #[test]
+    fn test_clones_share_token() {
+        let token = InstallationAccessToken::for_test("myToken", secs(3600));
+        let clone = token.clone();
+        *token.shared.current.lock().unwrap() = CurrentToken::new(
+            "newToken".into(),
+            time::UNIX_EPOCH,
+            time::UNIX_EPOCH,
+        );
+        assert_eq!(clone.snapshot().token(), "newToken");
+    }
+
+    /// Fails to compile if the token or its futures can't be shared
+    /// between threads.
+    #[allow(dead_code)]
+    fn test_send_sync(token: &InstallationAccessToken) {
+        fn is_send_sync<T: Send + Sync>(_: &T) {}
+        fn is_send<T: Send>(_: T) {}
+        is_send_sync(token);
+        is_send_sync(&GithubAuthParams::default());
+        is_send_sync(&TokenManager::new(reqwest::Client::new()));
+        is_send(token.token());
+        is_send(token.header());
+        is_send(token.connect());
+        is_send(token.healthy());
+        is_send(token.revoke());
+        is_send(token.ensure_valid_for(secs(60)));
+        is_send(token.execute(reqwest::Request::new(
+            reqwest::Method::GET,
+            "https://api.github.com".parse().unwrap(),
+        )));
+    }
+
+    #[test]
+    fn test_accept() {
+        let mut params = GithubAuthParams::default();
+        assert_eq!(params.accept(GITHUB_JSON), GITHUB_JSON);
+        params.previews = vec![
+            "application/vnd.github.machine-man-preview+json".into(),
+            "application/vnd.github.antiope-preview+json".into(),
+        ];
+        assert_eq!(
+            params.accept(GITHUB_JSON),
+            "application/vnd.github+json, \
+             application/vnd.github.machine-man-preview+json, \
+             application/vnd.github.antiope-preview+json"
+        );
+    }
+
+    #[test]
+    fn test_error_kind() {
+        assert_eq!(
+            AuthError::KeyRejected("old".into()).kind(),
+            AuthErrorKind::InvalidKey
+        );
+        assert_eq!(AuthError::UnknownApp(1).code(), "configuration");
+        let response = reqwest::Response::from(
+            http::Response::builder().status(404).body("").unwrap(),
+        );
+        let err = AuthError::from(response.error_for_status().unwrap_err());
+        assert_eq!(err.kind(), AuthErrorKind::NotFound);
+        assert_eq!(err.kind().to_string(), "not_found");
+    }
+
+    #[tokio::test]
+    async fn test_header_with_deadline() {
+        let token = InstallationAccessToken::for_test("myToken", secs(3600));
+        let headers = token
+            .header_with_deadline(time::SystemTime::now() + secs(60))
+            .await
+            .unwrap();
+        assert_eq!(headers[AUTHORIZATION], "token myToken");
+    }
+
+    #[tokio::test]
+    async fn test_header_nonblocking() {
+        let mut token =
+            InstallationAccessToken::for_test("staleToken", secs(30));
+        token.refresh_safety_margin = secs(60);
+        // The refresh fails in the background without a key, but the
+        // token is still valid and can be used.
+        let headers = token.header_nonblocking().await.unwrap();
+        assert_eq!(headers[AUTHORIZATION], "token staleToken");
+
+        let expired = InstallationAccessToken::for_test("oldToken", secs(0));
+        assert!(expired.header_nonblocking().await.is_err());
+    }
+
+    #[cfg(feature = "inject-token")]
+    #[tokio::test]
+    async fn test_set_raw_token() {
+        let token =
+            InstallationAccessToken::lazy(GithubAuthParams::default()).unwrap();
+        let expires_at = time::SystemTime::now() + secs(3600);
+        token.set_raw_token("injectedToken", expires_at);
+        assert_eq!(token.expires_at(), expires_at);
+        assert_eq!(token.token().await.unwrap(), "injectedToken");
+    }
+
+    #[tokio::test]
+    async fn test_subscribe() {
+        let token = InstallationAccessToken::for_test("myToken", secs(3600));
+        let mut updates = token.subscribe();
+        assert_eq!(updates.borrow_and_update().token, "myToken");
+        let expires_at = time::SystemTime::now() + secs(7200);
+        token.set_current(CurrentToken::new(
+            "newToken".into(),
+            expires_at,
+            time::SystemTime::now(),
+        ));
+        updates.changed().await.unwrap();
+        let info = updates.borrow().clone();
+        assert_eq!(info.token, "newToken");
+        assert_eq!(info.expires_at, expires_at);
+        assert!(!format!("{:?}", info).contains("newToken"));
+    }
+
+    #[tokio::test]
+    async fn test_apply_auth() {
+        let token = InstallationAccessToken::for_test("myToken", secs(3600));
+        let mut headers = HeaderMap::new();
+        headers.insert("accept", "application/json".parse().unwrap());
+        headers.insert(AUTHORIZATION, "token stale".parse().unwrap());
+        token.apply_auth(&mut headers).await.unwrap();
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers[AUTHORIZATION], "token myToken");
+    }
+
+    #[tokio::test]
+    async fn test_authorization_is_cached() {
+        let token = InstallationAccessToken::for_test("myToken", secs(3600));
+        let value = token.authorization().await.unwrap();
+        assert_eq!(value, "token myToken");
+        assert!(value.is_sensitive());
+
+        // A new token gets a new header value.
+        *token.shared.current.lock().unwrap() = CurrentToken::new(
+            "newToken".into(),
+            time::SystemTime::now() + secs(3600),
+            time::SystemTime::now(),
+        );
+        assert_eq!(token.authorization().await.unwrap(), "token newToken");
+
+        // Tokens that can't be put in a header fail when they are used.
+        *token.shared.current.lock().unwrap() = CurrentToken::new(
+            "bad\ntoken".into(),
+            time::SystemTime::now() + secs(3600),
+            time::SystemTime::now(),
+        );
+        assert!(matches!(
+            token.authorization().await,
+            Err(AuthError::InvalidHeaderValue(_))
+        ));
+    }
+
+    #[test]
+    fn test_is_valid_for() {
+        let token = InstallationAccessToken::for_test("myToken", secs(300));
+        assert!(token.is_valid_for(time::Duration::from_secs(60)));
+        assert!(!token.is_valid_for(time::Duration::from_secs(600)));
+        assert!(!token.is_valid_for(time::Duration::MAX));
+        assert!(!token.is_expired());
+        assert!(token.expires_in() > time::Duration::from_secs(240));
+    }
+
+    #[test]
+    fn test_lazy() {
+        let token =
+            InstallationAccessToken::lazy(GithubAuthParams::default()).unwrap();
+        assert!(token.needs_refresh());
+        assert_eq!(token.expires_at(), time::UNIX_EPOCH);
+        assert!(token.is_expired());
+        assert_eq!(token.expires_in(), time::Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_token_cache() {
+        let cache = Arc::new(token_cache::MemoryTokenCache::default());
+        let params = GithubAuthParams::builder()
+            .app_id(1)
+            .installation_id(2)
+            .token_cache(cache.clone())
+            .build();
+        let key = token_cache::cache_key(&params);
+        let now = time::SystemTime::now();
+        let cached = TokenInfo {
+            token: "cached".into(),
+            expires_at: now + time::Duration::from_secs(3600),
+            fetched_at: now,
+        };
+        cache.store(&key, &cached).await.unwrap();
+        // The cached token is used without minting one.
+        let token = InstallationAccessToken::lazy_with_client(
+            reqwest::Client::new(),
+            params,
+        );
+        assert_eq!(token.token().await.unwrap(), "cached");
+
+        let newer = InstallationAccessToken::from_parts(
+            reqwest::Client::new(),
+            (*token.params).clone(),
+            CurrentToken::new(
+                "newer".into(),
+                now + time::Duration::from_secs(7200),
+                now,
+            ),
+        );
+        newer.persist().await.unwrap();
+        let stored = cache.load(&key).await.unwrap().unwrap();
+        assert_eq!(stored.token, "newer");
+    }
 }
@@ -0,0 +1,807 @@
+//! Managing installation access tokens for many installations, and
+//! optionally many apps, from one place.
+
+use crate::{
+    app, AuthError, GithubAuthParams, InstallationAccessToken, TokenUsage,
+};
+use futures_util::future::join_all;
+use futures_util::stream::{self, StreamExt};
+use reqwest::header::HeaderMap;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+pub(crate) type TokenSlot = Arc<Mutex<Option<InstallationAccessToken>>>;
+
+struct CacheEntry<V> {
+    value: V,
+    last_used: Instant,
+    /// The key of the entry in [`Cache::by_use`].
+    use_id: u64,
+}
+
+impl<V> CacheEntry<V> {
+    fn is_idle(&self, policy: &CachePolicy) -> bool {
+        policy.idle_timeout.is_some_and(|idle_timeout| {
+            self.last_used.elapsed() >= idle_timeout
+        })
+    }
+}
+
+/// Values kept according to a [`CachePolicy`], such as token slots or
+/// installation IDs that were looked up.
+///
+/// Lookups take logarithmic time however many entries there are: the
+/// least recently used entry is found through `by_use`, and an idle
+/// entry is only dropped when it is looked up or by
+/// [`evict`](Self::evict).
+pub(crate) struct Cache<K, V> {
+    entries: HashMap<K, CacheEntry<V>>,
+    /// Keys ordered by when their entries were last used, oldest first.
+    by_use: BTreeMap<u64, K>,
+    next_use_id: u64,
+}
+
+impl<K: Clone + Eq + Hash, V: Clone> Cache<K, V> {
+    pub(crate) fn new() -> Self {
+        Cache {
+            entries: HashMap::new(),
+            by_use: BTreeMap::new(),
+            next_use_id: 0,
+        }
+    }
+
+    /// Get the value for `key` and mark it as used, unless it has been
+    /// idle for longer than the policy allows.
+    pub(crate) fn get(&mut self, policy: &CachePolicy, key: &K) -> Option<V> {
+        if self.entries.get(key)?.is_idle(policy) {
+            self.remove(key);
+            return None;
+        }
+        let use_id = self.next_use_id();
+        let entry = self.entries.get_mut(key)?;
+        self.by_use.remove(&entry.use_id);
+        self.by_use.insert(use_id, key.clone());
+        entry.use_id = use_id;
+        entry.last_used = Instant::now();
+        Some(entry.value.clone())
+    }
+
+    /// Get the value for `key`, inserting `value()` if there is none,
+    /// and mark it as used. The least recently used entries beyond the
+    /// policy's limit are evicted.
+    pub(crate) fn get_or_insert_with(
+        &mut self,
+        policy: &CachePolicy,
+        key: K,
+        value: impl FnOnce() -> V,
+    ) -> V {
+        if let Some(value) = self.get(policy, &key) {
+            return value;
+        }
+        let value = value();
+        let use_id = self.next_use_id();
+        self.by_use.insert(use_id, key.clone());
+        self.entries.insert(
+            key,
+            CacheEntry {
+                value: value.clone(),
+                last_used: Instant::now(),
+                use_id,
+            },
+        );
+        if let Some(max) = policy.max_installations {
+            while self.entries.len() > max.max(1) {
+                let Some((_, oldest)) = self.by_use.pop_first() else {
+                    break;
+                };
+                self.entries.remove(&oldest);
+            }
+        }
+        value
+    }
+
+    fn next_use_id(&mut self) -> u64 {
+        self.next_use_id += 1;
+        self.next_use_id
+    }
+
+    fn remove(&mut self, key: &K) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.by_use.remove(&entry.use_id);
+        }
+    }
+
+    /// Remove the entry for `key` if `matches` its value, such as the
+    /// slot of a token that couldn't be minted.
+    pub(crate) fn remove_if(
+        &mut self,
+        key: &K,
+        matches: impl FnOnce(&V) -> bool,
+    ) {
+        if self
+            .entries
+            .get(key)
+            .is_some_and(|entry| matches(&entry.value))
+        {
+            self.remove(key);
+        }
+    }
+
+    /// Evict entries that have been idle for longer than the policy
+    /// allows. Only the idle entries are visited, oldest first.
+    pub(crate) fn evict(&mut self, policy: &CachePolicy) {
+        while let Some((_, key)) = self.by_use.first_key_value() {
+            if !self.entries[key].is_idle(policy) {
+                break;
+            }
+            let key = key.clone();
+            self.remove(&key);
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub(crate) fn values(&self) -> Vec<(K, V)> {
+        self.entries
+            .iter()
+            .map(|(key, entry)| (key.clone(), entry.value.clone()))
+            .collect()
+    }
+
+    pub(crate) fn drain(&mut self) -> Vec<(K, V)> {
+        self.by_use.clear();
+        self.entries
+            .drain()
+            .map(|(key, entry)| (key, entry.value))
+            .collect()
+    }
+}
+
+/// Limits on the tokens a [`TokenManager`] keeps, set with
+/// [`TokenManager::set_cache_policy`].
+///
+/// By default every token is kept, and refreshed when it is next used.
+/// Evicted tokens are minted again when they are next used; handles
+/// returned by [`TokenManager::installation`] keep working.
+///
+/// Tokens for whole installations and tokens for single repositories
+/// are limited separately, and installation IDs looked up by
+/// organization or repository are evicted along with them.
+#[derive(Clone, Debug, Default)]
+pub struct CachePolicy {
+    /// Keep at most this many tokens, evicting the least recently used.
+    pub max_installations: Option<usize>,
+
+    /// Evict tokens that haven't been used for this long. Idle tokens
+    /// are dropped by [`TokenManager::maintain`], or when they are next
+    /// looked up.
+    pub idle_timeout: Option<Duration>,
+
+    /// Whether [`TokenManager::maintain`] refreshes cached tokens
+    /// before they expire, so that they are warm when used. Otherwise
+    /// they are refreshed when they are next used.
+    pub proactive_refresh: bool,
+}
+
+/// Caches installation access tokens keyed by app and installation.
+///
+/// Some platforms operate several GitHub apps, for example one per
+/// environment. Each app's credentials are registered with
+/// [`add_app`](Self::add_app), and tokens for any of its installations
+/// are minted on first use and refreshed as needed. All tokens share
+/// one HTTP client.
+pub struct TokenManager {
+    client: reqwest::Client,
+    apps: HashMap<u64, GithubAuthParams>,
+    policy: CachePolicy,
+    tokens: Mutex<Cache<(u64, u64), TokenSlot>>,
+    /// Installation IDs looked up by app ID and lowercase org login.
+    org_installations: Mutex<Cache<(u64, String), u64>>,
+    /// Tokens scoped to one repository, keyed by app ID and lowercase
+    /// `owner/name`, with the ID of the repository's installation.
+    repo_tokens: Mutex<Cache<(u64, String), (u64, TokenSlot)>>,
+}
+
+impl TokenManager {
+    /// Create a manager with no apps whose tokens share `client`.
+    pub fn new(client: reqwest::Client) -> TokenManager {
+        TokenManager {
+            client,
+            apps: HashMap::new(),
+            policy: CachePolicy::default(),
+            tokens: Mutex::new(Cache::new()),
+            org_installations: Mutex::new(Cache::new()),
+            repo_tokens: Mutex::new(Cache::new()),
+        }
+    }
+
+    /// Create a manager for a single app, with a client built from its
+    /// parameters.
+    pub fn for_app(
+        params: GithubAuthParams,
+    ) -> Result<TokenManager, AuthError> {
+        let mut manager = TokenManager::new(params.build_client()?);
+        manager.add_app(params);
+        Ok(manager)
+    }
+
+    /// Register an app's credentials, keyed by `params.app_id`,
+    /// replacing any previous credentials for the same app.
+    /// `params.installation_id` is not used.
+    pub fn add_app(&mut self, params: GithubAuthParams) {
+        self.apps.insert(params.app_id, params);
+    }
+
+    /// Set limits on the tokens that are kept. They are applied the
+    /// next time a token is looked up.
+    pub fn set_cache_policy(&mut self, policy: CachePolicy) {
+        self.policy = policy;
+    }
+
+    /// IDs of the registered apps.
+    pub fn app_ids(&self) -> impl Iterator<Item = u64> + '_ {
+        self.apps.keys().copied()
+    }
+
+    /// Get the HTTP client shared by all tokens.
+    pub fn client(&self) -> &reqwest::Client {
+        &self.client
+    }
+
+    /// Open a connection to the REST API of each registered app. See
+    /// [`InstallationAccessToken::warm_up`].
+    pub async fn warm_up(&self) -> Result<(), AuthError> {
+        let mut apps: Vec<_> = self.apps.values().collect();
+        apps.sort_unstable_by(|a, b| a.api_base().cmp(b.api_base()));
+        apps.dedup_by(|a, b| a.api_base() == b.api_base());
+        for params in apps {
+            params.warm_up(&self.client).await?;
+        }
+        Ok(())
+    }
+
+    /// Get an HTTP authentication header for an installation of an app,
+    /// minting or refreshing its token if necessary.
+    pub async fn header_for(
+        &self,
+        app_id: u64,
+        installation_id: u64,
+    ) -> Result<HeaderMap, AuthError> {
+        self.installation(app_id, installation_id)
+            .await?
+            .header()
+            .await
+    }
+
+    /// Get the installation access token for an installation of an
+    /// app, minting or refreshing it if necessary.
+    pub async fn token_for(
+        &self,
+        app_id: u64,
+        installation_id: u64,
+    ) -> Result<String, AuthError> {
+        self.installation(app_id, installation_id)
+            .await?
+            .token()
+            .await
+    }
+
+    /// Get the installation access token for the installation of an
+    /// app on the organization `org`, looking up the installation and
+    /// minting or refreshing its token if necessary. The installation
+    /// ID is remembered, so later calls don't look it up again.
+    pub async fn token_for_org(
+        &self,
+        app_id: u64,
+        org: &str,
+    ) -> Result<String, AuthError> {
+        self.installation_for_org(app_id, org).await?.token().await
+    }
+
+    /// Get a handle to the cached token for the installation of an app
+    /// on the organization `org`. See
+    /// [`token_for_org`](Self::token_for_org).
+    pub async fn installation_for_org(
+        &self,
+        app_id: u64,
+        org: &str,
+    ) -> Result<InstallationAccessToken, AuthError> {
+        let params = self
+            .apps
+            .get(&app_id)
+            .ok_or(AuthError::UnknownApp(app_id))?;
+        let key = (app_id, org.to_lowercase());
+        let cached =
+            self.org_installations.lock().await.get(&self.policy, &key);
+        let installation_id = match cached {
+            Some(id) => id,
+            None => {
+                let id = app::get_org_installation(&self.client, params, org)
+                    .await?
+                    .id;
+                self.org_installations.lock().await.get_or_insert_with(
+                    &self.policy,
+                    key,
+                    || id,
+                )
+            }
+        };
+        self.installation(app_id, installation_id).await
+    }
+
+    /// Get an installation access token that can only access the
+    /// repository `full_name`, such as `"octo-org/octo-repo"`, looking
+    /// up the repository's installation and minting or refreshing the
+    /// token if necessary. Tokens are cached per repository, separately
+    /// from tokens for whole installations.
+    pub async fn token_for_repo(
+        &self,
+        app_id: u64,
+        full_name: &str,
+    ) -> Result<String, AuthError> {
+        self.installation_for_repo(app_id, full_name)
+            .await?
+            .token()
+            .await
+    }
+
+    /// Get a handle to the cached token for the repository `full_name`.
+    /// See [`token_for_repo`](Self::token_for_repo).
+    pub async fn installation_for_repo(
+        &self,
+        app_id: u64,
+        full_name: &str,
+    ) -> Result<InstallationAccessToken, AuthError> {
+        let params = self
+            .apps
+            .get(&app_id)
+            .ok_or(AuthError::UnknownApp(app_id))?;
+        let Some((owner, repo)) = full_name
+            .split_once('/')
+            .filter(|(owner, repo)| !owner.is_empty() && !repo.is_empty())
+        else {
+            return Err(AuthError::ConfigError(format!(
+                "repository name is not owner/name: {}",
+                full_name
+            )));
+        };
+        let key = (app_id, full_name.to_lowercase());
+        let cached = self.repo_tokens.lock().await.get(&self.policy, &key);
+        let (installation_id, slot) = match cached {
+            Some(entry) => entry,
+            None => {
+                let installation_id = app::get_repo_installation(
+                    &self.client,
+                    params,
+                    owner,
+                    repo,
+                )
+                .await?
+                .id;
+                self.repo_tokens.lock().await.get_or_insert_with(
+                    &self.policy,
+                    key.clone(),
+                    || (installation_id, TokenSlot::default()),
+                )
+            }
+        };
+        let mut guard = slot.lock().await;
+        if let Some(token) = &*guard {
+            return Ok(token.clone());
+        }
+        let params = GithubAuthParams {
+            installation_id,
+            repositories: vec![repo.to_owned()],
+            ..params.clone()
+        };
+        match InstallationAccessToken::with_client(self.client.clone(), params)
+            .await
+        {
+            Ok(token) => Ok(guard.insert(token).clone()),
+            Err(err) => {
+                // Look the repository up again when it is next used.
+                self.repo_tokens
+                    .lock()
+                    .await
+                    .remove_if(&key, |(_, cached)| Arc::ptr_eq(cached, &slot));
+                Err(err)
+            }
+        }
+    }
+
+    /// Get a handle to the cached token for an installation, minting it
+    /// first if there isn't one yet. The handle shares its state with
+    /// the cache, so refreshes through it are seen by the manager.
+    pub async fn installation(
+        &self,
+        app_id: u64,
+        installation_id: u64,
+    ) -> Result<InstallationAccessToken, AuthError> {
+        let params = self
+            .apps
+            .get(&app_id)
+            .ok_or(AuthError::UnknownApp(app_id))?;
+        let key = (app_id, installation_id);
+        let slot = self.tokens.lock().await.get_or_insert_with(
+            &self.policy,
+            key,
+            TokenSlot::default,
+        );
+        // Only this installation is locked while its token is minted.
+        let mut guard = slot.lock().await;
+        if let Some(token) = &*guard {
+            return Ok(token.clone());
+        }
+        let params = GithubAuthParams {
+            installation_id,
+            ..params.clone()
+        };
+        match InstallationAccessToken::with_client(self.client.clone(), params)
+            .await
+        {
+            Ok(token) => Ok(guard.insert(token).clone()),
+            Err(err) => {
+                self.tokens
+                    .lock()
+                    .await
+                    .remove_if(&key, |cached| Arc::ptr_eq(cached, &slot));
+                Err(err)
+            }
+        }
+    }
+
+    /// Number of tokens for whole installations currently kept,
+    /// including idle ones that haven't been evicted yet.
+    pub async fn cached_installations(&self) -> usize {
+        self.tokens.lock().await.len()
+    }
+
+    /// Number of tokens for single repositories currently kept.
+    pub async fn cached_repositories(&self) -> usize {
+        self.repo_tokens.lock().await.len()
+    }
+
+    /// Get how much the token of each cached installation has been
+    /// used, keyed by app and installation ID. Tokens for single
+    /// repositories are in [`repo_stats`](Self::repo_stats).
+    pub async fn stats(&self) -> HashMap<(u64, u64), TokenUsage> {
+        let slots = self.tokens.lock().await.values();
+        let mut stats = HashMap::new();
+        for (key, slot) in slots {
+            if let Some(token) = &*slot.lock().await {
+                stats.insert(key, token.usage());
+            }
+        }
+        stats
+    }
+
+    /// Get how much the token of each cached repository has been used,
+    /// keyed by app ID and lowercase `owner/name`.
+    pub async fn repo_stats(&self) -> HashMap<(u64, String), TokenUsage> {
+        let slots = self.repo_tokens.lock().await.values();
+        let mut stats = HashMap::new();
+        for (key, (_, slot)) in slots {
+            if let Some(token) = &*slot.lock().await {
+                stats.insert(key, token.usage());
+            }
+        }
+        stats
+    }
+
+    /// Evict idle tokens and, if the policy has
+    /// [`proactive_refresh`](CachePolicy::proactive_refresh) set,
+    /// refresh tokens that are about to expire, including tokens for
+    /// single repositories. Call this periodically, for example every
+    /// minute. Returns the installations whose tokens couldn't be
+    /// refreshed.
+    pub async fn maintain(&self) -> Vec<((u64, u64), AuthError)> {
+        self.tokens.lock().await.evict(&self.policy);
+        self.org_installations.lock().await.evict(&self.policy);
+        self.repo_tokens.lock().await.evict(&self.policy);
+        let slots = self.slots().await;
+        let mut errors = Vec::new();
+        if !self.policy.proactive_refresh {
+            return errors;
+        }
+        for (key, slot) in slots {
+            let token = slot.lock().await.clone();
+            if let Some(token) = token {
+                if let Err(err) = token.connect().await {
+                    errors.push((key, err));
+                }
+            }
+        }
+        errors
+    }
+
+    /// The slots of installation and repository tokens, keyed by app
+    /// and installation ID.
+    async fn slots(&self) -> Vec<((u64, u64), TokenSlot)> {
+        let mut slots = self.tokens.lock().await.values();
+        slots.extend(repo_slots(self.repo_tokens.lock().await.values()));
+        slots
+    }
+
+    /// Revoke every cached token, including tokens for single
+    /// repositories, and empty the cache, for example when a deployment
+    /// is rolled out. The tokens are revoked concurrently,
+    /// each failing with [`AuthError::Timeout`] if it takes longer than
+    /// `timeout`. Returns the installations whose tokens couldn't be
+    /// revoked.
+    ///
+    /// Handles returned by [`installation`](Self::installation) fetch a
+    /// new token if they are used afterwards.
+    pub async fn shutdown(
+        &self,
+        timeout: Duration,
+    ) -> Vec<((u64, u64), AuthError)> {
+        self.org_installations.lock().await.drain();
+        let mut slots = self.tokens.lock().await.drain();
+        slots.extend(repo_slots(self.repo_tokens.lock().await.drain()));
+        let results =
+            join_all(slots.into_iter().map(|(key, slot)| async move {
+                let token = slot.lock().await.clone();
+                let result = match token {
+                    Some(token) => {
+                        tokio::time::timeout(timeout, token.revoke())
+                            .await
+                            .unwrap_or(Err(AuthError::Timeout))
+                    }
+                    None => Ok(()),
+                };
+                (key, result)
+            }))
+            .await;
+        results
+            .into_iter()
+            .filter_map(|(key, result)| Some((key, result.err()?)))
+            .collect()
+    }
+
+    /// Store every cached token, including tokens for single
+    /// repositories, in the [`token_cache`](GithubAuthParams::token_cache)
+    /// of its app. See [`InstallationAccessToken::persist`]. Returns the
+    /// installations whose tokens couldn't be stored.
+    ///
+    /// With a token cache, tokens are loaded from it rather than
+    /// minted, so a manager created at the start of each invocation of
+    /// a serverless function only mints tokens that aren't cached yet.
+    pub async fn persist(&self) -> Vec<((u64, u64), AuthError)> {
+        let mut errors = Vec::new();
+        for (key, slot) in self.slots().await {
+            let token = slot.lock().await.clone();
+            if let Some(token) = token {
+                if let Err(err) = token.persist().await {
+                    errors.push((key, err));
+                }
+            }
+        }
+        errors
+    }
+
+    /// Mint tokens for a known set of installations of an app at
+    /// startup, with at most `concurrency` in flight at once, so that
+    /// the first request for each installation doesn't wait for a
+    /// token. Returns the installations whose tokens couldn't be
+    /// minted; they are tried again when they are next used.
+    pub async fn warm(
+        &self,
+        app_id: u64,
+        installation_ids: &[u64],
+        concurrency: usize,
+    ) -> Vec<(u64, AuthError)> {
+        self.mint_all(
+            installation_ids.iter().map(|&id| (app_id, id)),
+            concurrency,
+        )
+        .await
+        .into_iter()
+        .filter_map(|((_, installation_id), result)| {
+            Some((installation_id, result.err()?))
+        })
+        .collect()
+    }
+
+    /// Mint tokens for many installations, with at most `concurrency`
+    /// of them in flight at once. `ids` are pairs of app and
+    /// installation IDs, and each is returned with its own result, in
+    /// the same order. Tokens that are already cached are not minted
+    /// again.
+    pub async fn mint_all(
+        &self,
+        ids: impl IntoIterator<Item = (u64, u64)>,
+        concurrency: usize,
+    ) -> Vec<((u64, u64), Result<InstallationAccessToken, AuthError>)> {
+        stream::iter(ids)
+            .map(|(app_id, installation_id)| async move {
+                let result = self.installation(app_id, installation_id).await;
+                ((app_id, installation_id), result)
+            })
+            .buffered(concurrency.max(1))
+            .collect()
+            .await
+    }
+}
+
+/// Key repository token slots by app and installation ID.
+fn repo_slots(
+    slots: Vec<((u64, String), (u64, TokenSlot))>,
+) -> impl Iterator<Item = ((u64, u64), TokenSlot)> {
+    slots
+        .into_iter()
+        .map(|((app_id, _), (installation_id, slot))| {
+            ((app_id, installation_id), slot)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::Simulation;
+
+    /// A manager for app 1 whose requests are answered by `simulation`.
+    fn simulated(simulation: &Arc<Simulation>) -> TokenManager {
+        let mut manager = TokenManager::new(reqwest::Client::new());
+        manager.add_app(
+            GithubAuthParams::builder()
+                .app_id(1)
+                .private_key(
+                    include_bytes!("../testdata/private-key.pem").to_vec(),
+                )
+                .hooks(simulation.clone())
+                .build(),
+        );
+        manager
+    }
+
+    #[test]
+    fn test_cache_order() {
+        let policy = CachePolicy {
+            max_installations: Some(2),
+            idle_timeout: Some(Duration::from_secs(3600)),
+            ..Default::default()
+        };
+        let mut cache = Cache::new();
+        cache.get_or_insert_with(&policy, 1, || "a");
+        cache.get_or_insert_with(&policy, 2, || "b");
+        // Using 1 again leaves 2 as the least recently used.
+        assert_eq!(cache.get(&policy, &1), Some("a"));
+        cache.get_or_insert_with(&policy, 3, || "c");
+        assert_eq!(cache.get(&policy, &2), None);
+        assert_eq!(cache.len(), 2);
+
+        let idle = CachePolicy {
+            idle_timeout: Some(Duration::ZERO),
+            ..Default::default()
+        };
+        assert_eq!(cache.get(&idle, &1), None);
+        assert_eq!(cache.len(), 1);
+        cache.evict(&idle);
+        assert_eq!(cache.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_app() {
+        let manager = TokenManager::new(reqwest::Client::new());
+        assert!(matches!(
+            manager.header_for(1, 2).await,
+            Err(AuthError::UnknownApp(1))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_org_lookup_is_cached() {
+        let simulation = Arc::new(Simulation::new());
+        let manager = simulated(&simulation);
+        manager.org_installations.lock().await.get_or_insert_with(
+            &CachePolicy::default(),
+            (1, "octo-org".into()),
+            || 2,
+        );
+        simulation.respond_token("ghs_org", Duration::from_secs(3600));
+        assert_eq!(
+            manager.token_for_org(1, "Octo-Org").await.unwrap(),
+            "ghs_org"
+        );
+        assert_eq!(
+            simulation.requests(),
+            ["POST /app/installations/2/access_tokens"]
+        );
+        assert!(matches!(
+            manager.token_for_org(3, "octo-org").await,
+            Err(AuthError::UnknownApp(3))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_token_for_repo_checks_name() {
+        let mut manager = TokenManager::new(reqwest::Client::new());
+        manager.add_app(GithubAuthParams {
+            app_id: 1,
+            ..Default::default()
+        });
+        for name in ["octo-repo", "octo-org/", "/octo-repo"] {
+            assert!(matches!(
+                manager.token_for_repo(1, name).await,
+                Err(AuthError::ConfigError(_))
+            ));
+        }
+        manager.repo_tokens.lock().await.get_or_insert_with(
+            &CachePolicy::default(),
+            (1, "octo-org/octo-repo".into()),
+            || (2, TokenSlot::default()),
+        );
+        // Minting fails without a key, but the cached lookup is used.
+        assert!(matches!(
+            manager.token_for_repo(1, "Octo-Org/Octo-Repo").await,
+            Err(AuthError::JwtError(_))
+        ));
+        // The failed slot isn't kept.
+        assert_eq!(manager.cached_repositories().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_failed_mint_is_not_cached() {
+        let mut manager = TokenManager::new(reqwest::Client::new());
+        manager.add_app(GithubAuthParams {
+            app_id: 1,
+            ..Default::default()
+        });
+        assert!(manager.installation(1, 2).await.is_err());
+        assert_eq!(manager.cached_installations().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_empties_cache() {
+        let simulation = Arc::new(Simulation::new());
+        let manager = simulated(&simulation);
+        simulation.respond_token("ghs_first", Duration::from_secs(3600));
+        simulation.respond(204, serde_json::Value::Null);
+        manager.installation(1, 2).await.unwrap();
+        assert_eq!(manager.cached_installations().await, 1);
+        let errors = manager.shutdown(Duration::from_secs(1)).await;
+        assert!(errors.is_empty());
+        assert_eq!(manager.cached_installations().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_max_installations() {
+        let simulation = Arc::new(Simulation::new());
+        let mut manager = simulated(&simulation);
+        manager.set_cache_policy(CachePolicy {
+            max_installations: Some(2),
+            ..Default::default()
+        });
+        for installation_id in 1..=3 {
+            simulation.respond_token("ghs", Duration::from_secs(3600));
+            manager.installation(1, installation_id).await.unwrap();
+        }
+        assert_eq!(manager.cached_installations().await, 2);
+        assert!(!manager.stats().await.contains_key(&(1, 1)));
+    }
+
+    #[tokio::test]
+    async fn test_warm() {
+        let manager = TokenManager::new(reqwest::Client::new());
+        let errors = manager.warm(1, &[2, 3], 4).await;
+        let ids: Vec<_> = errors.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, [2, 3]);
+        assert!(matches!(errors[0].1, AuthError::UnknownApp(1)));
+    }
+
+    #[tokio::test]
+    async fn test_mint_all_reports_each_error() {
+        let manager = TokenManager::new(reqwest::Client::new());
+        let results = manager.mint_all([(1, 2), (3, 4)], 2).await;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0, (1, 2));
+        assert!(matches!(results[0].1, Err(AuthError::UnknownApp(1))));
+        assert_eq!(results[1].0, (3, 4));
+        assert!(matches!(results[1].1, Err(AuthError::UnknownApp(3))));
+    }
+}
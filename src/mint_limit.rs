@@ -0,0 +1,137 @@
+//! The guardrail set with [`GithubAuthParams::max_mints_per_minute`].
+
+use crate::{AuthError, GithubAuthParams};
+use log::error;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// Used by parameters that don't set
+/// [`mint_limiter`](GithubAuthParams::mint_limiter), so that tokens
+/// created separately for the same installation count together.
+static SHARED: MintLimiter = MintLimiter::new();
+
+/// Counts the tokens minted in the last minute for
+/// [`max_mints_per_minute`](GithubAuthParams::max_mints_per_minute),
+/// keyed by app and installation ID.
+///
+/// Parameters share one limiter for the whole process unless they set
+/// [`mint_limiter`](GithubAuthParams::mint_limiter), for example to
+/// keep tests apart.
+pub struct MintLimiter {
+    mints: Mutex<BTreeMap<(u64, u64), VecDeque<Instant>>>,
+}
+
+impl MintLimiter {
+    /// Create a limiter that hasn't counted any mints.
+    pub const fn new() -> MintLimiter {
+        MintLimiter {
+            mints: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Count an attempt to mint a token for `key` at `now`, or return
+    /// `false` without counting it if `max` were exceeded.
+    fn try_mint(&self, key: (u64, u64), max: u32, now: Instant) -> bool {
+        let mut mints = self.mints.lock().unwrap();
+        // Drop every installation's expired mints, and installations
+        // that are left with none, so that the map doesn't grow with
+        // each installation ever seen.
+        mints.retain(|_, recent| {
+            while recent
+                .front()
+                .is_some_and(|&minted| now.duration_since(minted) >= WINDOW)
+            {
+                recent.pop_front();
+            }
+            !recent.is_empty()
+        });
+        let recent = mints.entry(key).or_default();
+        if recent.len() >= max as usize {
+            return false;
+        }
+        recent.push_back(now);
+        true
+    }
+}
+
+impl Default for MintLimiter {
+    fn default() -> MintLimiter {
+        MintLimiter::new()
+    }
+}
+
+impl fmt::Debug for MintLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MintLimiter")
+            .field("installations", &self.mints.lock().unwrap().len())
+            .finish()
+    }
+}
+
+/// Count an attempt to mint a token with `params`, or fail with
+/// [`AuthError::MintRateExceeded`] if it would exceed the limit.
+pub(crate) fn check(params: &GithubAuthParams) -> Result<(), AuthError> {
+    let Some(max) = params.max_mints_per_minute else {
+        return Ok(());
+    };
+    let limiter = params.mint_limiter.as_deref().unwrap_or(&SHARED);
+    let key = (params.app_id, params.installation_id);
+    if !limiter.try_mint(key, max, Instant::now()) {
+        error!(
+            "refusing to mint more than {} tokens per minute for \
+             installation {}",
+            max, params.installation_id
+        );
+        return Err(AuthError::MintRateExceeded(params.installation_id));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_limit() {
+        let limiter = Arc::new(MintLimiter::new());
+        let mut params = GithubAuthParams {
+            installation_id: 1,
+            mint_limiter: Some(limiter.clone()),
+            ..Default::default()
+        };
+        // Without a limit, mints aren't counted.
+        for _ in 0..5 {
+            check(&params).unwrap();
+        }
+        params.max_mints_per_minute = Some(2);
+        check(&params).unwrap();
+        check(&params).unwrap();
+        assert!(matches!(
+            check(&params),
+            Err(AuthError::MintRateExceeded(1))
+        ));
+
+        // Other installations have their own limit.
+        params.installation_id = 2;
+        check(&params).unwrap();
+        assert_eq!(limiter.mints.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_prune() {
+        let limiter = MintLimiter::new();
+        let start = Instant::now();
+        assert!(limiter.try_mint((1, 1), 1, start));
+        assert!(!limiter.try_mint((1, 1), 1, start));
+        assert!(limiter.try_mint((1, 2), 1, start + WINDOW / 2));
+        // The first installation's mint has left the window.
+        assert!(limiter.try_mint((1, 3), 1, start + WINDOW));
+        let mints = limiter.mints.lock().unwrap();
+        assert_eq!(mints.keys().collect::<Vec<_>>(), [&(1, 2), &(1, 3)]);
+    }
+}
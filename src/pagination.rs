@@ -0,0 +1,153 @@
+//! Fetching every page of a paginated list endpoint.
+
+use crate::error_response::check_status;
+use crate::{AuthError, InstallationAccessToken, GITHUB_JSON};
+use reqwest::header::{HeaderMap, LINK};
+use reqwest::Response;
+use serde::de::{DeserializeOwned, Error as _};
+use serde_json::Value;
+use std::future::Future;
+use url::Url;
+
+/// Get the `rel="next"` URL from a `Link` header, if there is one.
+pub(crate) fn next_page_url(headers: &HeaderMap) -> Option<String> {
+    let link = headers.get(LINK)?.to_str().ok()?;
+    link.split(',').find_map(|part| {
+        let (url, params) = part.trim().split_once(';')?;
+        let is_next = params
+            .split(';')
+            .any(|param| param.trim() == r#"rel="next""#);
+        if is_next {
+            Some(
+                url.trim()
+                    .trim_start_matches('<')
+                    .trim_end_matches('>')
+                    .into(),
+            )
+        } else {
+            None
+        }
+    })
+}
+
+/// Get the items from a page of results. Most endpoints return a
+/// bare array, but some wrap it in an object alongside a
+/// `total_count`, for example `{"total_count": 2, "repositories": [...]}`.
+pub(crate) fn page_items<T: DeserializeOwned>(
+    page: Value,
+) -> Result<Vec<T>, AuthError> {
+    let items = match page {
+        Value::Array(_) => page,
+        Value::Object(object) => object
+            .into_iter()
+            .map(|(_, value)| value)
+            .find(Value::is_array)
+            .ok_or_else(|| {
+                serde_json::Error::custom("page does not contain an array")
+            })?,
+        _ => {
+            return Err(serde_json::Error::custom(
+                "page is not an array or object",
+            )
+            .into())
+        }
+    };
+    Ok(serde_json::from_value(items)?)
+}
+
+/// Get every page of a paginated list endpoint starting at `url` and
+/// concatenate the results. `get_page` sends the authenticated request
+/// for each page's URL and checks its status.
+///
+/// Pages are followed using the `Link` header, and `per_page=100` is
+/// added to `url` unless it already sets a page size.
+pub(crate) async fn get_all_pages<T, F, Fut>(
+    url: &str,
+    mut get_page: F,
+) -> Result<Vec<T>, AuthError>
+where
+    T: DeserializeOwned,
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = Result<Response, AuthError>>,
+{
+    let mut url = Url::parse(url)?;
+    if !url.query_pairs().any(|(key, _)| key == "per_page") {
+        url.query_pairs_mut().append_pair("per_page", "100");
+    }
+
+    let mut items = Vec::new();
+    let mut next = Some(url.to_string());
+    while let Some(url) = next {
+        let response = get_page(url).await?;
+        next = next_page_url(response.headers());
+        items.extend(page_items(response.json().await?)?);
+    }
+    Ok(items)
+}
+
+impl InstallationAccessToken {
+    /// Get every page of a paginated list endpoint and concatenate the
+    /// results.
+    ///
+    /// Pages are followed using the `Link` header, and `per_page=100`
+    /// is added to `url` unless it already sets a page size. Each
+    /// request goes through [`execute`](Self::execute), so the token is
+    /// refreshed between pages as needed.
+    pub async fn get_all_pages<T: DeserializeOwned>(
+        &self,
+        url: &str,
+    ) -> Result<Vec<T>, AuthError> {
+        get_all_pages(url, |url| async move {
+            let request = self
+                .client
+                .get(&url)
+                .header("Accept", self.params.accept(GITHUB_JSON))
+                .build()?;
+            check_status(&self.params, self.execute(request).await?).await
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_page_url() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(next_page_url(&headers), None);
+
+        headers.insert(
+            LINK,
+            r#"<https://api.github.com/x?page=1>; rel="prev", <https://api.github.com/x?page=3>; rel="next", <https://api.github.com/x?page=5>; rel="last""#
+                .parse()
+                .unwrap(),
+        );
+        assert_eq!(
+            next_page_url(&headers).as_deref(),
+            Some("https://api.github.com/x?page=3")
+        );
+
+        headers.insert(
+            LINK,
+            r#"<https://api.github.com/x?page=1>; rel="first""#.parse().unwrap(),
+        );
+        assert_eq!(next_page_url(&headers), None);
+    }
+
+    #[test]
+    fn test_page_items() {
+        let items: Vec<u32> = page_items(serde_json::json!([1, 2, 3])).unwrap();
+        assert_eq!(items, [1, 2, 3]);
+
+        let items: Vec<u32> = page_items(serde_json::json!({
+            "total_count": 2,
+            "repositories": [4, 5]
+        }))
+        .unwrap();
+        assert_eq!(items, [4, 5]);
+
+        assert!(page_items::<u32>(serde_json::json!({"a": 1})).is_err());
+    }
+}
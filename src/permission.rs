@@ -0,0 +1,333 @@
+//! Names of GitHub app permissions and their access levels.
+//!
+//! Permissions are kept as strings in
+//! [`GithubAuthParams::permissions`](crate::GithubAuthParams::permissions)
+//! and [`Installation::permissions`](crate::Installation::permissions),
+//! so that permissions GitHub adds later can still be used. These types
+//! convert into those strings, so that the common ones can't be
+//! misspelled:
+//!
+//! ```
+//! use github_app_auth::{Access, GithubAuthParams, Permission};
+//!
+//! let params = GithubAuthParams::builder()
+//!     .permission(Permission::Contents, Access::Read)
+//!     .permission(Permission::PullRequests, Access::Write)
+//!     .build();
+//! assert_eq!(params.permissions["pull_requests"], "write");
+//! ```
+
+use crate::AuthError;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// A permission that can be granted to a GitHub app.
+///
+/// See <https://docs.github.com/en/rest/apps/apps#create-an-installation-access-token-for-an-app>
+/// for what each one allows.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[non_exhaustive]
+pub enum Permission {
+    /// `actions`: workflows, workflow runs and artifacts.
+    Actions,
+    /// `administration`: repository settings, teams and collaborators.
+    Administration,
+    /// `checks`: checks on code.
+    Checks,
+    /// `codespaces`: codespaces.
+    Codespaces,
+    /// `contents`: repository contents, commits, branches, downloads,
+    /// releases and merges.
+    Contents,
+    /// `dependabot_secrets`: Dependabot secrets.
+    DependabotSecrets,
+    /// `deployments`: deployments and deployment statuses.
+    Deployments,
+    /// `environments`: deployment environments.
+    Environments,
+    /// `issues`: issues and related comments, assignees, labels and
+    /// milestones.
+    Issues,
+    /// `metadata`: search repositories, list collaborators and access
+    /// repository metadata.
+    Metadata,
+    /// `packages`: packages published to GitHub Packages.
+    Packages,
+    /// `pages`: GitHub Pages.
+    Pages,
+    /// `pull_requests`: pull requests and related comments, assignees,
+    /// labels, milestones and merges.
+    PullRequests,
+    /// `repository_hooks`: repository webhooks.
+    RepositoryHooks,
+    /// `repository_projects`: classic projects of a repository.
+    RepositoryProjects,
+    /// `secret_scanning_alerts`: secret scanning alerts.
+    SecretScanningAlerts,
+    /// `secrets`: repository secrets.
+    Secrets,
+    /// `security_events`: code scanning alerts.
+    SecurityEvents,
+    /// `single_file`: a single file.
+    SingleFile,
+    /// `statuses`: commit statuses.
+    Statuses,
+    /// `vulnerability_alerts`: Dependabot alerts.
+    VulnerabilityAlerts,
+    /// `workflows`: workflow files.
+    Workflows,
+    /// `members`: organization teams and members.
+    Members,
+    /// `organization_administration`: organization settings.
+    OrganizationAdministration,
+    /// `organization_hooks`: organization webhooks.
+    OrganizationHooks,
+    /// `organization_packages`: organization packages.
+    OrganizationPackages,
+    /// `organization_plan`: an organization's plan.
+    OrganizationPlan,
+    /// `organization_projects`: organization projects.
+    OrganizationProjects,
+    /// `organization_secrets`: organization secrets.
+    OrganizationSecrets,
+    /// `organization_self_hosted_runners`: self-hosted runners of an
+    /// organization.
+    OrganizationSelfHostedRunners,
+    /// `organization_user_blocking`: users blocked by an organization.
+    OrganizationUserBlocking,
+    /// `team_discussions`: team discussions.
+    TeamDiscussions,
+}
+
+impl Permission {
+    /// Every permission, in the order they are declared.
+    pub const ALL: &'static [Permission] = &[
+        Permission::Actions,
+        Permission::Administration,
+        Permission::Checks,
+        Permission::Codespaces,
+        Permission::Contents,
+        Permission::DependabotSecrets,
+        Permission::Deployments,
+        Permission::Environments,
+        Permission::Issues,
+        Permission::Metadata,
+        Permission::Packages,
+        Permission::Pages,
+        Permission::PullRequests,
+        Permission::RepositoryHooks,
+        Permission::RepositoryProjects,
+        Permission::SecretScanningAlerts,
+        Permission::Secrets,
+        Permission::SecurityEvents,
+        Permission::SingleFile,
+        Permission::Statuses,
+        Permission::VulnerabilityAlerts,
+        Permission::Workflows,
+        Permission::Members,
+        Permission::OrganizationAdministration,
+        Permission::OrganizationHooks,
+        Permission::OrganizationPackages,
+        Permission::OrganizationPlan,
+        Permission::OrganizationProjects,
+        Permission::OrganizationSecrets,
+        Permission::OrganizationSelfHostedRunners,
+        Permission::OrganizationUserBlocking,
+        Permission::TeamDiscussions,
+    ];
+
+    /// The name GitHub uses for the permission, such as
+    /// `"pull_requests"`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Permission::Actions => "actions",
+            Permission::Administration => "administration",
+            Permission::Checks => "checks",
+            Permission::Codespaces => "codespaces",
+            Permission::Contents => "contents",
+            Permission::DependabotSecrets => "dependabot_secrets",
+            Permission::Deployments => "deployments",
+            Permission::Environments => "environments",
+            Permission::Issues => "issues",
+            Permission::Metadata => "metadata",
+            Permission::Packages => "packages",
+            Permission::Pages => "pages",
+            Permission::PullRequests => "pull_requests",
+            Permission::RepositoryHooks => "repository_hooks",
+            Permission::RepositoryProjects => "repository_projects",
+            Permission::SecretScanningAlerts => "secret_scanning_alerts",
+            Permission::Secrets => "secrets",
+            Permission::SecurityEvents => "security_events",
+            Permission::SingleFile => "single_file",
+            Permission::Statuses => "statuses",
+            Permission::VulnerabilityAlerts => "vulnerability_alerts",
+            Permission::Workflows => "workflows",
+            Permission::Members => "members",
+            Permission::OrganizationAdministration => {
+                "organization_administration"
+            }
+            Permission::OrganizationHooks => "organization_hooks",
+            Permission::OrganizationPackages => "organization_packages",
+            Permission::OrganizationPlan => "organization_plan",
+            Permission::OrganizationProjects => "organization_projects",
+            Permission::OrganizationSecrets => "organization_secrets",
+            Permission::OrganizationSelfHostedRunners => {
+                "organization_self_hosted_runners"
+            }
+            Permission::OrganizationUserBlocking => {
+                "organization_user_blocking"
+            }
+            Permission::TeamDiscussions => "team_discussions",
+        }
+    }
+}
+
+/// An access level of a [`Permission`]. Levels are ordered, so that
+/// `Access::Write > Access::Read`.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Eq,
+    Hash,
+    Ord,
+    PartialEq,
+    PartialOrd,
+    Deserialize,
+    Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum Access {
+    /// `read`
+    Read,
+    /// `write`
+    Write,
+    /// `admin`, which only some permissions have.
+    Admin,
+}
+
+impl Access {
+    /// The name GitHub uses for the level, such as `"write"`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Access::Read => "read",
+            Access::Write => "write",
+            Access::Admin => "admin",
+        }
+    }
+}
+
+/// Build a permissions map, as used by
+/// [`GithubAuthParams::permissions`](crate::GithubAuthParams::permissions)
+/// and [`preflight`](crate::InstallationAccessToken::preflight), from
+/// typed permissions.
+pub fn permissions(
+    permissions: impl IntoIterator<Item = (Permission, Access)>,
+) -> BTreeMap<String, String> {
+    permissions
+        .into_iter()
+        .map(|(permission, access)| (permission.into(), access.into()))
+        .collect()
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<Permission> for String {
+    fn from(permission: Permission) -> String {
+        permission.as_str().to_string()
+    }
+}
+
+impl From<Access> for String {
+    fn from(access: Access) -> String {
+        access.as_str().to_string()
+    }
+}
+
+impl FromStr for Permission {
+    type Err = AuthError;
+
+    fn from_str(name: &str) -> Result<Permission, AuthError> {
+        Permission::ALL
+            .iter()
+            .copied()
+            .find(|permission| permission.as_str() == name)
+            .ok_or_else(|| {
+                AuthError::ConfigError(format!("unknown permission: {}", name))
+            })
+    }
+}
+
+impl FromStr for Access {
+    type Err = AuthError;
+
+    fn from_str(level: &str) -> Result<Access, AuthError> {
+        match level {
+            "read" => Ok(Access::Read),
+            "write" => Ok(Access::Write),
+            "admin" => Ok(Access::Admin),
+            _ => Err(AuthError::ConfigError(format!(
+                "unknown access level: {}",
+                level
+            ))),
+        }
+    }
+}
+
+impl Serialize for Permission {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Permission {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Permission, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_round_trip() {
+        for &permission in Permission::ALL {
+            assert_eq!(
+                permission.as_str().parse::<Permission>().unwrap(),
+                permission
+            );
+            assert_eq!(
+                serde_json::to_value(permission).unwrap(),
+                permission.as_str()
+            );
+        }
+        assert!("pull_request".parse::<Permission>().is_err());
+        assert_eq!("admin".parse::<Access>().unwrap(), Access::Admin);
+        assert!(Access::Admin > Access::Write);
+        assert_eq!(
+            permissions([(Permission::Checks, Access::Write)]),
+            [("checks".to_string(), "write".to_string())].into()
+        );
+    }
+}
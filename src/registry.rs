@@ -0,0 +1,115 @@
+//! Helpers for authenticating with the GitHub Container Registry
+//! (`ghcr.io`) using an installation access token.
+//!
+//! The token is used as the password with the username
+//! `x-access-token`, the same as for git over HTTPS.
+
+use crate::git::GIT_USERNAME;
+use crate::{AuthError, InstallationAccessToken};
+use base64::Engine;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Hostname of the GitHub Container Registry.
+pub const GHCR_REGISTRY: &str = "ghcr.io";
+
+/// Credentials for logging in to a container registry.
+#[derive(Clone, Eq, PartialEq)]
+pub struct RegistryCredentials {
+    /// Registry hostname, for example [`GHCR_REGISTRY`].
+    pub registry: String,
+
+    /// Registry username.
+    pub username: String,
+
+    /// Registry password, which is the installation access token.
+    pub password: String,
+}
+
+#[derive(Serialize)]
+struct DockerConfig<'a> {
+    auths: BTreeMap<&'a str, DockerAuth>,
+}
+
+/// A single entry in the `auths` section of a docker `config.json`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct DockerAuth {
+    /// Base64 encoding of `username:password`.
+    pub auth: String,
+}
+
+impl RegistryCredentials {
+    /// Create `ghcr.io` credentials for an installation access token.
+    pub fn ghcr(token: &str) -> RegistryCredentials {
+        RegistryCredentials {
+            registry: GHCR_REGISTRY.into(),
+            username: GIT_USERNAME.into(),
+            password: token.into(),
+        }
+    }
+
+    /// Get the `auths` entry for this registry in a docker
+    /// `config.json`.
+    pub fn docker_auth(&self) -> DockerAuth {
+        DockerAuth {
+            auth: base64::engine::general_purpose::STANDARD
+                .encode(format!("{}:{}", self.username, self.password)),
+        }
+    }
+
+    /// Render a complete docker `config.json` containing only these
+    /// credentials, suitable for writing to `$DOCKER_CONFIG/config.json`.
+    pub fn docker_config_json(&self) -> String {
+        let config = DockerConfig {
+            auths: BTreeMap::from([(
+                self.registry.as_str(),
+                self.docker_auth(),
+            )]),
+        };
+        // Serializing a map of strings cannot fail.
+        serde_json::to_string(&config).expect("docker config serialization")
+    }
+}
+
+// Written by hand so that the token doesn't end up in logs.
+impl fmt::Debug for RegistryCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RegistryCredentials")
+            .field("registry", &self.registry)
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .finish()
+    }
+}
+
+impl InstallationAccessToken {
+    /// Get credentials for `ghcr.io`, refreshing the token if
+    /// necessary.
+    ///
+    /// The installation must have the `packages` permission for the
+    /// registry to accept the token.
+    pub async fn ghcr_credentials(
+        &self,
+    ) -> Result<RegistryCredentials, AuthError> {
+        Ok(RegistryCredentials::ghcr(&self.token().await?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_docker_config() {
+        let credentials = RegistryCredentials::ghcr("myToken");
+        assert_eq!(
+            credentials.docker_auth().auth,
+            "eC1hY2Nlc3MtdG9rZW46bXlUb2tlbg=="
+        );
+        assert_eq!(
+            credentials.docker_config_json(),
+            r#"{"auths":{"ghcr.io":{"auth":"eC1hY2Nlc3MtdG9rZW46bXlUb2tlbg=="}}}"#
+        );
+    }
+}
@@ -0,0 +1,129 @@
+//! Uploading release assets.
+//!
+//! Release assets are uploaded to a different host than the rest of
+//! the API (`uploads.github.com` on github.com, `/api/uploads` on
+//! GitHub Enterprise Server). The `upload_url` field of a release
+//! already points at the right place, so [`upload_asset`] uses it
+//! as-is. [`upload_release_asset`] instead builds the URL from the
+//! [`Endpoints`](crate::Endpoints) in the parameters.
+//!
+//! [`upload_asset`]: InstallationAccessToken::upload_asset
+//! [`upload_release_asset`]: InstallationAccessToken::upload_release_asset
+
+use crate::error_response::check_status;
+use crate::{AuthError, InstallationAccessToken, GITHUB_JSON};
+use serde::Deserialize;
+use url::Url;
+
+/// A release asset as returned by the API after uploading.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+pub struct ReleaseAsset {
+    /// Asset ID.
+    pub id: u64,
+
+    /// File name of the asset.
+    pub name: String,
+
+    /// Optional short description shown instead of the file name.
+    pub label: Option<String>,
+
+    /// MIME type of the asset.
+    pub content_type: String,
+
+    /// Size of the asset in bytes.
+    pub size: u64,
+
+    /// API URL of the asset.
+    pub url: String,
+
+    /// URL for downloading the asset in a browser.
+    pub browser_download_url: String,
+}
+
+/// Turn a release's `upload_url` into the URL for uploading an asset
+/// called `name`.
+///
+/// The API returns `upload_url` as a URI template such as
+/// `https://uploads.github.com/repos/o/r/releases/1/assets{?name,label}`;
+/// the template part is dropped and replaced with the actual query.
+fn asset_upload_url(upload_url: &str, name: &str) -> Result<Url, AuthError> {
+    let base = match upload_url.find('{') {
+        Some(index) => &upload_url[..index],
+        None => upload_url,
+    };
+    let mut url = Url::parse(base)?;
+    url.query_pairs_mut().append_pair("name", name);
+    Ok(url)
+}
+
+impl InstallationAccessToken {
+    /// Upload a release asset.
+    ///
+    /// `upload_url` is the `upload_url` field of the release, either
+    /// with or without its `{?name,label}` template suffix.
+    pub async fn upload_asset(
+        &self,
+        upload_url: &str,
+        name: &str,
+        bytes: impl Into<reqwest::Body>,
+        content_type: &str,
+    ) -> Result<ReleaseAsset, AuthError> {
+        let url = asset_upload_url(upload_url, name)?;
+        let request = self
+            .client
+            .post(url)
+            .header("Accept", self.params.accept(GITHUB_JSON))
+            .header("Content-Type", content_type)
+            .body(bytes)
+            .build()?;
+        let response = self.execute(request).await?;
+        Ok(check_status(&self.params, response).await?.json().await?)
+    }
+
+    /// Upload an asset to the release with ID `release_id` in the
+    /// repository `owner/repo`.
+    pub async fn upload_release_asset(
+        &self,
+        repository: &str,
+        release_id: u64,
+        name: &str,
+        bytes: impl Into<reqwest::Body>,
+        content_type: &str,
+    ) -> Result<ReleaseAsset, AuthError> {
+        let upload_url = format!(
+            "{}/repos/{}/releases/{}/assets",
+            self.params.endpoints.uploads_base(),
+            repository,
+            release_id
+        );
+        self.upload_asset(&upload_url, name, bytes, content_type)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_asset_upload_url() {
+        assert_eq!(
+            asset_upload_url(
+                "https://uploads.github.com/repos/o/r/releases/1/assets{?name,label}",
+                "my file.tar.gz"
+            )
+            .unwrap()
+            .as_str(),
+            "https://uploads.github.com/repos/o/r/releases/1/assets?name=my+file.tar.gz"
+        );
+        assert_eq!(
+            asset_upload_url(
+                "https://ghe.example/api/uploads/repos/o/r/releases/1/assets",
+                "a.zip"
+            )
+            .unwrap()
+            .as_str(),
+            "https://ghe.example/api/uploads/repos/o/r/releases/1/assets?name=a.zip"
+        );
+    }
+}
@@ -0,0 +1,230 @@
+//! When requests are retried, set with
+//! [`GithubAuthParams::retry_policy`](crate::GithubAuthParams::retry_policy).
+
+use crate::hooks::{self, ExtraHeaders, Hooks};
+use crate::{status_kind, AuthError, AuthErrorKind};
+use log::warn;
+use reqwest::header::RETRY_AFTER;
+use reqwest::{RequestBuilder, Response, StatusCode};
+use std::time::{Duration, SystemTime};
+
+/// GitHub asks clients to wait at least a minute after hitting a
+/// secondary rate limit that doesn't say how long to wait.
+const SECONDARY_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60);
+
+/// How failed requests are retried. This applies to token requests,
+/// requests authenticated as the app, such as
+/// [`GithubApp::installations`](crate::GithubApp::installations), app
+/// ID lookups with
+/// [`GithubAuthParams::resolve_app_id`](crate::GithubAuthParams::resolve_app_id),
+/// and requests sent with
+/// [`InstallationAccessToken::execute`](crate::InstallationAccessToken::execute)
+/// and the helpers built on it. Requests sent with
+/// [`OAuthApp`](crate::user::OAuthApp) follow
+/// [`OAuthAppParams::retry_policy`](crate::user::OAuthAppParams::retry_policy).
+///
+/// Rate-limited requests wait until the limit resets, as GitHub asks.
+/// Other failures wait `backoff_base`, then twice as long for each
+/// further attempt. A `401` to a request with an installation token is
+/// always retried once with a new token, without counting as an
+/// attempt.
+///
+/// By default only rate limits are retried, twice, if they reset
+/// within a minute.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts at most, including the first. `1` disables retries.
+    pub max_attempts: u32,
+
+    /// The wait before the first retry of a failure that isn't a rate
+    /// limit.
+    pub backoff_base: Duration,
+
+    /// Waits are capped at this. Rate limits that don't reset by then
+    /// aren't retried, so the rate-limited response is returned.
+    pub max_delay: Duration,
+
+    /// Which kinds of failures are retried, such as
+    /// [`AuthErrorKind::Network`] or [`AuthErrorKind::ServerError`].
+    /// Responses are classified by their status.
+    pub retry_on: Vec<AuthErrorKind>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            backoff_base: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            retry_on: vec![AuthErrorKind::RateLimited],
+        }
+    }
+}
+
+/// The result of an attempt to send a request.
+pub(crate) enum Outcome<'a> {
+    Response(&'a Response),
+    Error(&'a AuthError),
+}
+
+impl RetryPolicy {
+    /// A policy that never retries.
+    pub fn none() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        }
+    }
+
+    /// A policy that also retries network failures, timeouts and
+    /// server errors.
+    pub fn transient() -> RetryPolicy {
+        RetryPolicy {
+            retry_on: vec![
+                AuthErrorKind::RateLimited,
+                AuthErrorKind::Network,
+                AuthErrorKind::Timeout,
+                AuthErrorKind::ServerError,
+            ],
+            ..RetryPolicy::default()
+        }
+    }
+
+    /// How long to wait before retrying after `attempts` attempts, the
+    /// last of which had `outcome` at `now`, or `None` if it shouldn't
+    /// be retried.
+    pub(crate) fn delay(
+        &self,
+        attempts: u32,
+        outcome: Outcome<'_>,
+        now: SystemTime,
+    ) -> Option<Duration> {
+        if attempts >= self.max_attempts {
+            return None;
+        }
+        let (kind, wait) = match outcome {
+            Outcome::Response(response) => match rate_limit_wait(response, now)
+            {
+                Some(wait) => (AuthErrorKind::RateLimited, Some(wait)),
+                None if response.status().is_server_error() => {
+                    (status_kind(response.status()), None)
+                }
+                None => return None,
+            },
+            Outcome::Error(err) => (err.kind(), None),
+        };
+        if !self.retry_on.contains(&kind) {
+            return None;
+        }
+        match wait {
+            Some(wait) => (wait <= self.max_delay).then_some(wait),
+            None => {
+                let factor = 2u32.saturating_pow(attempts - 1);
+                Some(
+                    self.backoff_base
+                        .saturating_mul(factor)
+                        .min(self.max_delay),
+                )
+            }
+        }
+    }
+}
+
+/// Send the request built by `request`, building and sending it again
+/// for as long as `policy` says to retry.
+pub(crate) async fn send(
+    hooks: &Hooks,
+    extra_headers: &ExtraHeaders,
+    policy: &RetryPolicy,
+    mut request: impl FnMut() -> Result<RequestBuilder, AuthError>,
+) -> Result<Response, AuthError> {
+    let mut attempts = 1;
+    loop {
+        let result = hooks::send(hooks, extra_headers, request()?).await;
+        let outcome = match &result {
+            Ok(response) => Outcome::Response(response),
+            Err(err) => Outcome::Error(err),
+        };
+        let Some(wait) = policy.delay(attempts, outcome, hooks::now(hooks))
+        else {
+            return result;
+        };
+        warn!("request failed, retrying in {:?}", wait);
+        tokio::time::sleep(wait).await;
+        attempts += 1;
+    }
+}
+
+/// Work out how long to wait at `now` before retrying a rate-limited
+/// response, or `None` if the response isn't rate limited.
+fn rate_limit_wait(response: &Response, now: SystemTime) -> Option<Duration> {
+    let status = response.status();
+    if status != StatusCode::TOO_MANY_REQUESTS
+        && status != StatusCode::FORBIDDEN
+    {
+        return None;
+    }
+    let headers = response.headers();
+    if let Some(seconds) = headers
+        .get(RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+    {
+        return Some(Duration::from_secs(seconds));
+    }
+    match crate::RateLimit::from_headers(headers) {
+        Some(rate_limit) if rate_limit.remaining == 0 => {
+            Some(rate_limit.reset.duration_since(now).unwrap_or_default())
+        }
+        // A 403 that isn't about rate limits is a permissions problem.
+        _ if status == StatusCode::FORBIDDEN => None,
+        _ => Some(SECONDARY_RATE_LIMIT_WAIT),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(status: u16, retry_after: Option<u64>) -> Response {
+        let mut response = http::Response::builder().status(status);
+        if let Some(seconds) = retry_after {
+            response = response.header("retry-after", seconds.to_string());
+        }
+        response.body("").unwrap().into()
+    }
+
+    #[test]
+    fn test_delay() {
+        let now = SystemTime::now();
+        let policy = RetryPolicy::default();
+        let limited = response(429, Some(5));
+        assert_eq!(
+            policy.delay(1, Outcome::Response(&limited), now),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(policy.delay(3, Outcome::Response(&limited), now), None);
+        let too_long = response(429, Some(120));
+        assert_eq!(policy.delay(1, Outcome::Response(&too_long), now), None);
+        let failed = response(502, None);
+        assert_eq!(policy.delay(1, Outcome::Response(&failed), now), None);
+        assert_eq!(
+            policy.delay(1, Outcome::Response(&response(404, None)), now),
+            None
+        );
+
+        let policy = RetryPolicy::transient();
+        assert_eq!(
+            policy.delay(1, Outcome::Response(&failed), now),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(
+            policy.delay(2, Outcome::Error(&AuthError::Timeout), now),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(
+            RetryPolicy::none().delay(1, Outcome::Response(&limited), now),
+            None
+        );
+    }
+}
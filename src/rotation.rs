@@ -0,0 +1,143 @@
+//! Rotating an app's private key.
+//!
+//! Rotation takes a few steps: generate a new key in the app settings,
+//! check that GitHub accepts JWTs signed with it, switch to signing with
+//! it, and delete the old key once no JWTs signed with it are in use.
+//! [`KeyRotation`] does the middle part and says when the last step is
+//! safe.
+//!
+//! ```no_run
+//! use github_app_auth::rotation::KeyRotation;
+//! use github_app_auth::{AppKey, GithubAuthParams};
+//!
+//! # async fn wrapper(old: GithubAuthParams, new_pem: Vec<u8>) {
+//! let rotation = KeyRotation::new(old, AppKey {
+//!     id: Some("2024-06".into()),
+//!     private_key: new_pem,
+//! })
+//! .unwrap();
+//! let rotated = rotation.rotate().await.expect("new key was rejected");
+//! // Use rotated.params from now on, and delete the old key in the
+//! // app settings after rotated.old_key_deletable_at.
+//! # }
+//! ```
+
+use crate::error_response::check_status;
+use crate::{
+    send_as_app, AppKey, AuthError, GithubAuthParams, GITHUB_JSON, JWT_LIFETIME,
+};
+use reqwest::StatusCode;
+use std::time::SystemTime;
+
+/// A rotation from the private key of some parameters to a new key.
+pub struct KeyRotation {
+    /// The [`reqwest::Client`] used to send requests.
+    pub client: reqwest::Client,
+
+    params: GithubAuthParams,
+    new_key: AppKey,
+}
+
+/// The outcome of a successful [`KeyRotation::rotate`].
+#[derive(Clone)]
+pub struct RotatedKeys {
+    /// The parameters with the new key as their private key. The old
+    /// key is not kept as a fallback.
+    pub params: GithubAuthParams,
+
+    /// When the last JWT signed with the old key expires, after which
+    /// the old key can be deleted from the app settings.
+    pub old_key_deletable_at: SystemTime,
+}
+
+impl KeyRotation {
+    /// Prepare to rotate from the private key of `params` to `new_key`.
+    pub fn new(
+        params: GithubAuthParams,
+        new_key: AppKey,
+    ) -> Result<KeyRotation, AuthError> {
+        let client = params.build_client()?;
+        Ok(KeyRotation {
+            client,
+            params,
+            new_key,
+        })
+    }
+
+    /// The parameters with only the new key.
+    fn new_params(&self) -> GithubAuthParams {
+        GithubAuthParams {
+            private_key: self.new_key.private_key.clone(),
+            key_id: self.new_key.id.clone(),
+            fallback_keys: Vec::new(),
+            reloadable_key: None,
+            signer: None,
+            #[cfg(feature = "secrecy")]
+            secret_private_key: None,
+            ..self.params.clone()
+        }
+    }
+
+    /// Check whether GitHub accepts JWTs signed with the new key, by
+    /// getting the app with `GET /app`.
+    pub async fn verify_new_key(&self) -> Result<bool, AuthError> {
+        let params = self.new_params();
+        let url = format!("{}/app", params.api_base());
+        let (_, response) = send_as_app(&params, |jwt| {
+            self.client
+                .get(&url)
+                .bearer_auth(jwt)
+                .header("Accept", params.accept(GITHUB_JSON))
+        })
+        .await;
+        let response = response?;
+        if response.status() == StatusCode::UNAUTHORIZED {
+            return Ok(false);
+        }
+        check_status(&params, response).await?;
+        Ok(true)
+    }
+
+    /// Verify the new key and switch to it. Fails with
+    /// [`AuthError::KeyRejected`] if GitHub doesn't accept the new key
+    /// yet, for example because it was generated for another app.
+    pub async fn rotate(&self) -> Result<RotatedKeys, AuthError> {
+        if !self.verify_new_key().await? {
+            return Err(AuthError::KeyRejected(
+                self.new_key.id.clone().unwrap_or_default(),
+            ));
+        }
+        Ok(RotatedKeys {
+            params: self.new_params(),
+            old_key_deletable_at: SystemTime::now() + JWT_LIFETIME,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_params() {
+        let rotation = KeyRotation::new(
+            GithubAuthParams {
+                app_id: 1,
+                private_key: b"old".to_vec(),
+                key_id: Some("old".into()),
+                fallback_keys: vec![AppKey::default()],
+                ..Default::default()
+            },
+            AppKey {
+                id: Some("new".into()),
+                private_key: b"new".to_vec(),
+            },
+        )
+        .unwrap();
+        let params = rotation.new_params();
+        assert_eq!(params.app_id, 1);
+        assert_eq!(params.private_key, b"new");
+        assert_eq!(params.key_id.as_deref(), Some("new"));
+        assert!(params.fallback_keys.is_empty());
+    }
+}
@@ -0,0 +1,142 @@
+//! Helpers for provisioning self-hosted GitHub Actions runners.
+//!
+//! Registering a runner with `config.sh --token` and removing it
+//! again both require short-lived tokens that can only be created
+//! with API credentials. See
+//! [Self-hosted runners](https://docs.github.com/en/rest/actions/self-hosted-runners)
+//! for the required permissions.
+
+use crate::error_response::check_status;
+use crate::{
+    AuthError, InstallationAccessToken, RawInstallationAccessToken, GITHUB_JSON,
+};
+use std::fmt;
+use std::time::SystemTime;
+
+/// Where a self-hosted runner is registered.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RunnerScope {
+    /// A runner available to a single repository.
+    Repository {
+        /// Repository owner (user or organization login).
+        owner: String,
+        /// Repository name.
+        repo: String,
+    },
+
+    /// A runner available to an organization.
+    Organization(String),
+}
+
+impl RunnerScope {
+    /// Create a repository scope from `owner` and `repo`.
+    pub fn repository(owner: &str, repo: &str) -> RunnerScope {
+        RunnerScope::Repository {
+            owner: owner.into(),
+            repo: repo.into(),
+        }
+    }
+
+    /// Create an organization scope.
+    pub fn organization(org: &str) -> RunnerScope {
+        RunnerScope::Organization(org.into())
+    }
+
+    fn runners_path(&self) -> String {
+        match self {
+            RunnerScope::Repository { owner, repo } => {
+                format!("repos/{}/{}/actions/runners", owner, repo)
+            }
+            RunnerScope::Organization(org) => {
+                format!("orgs/{}/actions/runners", org)
+            }
+        }
+    }
+}
+
+/// A token for registering or removing a self-hosted runner.
+#[derive(Clone, Eq, PartialEq)]
+pub struct RunnerToken {
+    /// The token to pass to the runner's `config.sh --token`.
+    pub token: String,
+
+    /// When the token expires, typically one hour after creation.
+    pub expires_at: SystemTime,
+}
+
+impl From<RawInstallationAccessToken> for RunnerToken {
+    fn from(raw: RawInstallationAccessToken) -> RunnerToken {
+        RunnerToken {
+            token: raw.token,
+            expires_at: raw.expires_at,
+        }
+    }
+}
+
+// Written by hand so that the token doesn't end up in logs.
+impl fmt::Debug for RunnerToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RunnerToken")
+            .field("token", &"<redacted>")
+            .field("expires_at", &self.expires_at)
+            .finish()
+    }
+}
+
+impl InstallationAccessToken {
+    /// Create a token for registering a self-hosted runner.
+    pub async fn create_runner_registration_token(
+        &self,
+        scope: &RunnerScope,
+    ) -> Result<RunnerToken, AuthError> {
+        self.create_runner_token(scope, "registration-token").await
+    }
+
+    /// Create a token for removing a self-hosted runner.
+    pub async fn create_runner_remove_token(
+        &self,
+        scope: &RunnerScope,
+    ) -> Result<RunnerToken, AuthError> {
+        self.create_runner_token(scope, "remove-token").await
+    }
+
+    async fn create_runner_token(
+        &self,
+        scope: &RunnerScope,
+        kind: &str,
+    ) -> Result<RunnerToken, AuthError> {
+        let url = format!(
+            "{}/{}/{}",
+            self.params.api_base(),
+            scope.runners_path(),
+            kind
+        );
+        let request = self
+            .client
+            .post(&url)
+            .header("Accept", self.params.accept(GITHUB_JSON))
+            .build()?;
+        let response =
+            check_status(&self.params, self.execute(request).await?).await?;
+        Ok(RawInstallationAccessToken::from_response(response)
+            .await?
+            .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_runners_path() {
+        assert_eq!(
+            RunnerScope::repository("owner", "repo").runners_path(),
+            "repos/owner/repo/actions/runners"
+        );
+        assert_eq!(
+            RunnerScope::organization("org").runners_path(),
+            "orgs/org/actions/runners"
+        );
+    }
+}
@@ -0,0 +1,190 @@
+//! Signing app JWTs with keys that this crate can't read.
+//!
+//! Set [`GithubAuthParams::signer`](crate::GithubAuthParams::signer) to
+//! a [`JwtSigner`] to keep the private key in a hardware security
+//! module, a cloud key management service or an OS key store. The
+//! signer only has to produce the RS256 signature; the JWT itself is
+//! assembled by this crate.
+
+#[cfg(all(windows, feature = "windows-cng"))]
+pub mod cng;
+
+use crate::AuthError;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ring::rand::SystemRandom;
+use ring::signature::{RsaKeyPair, RSA_PKCS1_SHA256};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+
+/// How many parsed private keys one set of parameters keeps, so that
+/// a key that is reloaded often doesn't keep every old key alive.
+const PARSED_KEYS_SIZE: usize = 16;
+
+/// Produces RS256 signatures for app JWTs.
+pub trait JwtSigner: Send + Sync {
+    /// Sign `message` with RSASSA-PKCS1-v1_5 using SHA-256, and
+    /// return the signature. Return [`AuthError::SignerError`] if
+    /// signing fails.
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, AuthError>;
+}
+
+/// Encode a JWT with a signature from `signer`.
+pub(crate) fn encode(
+    signer: &dyn JwtSigner,
+    header: &jsonwebtoken::Header,
+    claims: &impl Serialize,
+) -> Result<String, AuthError> {
+    let message = format!(
+        "{}.{}",
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(header)?),
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(claims)?)
+    );
+    let signature = signer.sign(message.as_bytes())?;
+    Ok(format!("{}.{}", message, URL_SAFE_NO_PAD.encode(signature)))
+}
+
+/// An RSA private key parsed once and kept for signing many JWTs.
+pub(crate) struct RsaKey(RsaKeyPair);
+
+impl RsaKey {
+    /// Parse a PEM-encoded PKCS#1 or PKCS#8 RSA private key.
+    fn from_pem(pem: &[u8]) -> Result<RsaKey, AuthError> {
+        let invalid_format = || {
+            AuthError::JwtError(
+                jsonwebtoken::errors::ErrorKind::InvalidKeyFormat.into(),
+            )
+        };
+        let invalid = |_: ring::error::KeyRejected| {
+            AuthError::JwtError(
+                jsonwebtoken::errors::ErrorKind::InvalidRsaKey("key rejected")
+                    .into(),
+            )
+        };
+        let parsed = pem::parse(pem).map_err(|_| invalid_format())?;
+        let key_pair = match parsed.tag.as_str() {
+            "PRIVATE KEY" => {
+                RsaKeyPair::from_pkcs8(&parsed.contents).map_err(invalid)?
+            }
+            "RSA PRIVATE KEY" => {
+                RsaKeyPair::from_der(&parsed.contents).map_err(invalid)?
+            }
+            _ => return Err(invalid_format()),
+        };
+        Ok(RsaKey(key_pair))
+    }
+}
+
+/// The PEM keys of one [`GithubAuthParams`](crate::GithubAuthParams),
+/// parsed when they are first used. Clones of the parameters share
+/// them, and they are dropped with the last clone.
+///
+/// Keys are looked up by the SHA-256 digest of their PEM encoding,
+/// since the parameters' keys can be changed or reloaded after they
+/// were parsed.
+#[derive(Clone, Default)]
+pub(crate) struct ParsedKeys(Arc<Mutex<Vec<ParsedKey>>>);
+
+/// A parsed key and the digest of its PEM encoding.
+type ParsedKey = ([u8; 32], Arc<RsaKey>);
+
+impl ParsedKeys {
+    /// Get the parsed key for `pem`, parsing it if it wasn't before.
+    pub(crate) fn get(&self, pem: &[u8]) -> Result<Arc<RsaKey>, AuthError> {
+        let digest: [u8; 32] = ring::digest::digest(&ring::digest::SHA256, pem)
+            .as_ref()
+            .try_into()
+            .expect("SHA-256 digests are 32 bytes");
+        let mut keys = self.0.lock().unwrap();
+        if let Some((_, key)) = keys.iter().find(|(d, _)| *d == digest) {
+            return Ok(key.clone());
+        }
+        let key = Arc::new(RsaKey::from_pem(pem)?);
+        if keys.len() >= PARSED_KEYS_SIZE {
+            keys.remove(0);
+        }
+        keys.push((digest, key.clone()));
+        Ok(key)
+    }
+}
+
+impl JwtSigner for RsaKey {
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, AuthError> {
+        let mut signature = vec![0; self.0.public().modulus_len()];
+        self.0
+            .sign(
+                &RSA_PKCS1_SHA256,
+                &SystemRandom::new(),
+                message,
+                &mut signature,
+            )
+            .map_err(|_| AuthError::SignerError("RSA signing failed".into()))?;
+        Ok(signature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{create_app_jwt, GithubAuthParams};
+    use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Validation};
+    use std::sync::Arc;
+
+    struct PemSigner(EncodingKey);
+
+    impl JwtSigner for PemSigner {
+        fn sign(&self, message: &[u8]) -> Result<Vec<u8>, AuthError> {
+            let signature =
+                jsonwebtoken::crypto::sign(message, &self.0, Algorithm::RS256)?;
+            Ok(URL_SAFE_NO_PAD.decode(signature).unwrap())
+        }
+    }
+
+    #[test]
+    fn test_external_signer() {
+        let private_key = include_bytes!("../testdata/private-key.pem");
+        let public_key = include_bytes!("../testdata/public-key.pem");
+        let params = GithubAuthParams {
+            app_id: 1234,
+            key_id: Some("hsm".into()),
+            signer: Some(Arc::new(PemSigner(
+                EncodingKey::from_rsa_pem(private_key).unwrap(),
+            ))),
+            ..Default::default()
+        };
+        let jwt =
+            create_app_jwt(&params, &params.signing_keys()[0], 0).unwrap();
+        let decoded = jsonwebtoken::decode::<serde_json::Value>(
+            &jwt.token,
+            &DecodingKey::from_rsa_pem(public_key).unwrap(),
+            &Validation::new(Algorithm::RS256),
+        )
+        .unwrap();
+        assert_eq!(decoded.header.kid.as_deref(), Some("hsm"));
+        assert_eq!(decoded.claims["iss"], 1234);
+    }
+
+    #[test]
+    fn test_rsa_key_matches_jsonwebtoken() {
+        let pem = include_bytes!("../testdata/private-key.pem");
+        let keys = ParsedKeys::default();
+        let key = keys.get(pem).unwrap();
+        assert!(Arc::ptr_eq(&key, &keys.get(pem).unwrap()));
+        // Other parameters parse the key again.
+        assert!(!Arc::ptr_eq(&key, &ParsedKeys::default().get(pem).unwrap()));
+        let signature = jsonwebtoken::crypto::sign(
+            b"message",
+            &EncodingKey::from_rsa_pem(pem).unwrap(),
+            Algorithm::RS256,
+        )
+        .unwrap();
+        assert_eq!(
+            URL_SAFE_NO_PAD.encode(key.sign(b"message").unwrap()),
+            signature
+        );
+        assert!(keys.get(b"not a key").is_err());
+        assert!(keys
+            .get(include_bytes!("../testdata/public-key.pem"))
+            .is_err());
+    }
+}
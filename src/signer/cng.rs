@@ -0,0 +1,285 @@
+//! Signing app JWTs with a key in the Windows certificate store.
+//!
+//! Enabled by the `windows-cng` feature on Windows. The key stays with
+//! its CNG key storage provider, such as the TPM-backed Microsoft
+//! Platform Crypto Provider, so it can be marked non-exportable.
+//!
+//! ```no_run
+//! use github_app_auth::signer::cng::{CngSigner, StoreLocation};
+//! use github_app_auth::GithubAuthParams;
+//! use std::sync::Arc;
+//!
+//! let signer = CngSigner::from_certificate(
+//!     StoreLocation::LocalMachine,
+//!     "My",
+//!     "3b 9c 22 5f 1a 2e 8d 4a 0f 6c 7b 11 e3 90 54 d8 a2 6f 19 c4",
+//! )
+//! .unwrap();
+//! let params = GithubAuthParams::builder()
+//!     .user_agent("my-cool-user-agent")
+//!     .app_id(1234)
+//!     .installation_id(5678)
+//!     .signer(Arc::new(signer))
+//!     .build();
+//! ```
+
+use super::JwtSigner;
+use crate::AuthError;
+use std::ptr;
+use windows_sys::Win32::Security::Cryptography::{
+    CertCloseStore, CertFindCertificateInStore, CertFreeCertificateContext,
+    CertOpenStore, CryptAcquireCertificatePrivateKey, NCryptFreeObject,
+    NCryptOpenKey, NCryptOpenStorageProvider, NCryptSignHash, BCRYPT_PAD_PKCS1,
+    BCRYPT_PKCS1_PADDING_INFO, BCRYPT_SHA256_ALGORITHM, CERT_CONTEXT,
+    CERT_FIND_SHA1_HASH, CERT_STORE_OPEN_EXISTING_FLAG,
+    CERT_STORE_PROV_SYSTEM_W, CERT_STORE_READONLY_FLAG,
+    CERT_SYSTEM_STORE_CURRENT_USER, CERT_SYSTEM_STORE_LOCAL_MACHINE,
+    CRYPT_ACQUIRE_ONLY_NCRYPT_KEY_FLAG, CRYPT_INTEGER_BLOB, NCRYPT_KEY_HANDLE,
+    NCRYPT_PROV_HANDLE, PKCS_7_ASN_ENCODING, X509_ASN_ENCODING,
+};
+
+/// Which system certificate stores to look in.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StoreLocation {
+    /// The stores of the current user, such as `Cert:\CurrentUser\My`.
+    CurrentUser,
+
+    /// The stores of the machine, such as `Cert:\LocalMachine\My`.
+    LocalMachine,
+}
+
+/// A [`JwtSigner`] backed by a CNG key.
+#[derive(Debug)]
+pub struct CngSigner {
+    key: NCRYPT_KEY_HANDLE,
+    /// Whether the key handle has to be freed.
+    owns_key: bool,
+    /// The certificate the key was acquired from, which may own the key
+    /// handle. Null for keys opened by name.
+    cert: *const CERT_CONTEXT,
+}
+
+// CNG key handles can be used from any thread.
+unsafe impl Send for CngSigner {}
+unsafe impl Sync for CngSigner {}
+
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(Some(0)).collect()
+}
+
+/// Turn a failed `HRESULT` into an error.
+fn check(function: &str, status: i32) -> Result<(), AuthError> {
+    if status < 0 {
+        return Err(AuthError::SignerError(format!(
+            "{} failed: 0x{:08x}",
+            function, status
+        )));
+    }
+    Ok(())
+}
+
+fn last_error(function: &str) -> AuthError {
+    AuthError::SignerError(format!(
+        "{} failed: {}",
+        function,
+        std::io::Error::last_os_error()
+    ))
+}
+
+fn parse_thumbprint(thumbprint: &str) -> Result<Vec<u8>, AuthError> {
+    let digits: Vec<u8> = thumbprint
+        .bytes()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect();
+    let invalid = || {
+        AuthError::SignerError(format!("invalid thumbprint: {}", thumbprint))
+    };
+    if digits.len() != 40 {
+        return Err(invalid());
+    }
+    digits
+        .chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(invalid)
+        })
+        .collect()
+}
+
+impl CngSigner {
+    /// Use the private key of the certificate with the SHA-1
+    /// `thumbprint`, given in hex as shown by `certmgr` or PowerShell,
+    /// in the system store `store` such as `"My"`.
+    pub fn from_certificate(
+        location: StoreLocation,
+        store: &str,
+        thumbprint: &str,
+    ) -> Result<CngSigner, AuthError> {
+        let mut hash = parse_thumbprint(thumbprint)?;
+        let location = match location {
+            StoreLocation::CurrentUser => CERT_SYSTEM_STORE_CURRENT_USER,
+            StoreLocation::LocalMachine => CERT_SYSTEM_STORE_LOCAL_MACHINE,
+        };
+        let store_name = wide(store);
+        // SAFETY: the pointers passed are valid for the duration of
+        // each call, and every handle is closed or kept in the signer.
+        unsafe {
+            let store = CertOpenStore(
+                CERT_STORE_PROV_SYSTEM_W,
+                0,
+                0,
+                location
+                    | CERT_STORE_OPEN_EXISTING_FLAG
+                    | CERT_STORE_READONLY_FLAG,
+                store_name.as_ptr().cast(),
+            );
+            if store.is_null() {
+                return Err(last_error("CertOpenStore"));
+            }
+            let blob = CRYPT_INTEGER_BLOB {
+                cbData: hash.len() as u32,
+                pbData: hash.as_mut_ptr(),
+            };
+            let cert = CertFindCertificateInStore(
+                store,
+                X509_ASN_ENCODING | PKCS_7_ASN_ENCODING,
+                0,
+                CERT_FIND_SHA1_HASH,
+                ptr::addr_of!(blob).cast(),
+                ptr::null(),
+            );
+            // The certificate keeps the store open as long as needed.
+            CertCloseStore(store, 0);
+            if cert.is_null() {
+                return Err(last_error("CertFindCertificateInStore"));
+            }
+            let mut key = 0;
+            let mut key_spec = 0;
+            let mut owns_key = 0;
+            if CryptAcquireCertificatePrivateKey(
+                cert,
+                CRYPT_ACQUIRE_ONLY_NCRYPT_KEY_FLAG,
+                ptr::null(),
+                &mut key,
+                &mut key_spec,
+                &mut owns_key,
+            ) == 0
+            {
+                let err = last_error("CryptAcquireCertificatePrivateKey");
+                CertFreeCertificateContext(cert);
+                return Err(err);
+            }
+            Ok(CngSigner {
+                key,
+                owns_key: owns_key != 0,
+                cert,
+            })
+        }
+    }
+
+    /// Use the persisted key named `key_name` in the key storage
+    /// provider `provider`, such as
+    /// `"Microsoft Platform Crypto Provider"`.
+    pub fn open_key(
+        provider: &str,
+        key_name: &str,
+    ) -> Result<CngSigner, AuthError> {
+        let provider = wide(provider);
+        let key_name = wide(key_name);
+        // SAFETY: the pointers passed are valid for the duration of
+        // each call, and the provider handle is freed before returning.
+        unsafe {
+            let mut handle: NCRYPT_PROV_HANDLE = 0;
+            check(
+                "NCryptOpenStorageProvider",
+                NCryptOpenStorageProvider(&mut handle, provider.as_ptr(), 0),
+            )?;
+            let mut key = 0;
+            let status =
+                NCryptOpenKey(handle, &mut key, key_name.as_ptr(), 0, 0);
+            NCryptFreeObject(handle);
+            check("NCryptOpenKey", status)?;
+            Ok(CngSigner {
+                key,
+                owns_key: true,
+                cert: ptr::null(),
+            })
+        }
+    }
+}
+
+impl JwtSigner for CngSigner {
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, AuthError> {
+        let hash = ring::digest::digest(&ring::digest::SHA256, message);
+        let hash = hash.as_ref();
+        let padding = BCRYPT_PKCS1_PADDING_INFO {
+            pszAlgId: BCRYPT_SHA256_ALGORITHM,
+        };
+        let mut len = 0;
+        // SAFETY: the key handle is valid until the signer is dropped,
+        // and the buffers are valid for the duration of each call.
+        unsafe {
+            check(
+                "NCryptSignHash",
+                NCryptSignHash(
+                    self.key,
+                    ptr::addr_of!(padding).cast(),
+                    hash.as_ptr(),
+                    hash.len() as u32,
+                    ptr::null_mut(),
+                    0,
+                    &mut len,
+                    BCRYPT_PAD_PKCS1,
+                ),
+            )?;
+            let mut signature = vec![0; len as usize];
+            check(
+                "NCryptSignHash",
+                NCryptSignHash(
+                    self.key,
+                    ptr::addr_of!(padding).cast(),
+                    hash.as_ptr(),
+                    hash.len() as u32,
+                    signature.as_mut_ptr(),
+                    len,
+                    &mut len,
+                    BCRYPT_PAD_PKCS1,
+                ),
+            )?;
+            signature.truncate(len as usize);
+            Ok(signature)
+        }
+    }
+}
+
+impl Drop for CngSigner {
+    fn drop(&mut self) {
+        // SAFETY: the handles are not used after this.
+        unsafe {
+            if self.owns_key {
+                NCryptFreeObject(self.key);
+            }
+            if !self.cert.is_null() {
+                CertFreeCertificateContext(self.cert);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_thumbprint() {
+        let hash = parse_thumbprint(
+            "3b 9c 22 5f 1a 2e 8d 4a 0f 6c 7b 11 e3 90 54 d8 a2 6f 19 c4",
+        )
+        .unwrap();
+        assert_eq!(hash.len(), 20);
+        assert_eq!(hash[0], 0x3b);
+        assert!(parse_thumbprint("3b9c").is_err());
+    }
+}
@@ -0,0 +1,293 @@
+//! Testing token refreshes deterministically, with a simulated clock
+//! and scripted GitHub responses.
+//!
+//! Enabled by the `test-util` feature. A [`Simulation`] is registered
+//! as the [`hooks`](crate::GithubAuthParams::hooks) of some parameters.
+//! Requests sent with them are never sent; each gets the next scripted
+//! response instead, and tokens use the simulation's clock to decide
+//! when they have expired, so an hour can pass in a test without
+//! waiting for it:
+//!
+//! ```
+//! use github_app_auth::simulation::Simulation;
+//! use github_app_auth::{GithubAuthParams, InstallationAccessToken};
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! # async fn wrapper(private_key: Vec<u8>) {
+//! let simulation = Arc::new(Simulation::new());
+//! let token = InstallationAccessToken::lazy(
+//!     GithubAuthParams::builder()
+//!         .private_key(private_key)
+//!         .installation_id(5678)
+//!         .hooks(simulation.clone())
+//!         .build(),
+//! )
+//! .unwrap();
+//! simulation.respond_token("ghs_first", Duration::from_secs(3600));
+//! simulation.respond_token("ghs_second", Duration::from_secs(3600));
+//! assert_eq!(token.token().await.unwrap(), "ghs_first");
+//! simulation.advance(Duration::from_secs(3600));
+//! assert_eq!(token.token().await.unwrap(), "ghs_second");
+//! # }
+//! ```
+//!
+//! Only the times the crate compares token expiries with are
+//! simulated. Waits, such as for rate limits, still use Tokio's timer;
+//! pause it with Tokio's own `test-util` feature to skip them.
+
+use crate::{timestamp, AuthError, AuthEvent, HttpHooks};
+use reqwest::{Request, Response};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+/// A simulated clock and GitHub API.
+#[derive(Debug)]
+pub struct Simulation(Mutex<State>);
+
+#[derive(Debug)]
+struct State {
+    now: SystemTime,
+    script: VecDeque<Scripted>,
+    requests: Vec<String>,
+    events: Vec<AuthEvent>,
+}
+
+#[derive(Debug)]
+enum Scripted {
+    Token {
+        token: String,
+        valid_for: Duration,
+    },
+    Response {
+        status: u16,
+        body: serde_json::Value,
+    },
+}
+
+impl Default for Simulation {
+    fn default() -> Simulation {
+        Simulation::new()
+    }
+}
+
+impl Simulation {
+    /// Start a simulation at the current time, with no responses
+    /// scripted. The time is rounded down to a whole second, like the
+    /// timestamps in GitHub's responses.
+    pub fn new() -> Simulation {
+        let since_epoch = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+        Simulation::starting_at(
+            SystemTime::UNIX_EPOCH + Duration::from_secs(since_epoch.as_secs()),
+        )
+    }
+
+    /// Start a simulation at `now`.
+    pub fn starting_at(now: SystemTime) -> Simulation {
+        Simulation(Mutex::new(State {
+            now,
+            script: VecDeque::new(),
+            requests: Vec::new(),
+            events: Vec::new(),
+        }))
+    }
+
+    /// The simulated time.
+    pub fn now(&self) -> SystemTime {
+        self.0.lock().unwrap().now
+    }
+
+    /// Move the simulated time forward.
+    pub fn advance(&self, duration: Duration) {
+        self.0.lock().unwrap().now += duration;
+    }
+
+    /// Answer the next request without a scripted response with a new
+    /// installation access token, valid for `valid_for` from when it is
+    /// requested.
+    pub fn respond_token(&self, token: impl Into<String>, valid_for: Duration) {
+        self.0.lock().unwrap().script.push_back(Scripted::Token {
+            token: token.into(),
+            valid_for,
+        });
+    }
+
+    /// Answer the next request without a scripted response with
+    /// `status` and a JSON `body`.
+    pub fn respond(&self, status: u16, body: serde_json::Value) {
+        self.0
+            .lock()
+            .unwrap()
+            .script
+            .push_back(Scripted::Response { status, body });
+    }
+
+    /// Answer the next request without a scripted response with an
+    /// error `status` and a GitHub error message.
+    pub fn respond_error(&self, status: u16, message: &str) {
+        self.respond(
+            status,
+            serde_json::json!({
+                "message": message,
+                "documentation_url": "https://docs.github.com/rest",
+            }),
+        );
+    }
+
+    /// The requests answered so far, as method and path, such as
+    /// `"POST /app/installations/5678/access_tokens"`.
+    pub fn requests(&self) -> Vec<String> {
+        self.0.lock().unwrap().requests.clone()
+    }
+
+    /// The events reported to the hooks so far.
+    pub fn events(&self) -> Vec<AuthEvent> {
+        self.0.lock().unwrap().events.clone()
+    }
+
+    /// Number of scripted responses that haven't been used yet.
+    pub fn remaining(&self) -> usize {
+        self.0.lock().unwrap().script.len()
+    }
+
+    /// Answer a request with the next scripted response.
+    pub(crate) fn respond_to(
+        &self,
+        request: &Request,
+    ) -> Result<Response, AuthError> {
+        let mut state = self.0.lock().unwrap();
+        let request = format!("{} {}", request.method(), request.url().path());
+        let scripted = state.script.pop_front().ok_or_else(|| {
+            AuthError::ReplayError(format!("no response scripted: {}", request))
+        })?;
+        state.requests.push(request);
+        let (status, body) = match scripted {
+            Scripted::Token { token, valid_for } => (
+                201,
+                serde_json::json!({
+                    "token": token,
+                    "expires_at": timestamp::format(state.now + valid_for),
+                }),
+            ),
+            Scripted::Response { status, body } => (status, body),
+        };
+        let response = http::Response::builder()
+            .status(status)
+            .header("content-type", "application/json")
+            .body(body.to_string())
+            .map_err(|err| {
+                AuthError::ReplayError(format!("invalid response: {}", err))
+            })?;
+        Ok(response.into())
+    }
+}
+
+impl HttpHooks for Simulation {
+    fn on_event(&self, event: &AuthEvent) {
+        self.0.lock().unwrap().events.push(event.clone());
+    }
+
+    fn simulation(&self) -> Option<&Simulation> {
+        Some(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GithubAuthParams, InstallationAccessToken};
+    use std::sync::Arc;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    fn token(simulation: &Arc<Simulation>) -> InstallationAccessToken {
+        InstallationAccessToken::lazy(
+            GithubAuthParams::builder()
+                .private_key(
+                    include_bytes!("../testdata/private-key.pem").to_vec(),
+                )
+                .installation_id(2)
+                .hooks(simulation.clone())
+                .build(),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_refresh() {
+        let simulation = Arc::new(Simulation::new());
+        let token = token(&simulation);
+        simulation.respond_token("first", HOUR);
+        simulation.respond_token("second", HOUR);
+        assert_eq!(token.token().await.unwrap(), "first");
+        assert_eq!(token.expires_at(), simulation.now() + HOUR);
+
+        // The token is reused until it is within the safety margin of
+        // expiring.
+        simulation.advance(HOUR - token.refresh_safety_margin * 2);
+        assert_eq!(token.token().await.unwrap(), "first");
+        simulation.advance(token.refresh_safety_margin);
+        assert!(token.needs_refresh());
+        assert!(!token.is_expired());
+        assert_eq!(token.token().await.unwrap(), "second");
+        assert_eq!(
+            simulation.requests(),
+            ["POST /app/installations/2/access_tokens"; 2]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_refresh_is_retried() {
+        let simulation = Arc::new(Simulation::new());
+        let token = token(&simulation);
+        simulation.respond_token("first", HOUR);
+        token.connect().await.unwrap();
+
+        simulation.advance(HOUR);
+        assert!(token.is_expired());
+        simulation.respond_error(500, "Server Error");
+        assert!(token.token().await.is_err());
+        simulation.respond_token("second", HOUR);
+        assert_eq!(token.token().await.unwrap(), "second");
+        assert_eq!(simulation.remaining(), 0);
+        // Nothing is left to answer with.
+        simulation.advance(HOUR);
+        assert!(matches!(
+            token.token().await,
+            Err(AuthError::ReplayError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_stale_if_error() {
+        let simulation = Arc::new(Simulation::new());
+        let mut token = InstallationAccessToken::lazy(
+            GithubAuthParams::builder()
+                .private_key(
+                    include_bytes!("../testdata/private-key.pem").to_vec(),
+                )
+                .hooks(simulation.clone())
+                .stale_if_error(Duration::from_secs(10 * 60))
+                .build(),
+        )
+        .unwrap();
+        token.refresh_safety_margin = Duration::from_secs(30 * 60);
+        simulation.respond_token("first", HOUR);
+        token.connect().await.unwrap();
+
+        // 20 minutes are left, so the token is still used.
+        simulation.advance(Duration::from_secs(40 * 60));
+        simulation.respond_error(502, "Bad Gateway");
+        assert_eq!(token.token().await.unwrap(), "first");
+        // Only server errors are ignored.
+        simulation.respond_error(404, "Not Found");
+        assert!(token.token().await.is_err());
+        // With 5 minutes left, the failure is returned.
+        simulation.advance(Duration::from_secs(15 * 60));
+        simulation.respond_error(502, "Bad Gateway");
+        assert!(token.token().await.is_err());
+    }
+}
@@ -0,0 +1,61 @@
+//! Metrics about token minting and use.
+//!
+//! With the `metrics` feature enabled, the crate records the metrics
+//! below through the [`metrics`](https://docs.rs/metrics) facade. Install
+//! a recorder such as `metrics-exporter-prometheus` to expose them in
+//! Prometheus text format. Without the feature nothing is recorded.
+//!
+//! Metrics are not labelled by installation, so apps with many
+//! installations don't create a time series for each one.
+
+use std::time::Duration;
+
+/// Counter of installation access tokens minted successfully.
+pub const TOKENS_MINTED: &str = "github_app_auth_tokens_minted_total";
+
+/// Counter of failed attempts to mint an installation access token.
+pub const REFRESH_FAILURES: &str = "github_app_auth_refresh_failures_total";
+
+/// Histogram of how long minting a token took, in seconds.
+pub const MINT_DURATION: &str = "github_app_auth_mint_duration_seconds";
+
+/// Histogram of how old a token was when it was used for a request,
+/// in seconds.
+pub const TOKEN_AGE: &str = "github_app_auth_token_age_seconds";
+
+/// Counter of requests answered by a token broker, labelled with
+/// `result` set to `token` or `error`.
+pub const BROKER_REQUESTS: &str = "github_app_auth_broker_requests_total";
+
+pub(crate) fn record_mint(succeeded: bool, duration: Duration) {
+    #[cfg(feature = "metrics")]
+    {
+        if succeeded {
+            metrics::counter!(TOKENS_MINTED).increment(1);
+        } else {
+            metrics::counter!(REFRESH_FAILURES).increment(1);
+        }
+        metrics::histogram!(MINT_DURATION).record(duration);
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (succeeded, duration);
+}
+
+pub(crate) fn record_token_use(age: Duration) {
+    #[cfg(feature = "metrics")]
+    metrics::histogram!(TOKEN_AGE).record(age);
+    #[cfg(not(feature = "metrics"))]
+    let _ = age;
+}
+
+#[cfg(feature = "broker")]
+pub(crate) fn record_broker_request(succeeded: bool) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!(
+        BROKER_REQUESTS,
+        "result" => if succeeded { "token" } else { "error" }
+    )
+    .increment(1);
+    #[cfg(not(feature = "metrics"))]
+    let _ = succeeded;
+}
@@ -0,0 +1,164 @@
+//! RFC 3339 timestamps, as used by the GitHub API.
+//!
+//! These are parsed with chrono by default, or with the time crate if
+//! the `time` feature is enabled. Either way the public API only uses
+//! [`SystemTime`].
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serializer};
+use std::time::SystemTime;
+
+#[cfg(not(any(feature = "chrono", feature = "time")))]
+compile_error!("either the `chrono` or the `time` feature must be enabled");
+
+/// Parse an RFC 3339 timestamp such as `2016-07-11T22:14:10Z`.
+#[cfg(feature = "time")]
+pub(crate) fn parse(s: &str) -> Option<SystemTime> {
+    use ::time::format_description::well_known::Rfc3339;
+    ::time::OffsetDateTime::parse(s, &Rfc3339)
+        .ok()
+        .map(SystemTime::from)
+}
+
+/// Parse an RFC 3339 timestamp such as `2016-07-11T22:14:10Z`.
+#[cfg(all(feature = "chrono", not(feature = "time")))]
+pub(crate) fn parse(s: &str) -> Option<SystemTime> {
+    chrono::DateTime::parse_from_rfc3339(s)
+        .ok()
+        .map(SystemTime::from)
+}
+
+/// Parse an HTTP date such as `Mon, 11 Jul 2016 22:14:10 GMT`, as sent
+/// in the `Date` header.
+#[cfg(feature = "time")]
+pub(crate) fn parse_http_date(s: &str) -> Option<SystemTime> {
+    use ::time::format_description::well_known::Rfc2822;
+    ::time::OffsetDateTime::parse(s, &Rfc2822)
+        .ok()
+        .map(SystemTime::from)
+}
+
+/// Parse an HTTP date such as `Mon, 11 Jul 2016 22:14:10 GMT`, as sent
+/// in the `Date` header.
+#[cfg(all(feature = "chrono", not(feature = "time")))]
+pub(crate) fn parse_http_date(s: &str) -> Option<SystemTime> {
+    chrono::DateTime::parse_from_rfc2822(s)
+        .ok()
+        .map(SystemTime::from)
+}
+
+#[cfg(all(test, feature = "time"))]
+pub(crate) fn format_http_date(t: SystemTime) -> String {
+    use ::time::format_description::well_known::Rfc2822;
+    ::time::OffsetDateTime::from(t).format(&Rfc2822).unwrap()
+}
+
+#[cfg(all(test, feature = "chrono", not(feature = "time")))]
+pub(crate) fn format_http_date(t: SystemTime) -> String {
+    chrono::DateTime::<chrono::Utc>::from(t).to_rfc2822()
+}
+
+/// Format a time as an RFC 3339 timestamp in UTC with whole seconds.
+#[cfg_attr(not(feature = "broker"), allow(dead_code))]
+#[cfg(feature = "time")]
+pub(crate) fn format(t: SystemTime) -> String {
+    use ::time::format_description::well_known::Rfc3339;
+    ::time::OffsetDateTime::from(truncate(t))
+        .format(&Rfc3339)
+        .expect("timestamp out of range")
+}
+
+/// Format a time as an RFC 3339 timestamp in UTC with whole seconds.
+#[cfg_attr(not(feature = "broker"), allow(dead_code))]
+#[cfg(all(feature = "chrono", not(feature = "time")))]
+pub(crate) fn format(t: SystemTime) -> String {
+    chrono::DateTime::<chrono::Utc>::from(t)
+        .to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
+
+/// Drop fractional seconds, which GitHub never sends.
+#[cfg(feature = "time")]
+fn truncate(t: SystemTime) -> SystemTime {
+    match t.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(d) => {
+            SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(d.as_secs())
+        }
+        Err(_) => t,
+    }
+}
+
+/// For `#[serde(with = "timestamp")]`.
+pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<SystemTime, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    parse(&s)
+        .ok_or_else(|| D::Error::custom(format!("invalid timestamp: {}", s)))
+}
+
+/// For `#[serde(with = "timestamp")]`.
+#[cfg_attr(not(feature = "broker"), allow(dead_code))]
+pub(crate) fn serialize<S: Serializer>(
+    t: &SystemTime,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format(*t))
+}
+
+/// For `#[serde(with = "timestamp::option")]`.
+pub(crate) mod option {
+    use super::*;
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<SystemTime>, D::Error> {
+        match Option::<String>::deserialize(deserializer)? {
+            Some(s) => parse(&s).map(Some).ok_or_else(|| {
+                D::Error::custom(format!("invalid timestamp: {}", s))
+            }),
+            None => Ok(None),
+        }
+    }
+
+    pub(crate) fn serialize<S: Serializer>(
+        t: &Option<SystemTime>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match t {
+            Some(t) => super::serialize(t, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_round_trip() {
+        let t = SystemTime::UNIX_EPOCH + Duration::from_secs(1468275250);
+        assert_eq!(parse("2016-07-11T22:14:10Z"), Some(t));
+        assert_eq!(parse("2016-07-12T00:14:10+02:00"), Some(t));
+        assert_eq!(parse("yesterday"), None);
+        assert_eq!(format(t), "2016-07-11T22:14:10Z");
+        assert_eq!(format(t + Duration::from_millis(500)), format(t));
+        assert_eq!(parse_http_date("Mon, 11 Jul 2016 22:14:10 GMT"), Some(t));
+    }
+
+    proptest! {
+        #[test]
+        fn test_parse_any(s in "\\PC*") {
+            let _ = parse(&s);
+            let _ = parse_http_date(&s);
+        }
+
+        #[test]
+        fn test_format_parse(secs in 0u64..253402300799) {
+            let t = SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+            prop_assert_eq!(parse(&format(t)), Some(t));
+        }
+    }
+}
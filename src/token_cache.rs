@@ -0,0 +1,161 @@
+//! Sharing installation access tokens between short-lived processes.
+//!
+//! A process that only lives for one request, such as an AWS Lambda
+//! invocation, would otherwise mint a new token every time it starts.
+//! Set [`GithubAuthParams::token_cache`] to a [`TokenCache`] backed by
+//! an external store, such as a DynamoDB table or an SSM parameter, and
+//! tokens are loaded from it instead of minted while they are still
+//! valid. New tokens are only written back when
+//! [`persist`](crate::InstallationAccessToken::persist) is called, so
+//! an invocation decides itself whether the write is worth its time.
+//!
+//! ```
+//! use github_app_auth::token_cache::MemoryTokenCache;
+//! use github_app_auth::{GithubAuthParams, InstallationAccessToken};
+//! use std::sync::Arc;
+//!
+//! # async fn wrapper() -> Result<(), github_app_auth::AuthError> {
+//! // In a real deployment this would be an external store that
+//! // outlives the process.
+//! let cache = Arc::new(MemoryTokenCache::default());
+//! let token = InstallationAccessToken::lazy(
+//!     GithubAuthParams::builder()
+//!         .user_agent("my-cool-user-agent")
+//!         .private_key(b"my private key".to_vec())
+//!         .app_id(1234)
+//!         .installation_id(5678)
+//!         .token_cache(cache)
+//!         .build(),
+//! )?;
+//! // Loads the token from the cache, or mints one if there is none.
+//! let header = token.header().await?;
+//! // Send requests with the header, then keep the token for the
+//! // next invocation.
+//! token.persist().await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`InstallationAccessToken::lazy`](crate::InstallationAccessToken::lazy)
+//! does no network I/O, so creating the token during a cold start is
+//! cheap; don't call
+//! [`warm_up`](crate::InstallationAccessToken::warm_up) if the token
+//! is usually found in the cache.
+
+use crate::{AuthError, GithubAuthParams, TokenInfo};
+use futures_util::future::BoxFuture;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// An external store for installation access tokens.
+///
+/// Tokens are stored by the key from [`cache_key`], which tells apart
+/// installations and scoped tokens of the same installation. Return
+/// [`AuthError::CacheError`] if the store fails.
+pub trait TokenCache: Send + Sync {
+    /// Get the token stored under `key`, if any. The token may have
+    /// expired; it is checked before it is used.
+    fn load<'a>(
+        &'a self,
+        key: &'a str,
+    ) -> BoxFuture<'a, Result<Option<TokenInfo>, AuthError>>;
+
+    /// Store `token` under `key`, replacing any previous token. Stores
+    /// with a time to live can expire the entry at
+    /// [`token.expires_at`](TokenInfo::expires_at).
+    fn store<'a>(
+        &'a self,
+        key: &'a str,
+        token: &'a TokenInfo,
+    ) -> BoxFuture<'a, Result<(), AuthError>>;
+}
+
+/// The key under which tokens for `params` are cached. It contains the
+/// API base, the app and installation IDs, and the repositories and
+/// permissions the token is scoped to, but no secrets.
+pub fn cache_key(params: &GithubAuthParams) -> String {
+    let mut key = format!(
+        "{}/app/{}/installation/{}",
+        params.api_base(),
+        params.app_id,
+        params.installation_id
+    );
+    if !params.repositories.is_empty() {
+        let mut repositories = params.repositories.clone();
+        repositories.sort_unstable();
+        key.push_str("?repositories=");
+        key.push_str(&repositories.join(","));
+    }
+    if !params.permissions.is_empty() {
+        key.push_str(if params.repositories.is_empty() {
+            "?permissions="
+        } else {
+            "&permissions="
+        });
+        let permissions: Vec<_> = params
+            .permissions
+            .iter()
+            .map(|(name, level)| format!("{}:{}", name, level))
+            .collect();
+        key.push_str(&permissions.join(","));
+    }
+    key
+}
+
+/// A [`TokenCache`] in the memory of the process, for tests and for
+/// sharing tokens between parameters in one process.
+#[derive(Debug, Default)]
+pub struct MemoryTokenCache {
+    tokens: Mutex<HashMap<String, TokenInfo>>,
+}
+
+impl TokenCache for MemoryTokenCache {
+    fn load<'a>(
+        &'a self,
+        key: &'a str,
+    ) -> BoxFuture<'a, Result<Option<TokenInfo>, AuthError>> {
+        let token = self.tokens.lock().unwrap().get(key).cloned();
+        Box::pin(async move { Ok(token) })
+    }
+
+    fn store<'a>(
+        &'a self,
+        key: &'a str,
+        token: &'a TokenInfo,
+    ) -> BoxFuture<'a, Result<(), AuthError>> {
+        self.tokens
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), token.clone());
+        Box::pin(async { Ok(()) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_key() {
+        let params = GithubAuthParams::builder()
+            .app_id(1)
+            .installation_id(2)
+            .build();
+        assert_eq!(
+            cache_key(&params),
+            "https://api.github.com/app/1/installation/2"
+        );
+        let scoped = GithubAuthParams::builder()
+            .app_id(1)
+            .installation_id(2)
+            .repository("b")
+            .repository("a")
+            .permission("contents", "read")
+            .build();
+        assert_eq!(
+            cache_key(&scoped),
+            "https://api.github.com/app/1/installation/2\
+             ?repositories=a,b&permissions=contents:read"
+        );
+    }
+}
@@ -0,0 +1,262 @@
+//! Verifying webhook deliveries from GitHub.
+//!
+//! GitHub signs each delivery with the webhook secret configured for
+//! the app and sends the signature in the `X-Hub-Signature-256`
+//! header. See
+//! [Validating webhook deliveries](https://docs.github.com/en/webhooks/using-webhooks/validating-webhook-deliveries).
+
+use crate::TokenManager;
+use ring::hmac;
+use serde::de::DeserializeOwned;
+use std::fmt;
+use std::sync::Arc;
+
+#[cfg(feature = "actix-web")]
+pub mod actix;
+#[cfg(feature = "axum")]
+pub mod axum;
+pub mod dedup;
+#[cfg(feature = "webhook-events")]
+pub mod events;
+#[cfg(feature = "warp")]
+pub mod warp;
+
+/// Header containing the HMAC-SHA256 signature of the payload.
+pub const SIGNATURE_HEADER: &str = "x-hub-signature-256";
+
+/// Header containing the name of the event, such as `push`.
+pub const EVENT_HEADER: &str = "x-github-event";
+
+/// Header containing the unique ID of the delivery.
+pub const DELIVERY_HEADER: &str = "x-github-delivery";
+
+/// Webhook verification error enum.
+#[derive(thiserror::Error, Debug, Eq, PartialEq)]
+pub enum WebhookError {
+    /// The delivery has no signature header.
+    #[error("missing {SIGNATURE_HEADER} header")]
+    MissingSignature,
+
+    /// The signature header is not of the form `sha256=<hex digest>`.
+    #[error("malformed signature")]
+    MalformedSignature,
+
+    /// The signature does not match the payload and secret.
+    #[error("signature does not match")]
+    SignatureMismatch,
+
+    /// A required header other than the signature is missing or not
+    /// valid UTF-8.
+    #[error("missing {0} header")]
+    MissingHeader(&'static str),
+
+    /// The payload is not valid JSON.
+    #[error("invalid payload: {0}")]
+    InvalidPayload(String),
+}
+
+impl WebhookError {
+    /// Whether the error means the delivery didn't come from GitHub, as
+    /// opposed to being malformed.
+    #[cfg(any(feature = "actix-web", feature = "axum", feature = "warp"))]
+    fn is_unauthorized(&self) -> bool {
+        matches!(
+            self,
+            WebhookError::MissingSignature
+                | WebhookError::MalformedSignature
+                | WebhookError::SignatureMismatch
+        )
+    }
+}
+
+/// The secret configured for an app's webhooks.
+///
+/// Cloning is cheap, so it can be kept in web framework state.
+#[derive(Clone)]
+pub struct WebhookSecret(Arc<[u8]>);
+
+impl WebhookSecret {
+    /// Wrap a webhook secret.
+    pub fn new(secret: impl Into<Vec<u8>>) -> WebhookSecret {
+        WebhookSecret(secret.into().into())
+    }
+
+    /// Verify an `X-Hub-Signature-256` header value against a payload.
+    /// See [`verify_signature`].
+    pub fn verify(
+        &self,
+        payload: &[u8],
+        signature: &str,
+    ) -> Result<(), WebhookError> {
+        verify_signature(&self.0, payload, signature)
+    }
+}
+
+// Written by hand so that the secret doesn't end up in logs.
+impl fmt::Debug for WebhookSecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("WebhookSecret").field(&"<redacted>").finish()
+    }
+}
+
+/// Application state for a GitHub app server, for the web framework
+/// integrations.
+#[derive(Clone)]
+pub struct GithubState {
+    /// Secret used to verify incoming webhooks.
+    pub webhook_secret: WebhookSecret,
+
+    /// Tokens for calling the API in response to webhooks.
+    pub tokens: Arc<TokenManager>,
+}
+
+/// A webhook delivery whose signature has been verified.
+#[derive(Clone, Debug)]
+pub struct Delivery {
+    /// Name of the event, such as `push`.
+    pub event: String,
+
+    /// Unique ID of the delivery. Redeliveries keep the same ID.
+    pub delivery_id: String,
+
+    /// ID of the installation the event is for, if any. This is what
+    /// to mint an installation access token for when handling it.
+    pub installation_id: Option<u64>,
+
+    /// The parsed JSON payload.
+    pub payload: serde_json::Value,
+}
+
+impl Delivery {
+    /// Verify a delivery from its header values and raw body, and
+    /// parse the payload.
+    ///
+    /// Framework integrations call this with the values of
+    /// [`EVENT_HEADER`], [`DELIVERY_HEADER`] and [`SIGNATURE_HEADER`].
+    pub fn verify(
+        secret: &WebhookSecret,
+        event: Option<&str>,
+        delivery_id: Option<&str>,
+        signature: Option<&str>,
+        body: &[u8],
+    ) -> Result<Delivery, WebhookError> {
+        let signature = signature.ok_or(WebhookError::MissingSignature)?;
+        secret.verify(body, signature)?;
+        let event = event.ok_or(WebhookError::MissingHeader(EVENT_HEADER))?;
+        let delivery_id =
+            delivery_id.ok_or(WebhookError::MissingHeader(DELIVERY_HEADER))?;
+        let payload: serde_json::Value = serde_json::from_slice(body)
+            .map_err(|err| WebhookError::InvalidPayload(err.to_string()))?;
+        let installation_id = payload
+            .get("installation")
+            .and_then(|installation| installation.get("id"))
+            .and_then(serde_json::Value::as_u64);
+        Ok(Delivery {
+            event: event.to_string(),
+            delivery_id: delivery_id.to_string(),
+            installation_id,
+            payload,
+        })
+    }
+
+    /// Deserialize the payload into a typed struct.
+    pub fn parse<T: DeserializeOwned>(&self) -> Result<T, serde_json::Error> {
+        T::deserialize(&self.payload)
+    }
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Verify an `X-Hub-Signature-256` header value against a payload.
+///
+/// `payload` must be the raw request body exactly as received; parsing
+/// and re-serializing the JSON changes the bytes and breaks the
+/// signature. The comparison is done in constant time.
+pub fn verify_signature(
+    secret: &[u8],
+    payload: &[u8],
+    signature: &str,
+) -> Result<(), WebhookError> {
+    let digest = signature
+        .strip_prefix("sha256=")
+        .and_then(decode_hex)
+        .ok_or(WebhookError::MalformedSignature)?;
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret);
+    hmac::verify(&key, payload, &digest)
+        .map_err(|_| WebhookError::SignatureMismatch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Example from GitHub's documentation.
+    pub(crate) const SECRET: &[u8] = b"It's a Secret to Everybody";
+    const PAYLOAD: &[u8] = b"Hello, World!";
+    const SIGNATURE: &str = "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+
+    #[test]
+    fn test_verify_signature() {
+        assert_eq!(verify_signature(SECRET, PAYLOAD, SIGNATURE), Ok(()));
+        assert_eq!(
+            verify_signature(b"wrong secret", PAYLOAD, SIGNATURE),
+            Err(WebhookError::SignatureMismatch)
+        );
+        assert_eq!(
+            verify_signature(SECRET, b"Hello, World?", SIGNATURE),
+            Err(WebhookError::SignatureMismatch)
+        );
+        assert_eq!(
+            verify_signature(SECRET, PAYLOAD, &SIGNATURE[7..]),
+            Err(WebhookError::MalformedSignature)
+        );
+        assert_eq!(
+            verify_signature(SECRET, PAYLOAD, "sha256=zz"),
+            Err(WebhookError::MalformedSignature)
+        );
+    }
+
+    #[test]
+    fn test_delivery_verify() {
+        let secret = WebhookSecret::new(SECRET);
+        let body = br#"{"action":"created","installation":{"id":42}}"#;
+        let signature = sign(body);
+        let delivery = Delivery::verify(
+            &secret,
+            Some("installation"),
+            Some("72d3162e"),
+            Some(&signature),
+            body,
+        )
+        .unwrap();
+        assert_eq!(delivery.event, "installation");
+        assert_eq!(delivery.installation_id, Some(42));
+        assert_eq!(
+            Delivery::verify(&secret, Some("push"), Some("1"), None, body)
+                .unwrap_err(),
+            WebhookError::MissingSignature
+        );
+        assert_eq!(
+            Delivery::verify(&secret, None, Some("1"), Some(&signature), body)
+                .unwrap_err(),
+            WebhookError::MissingHeader(EVENT_HEADER)
+        );
+    }
+
+    /// Compute the signature header value for `body` with [`SECRET`].
+    pub(crate) fn sign(body: &[u8]) -> String {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, SECRET);
+        let tag = hmac::sign(&key, body);
+        let hex: String =
+            tag.as_ref().iter().map(|b| format!("{:02x}", b)).collect();
+        format!("sha256={}", hex)
+    }
+}
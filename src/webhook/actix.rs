@@ -0,0 +1,134 @@
+//! Receiving webhooks and sharing tokens in [actix-web](actix_web)
+//! servers.
+//!
+//! [`Delivery`] can be used as an extractor in any handler of an app
+//! that has either a [`WebhookSecret`] or a `web::Data<GithubState>`
+//! registered as app data. Requests with a missing or wrong signature
+//! are rejected with `401 Unauthorized` before the handler runs.
+//!
+//! ```no_run
+//! use actix_web::{web, App, HttpServer};
+//! use github_app_auth::webhook::{Delivery, GithubState, WebhookSecret};
+//! use github_app_auth::{GithubAuthParams, TokenManager};
+//! use std::sync::Arc;
+//!
+//! async fn webhook(state: web::Data<GithubState>, delivery: Delivery) -> &'static str {
+//!     if let Some(installation_id) = delivery.installation_id {
+//!         let header = state.tokens.header_for(1234, installation_id).await;
+//!         // ...
+//!     }
+//!     "ok"
+//! }
+//!
+//! # async fn wrapper(params: GithubAuthParams) -> std::io::Result<()> {
+//! let state = web::Data::new(GithubState {
+//!     webhook_secret: WebhookSecret::new("my webhook secret"),
+//!     tokens: Arc::new(TokenManager::for_app(params).unwrap()),
+//! });
+//! HttpServer::new(move || {
+//!     App::new()
+//!         .app_data(state.clone())
+//!         .route("/webhook", web::post().to(webhook))
+//! })
+//! .bind("127.0.0.1:8080")?
+//! .run()
+//! .await
+//! # }
+//! ```
+
+use super::{
+    Delivery, GithubState, WebhookError, WebhookSecret, DELIVERY_HEADER,
+    EVENT_HEADER, SIGNATURE_HEADER,
+};
+use actix_web::dev::Payload;
+use actix_web::http::StatusCode;
+use actix_web::web::{Bytes, Data};
+use actix_web::{FromRequest, HttpRequest, ResponseError};
+use std::future::Future;
+use std::pin::Pin;
+
+impl FromRequest for Delivery {
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Delivery, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        let body = Bytes::from_request(&req, payload);
+        Box::pin(async move {
+            let secret = req
+                .app_data::<WebhookSecret>()
+                .cloned()
+                .or_else(|| {
+                    req.app_data::<Data<GithubState>>()
+                        .map(|state| state.webhook_secret.clone())
+                })
+                .ok_or_else(|| {
+                    actix_web::error::ErrorInternalServerError(
+                        "no webhook secret configured",
+                    )
+                })?;
+            let body = body.await?;
+            let header = |name| {
+                req.headers()
+                    .get(name)
+                    .and_then(|value| value.to_str().ok())
+            };
+            Ok(Delivery::verify(
+                &secret,
+                header(EVENT_HEADER),
+                header(DELIVERY_HEADER),
+                header(SIGNATURE_HEADER),
+                &body,
+            )?)
+        })
+    }
+}
+
+impl ResponseError for WebhookError {
+    fn status_code(&self) -> StatusCode {
+        if self.is_unauthorized() {
+            StatusCode::UNAUTHORIZED
+        } else {
+            StatusCode::BAD_REQUEST
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webhook::tests::{sign, SECRET};
+    use actix_web::{test, web, App};
+
+    async fn handler(delivery: Delivery) -> String {
+        format!("{} {:?}", delivery.event, delivery.installation_id)
+    }
+
+    #[actix_web::test]
+    async fn test_delivery_extractor() {
+        let app = test::init_service(
+            App::new()
+                .app_data(WebhookSecret::new(SECRET))
+                .route("/webhook", web::post().to(handler)),
+        )
+        .await;
+        let body = r#"{"installation":{"id":7}}"#;
+        let request = |signature: String| {
+            test::TestRequest::post()
+                .uri("/webhook")
+                .insert_header((EVENT_HEADER, "push"))
+                .insert_header((DELIVERY_HEADER, "1"))
+                .insert_header((SIGNATURE_HEADER, signature))
+                .set_payload(body)
+                .to_request()
+        };
+
+        let response =
+            test::call_service(&app, request(sign(body.as_bytes()))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(&test::read_body(response).await[..], b"push Some(7)");
+
+        let response = test::call_service(&app, request(sign(b"{}"))).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,138 @@
+//! Receiving webhooks and sharing tokens in [axum] servers.
+//!
+//! [`Delivery`] can be used as an extractor in any handler whose state
+//! provides a [`WebhookSecret`] through [`FromRef`]. Requests with a
+//! missing or wrong signature are rejected with `401 Unauthorized`
+//! before the handler runs. [`GithubState`] bundles the secret with a
+//! [`TokenManager`] for minting tokens for the delivery's installation.
+//!
+//! ```no_run
+//! use axum::extract::State;
+//! use axum::routing::post;
+//! use axum::Router;
+//! use github_app_auth::webhook::{Delivery, GithubState, WebhookSecret};
+//! use github_app_auth::{GithubAuthParams, TokenManager};
+//! use std::sync::Arc;
+//!
+//! async fn webhook(State(state): State<GithubState>, delivery: Delivery) {
+//!     if let Some(installation_id) = delivery.installation_id {
+//!         let header = state.tokens.header_for(1234, installation_id).await;
+//!         // ...
+//!     }
+//! }
+//!
+//! # async fn wrapper(params: GithubAuthParams) {
+//! let state = GithubState {
+//!     webhook_secret: WebhookSecret::new("my webhook secret"),
+//!     tokens: Arc::new(TokenManager::for_app(params).unwrap()),
+//! };
+//! let app = Router::new().route("/webhook", post(webhook)).with_state(state);
+//! let listener = tokio::net::TcpListener::bind("127.0.0.1:8080").await.unwrap();
+//! axum::serve(listener, app).await.unwrap();
+//! # }
+//! ```
+
+use super::{
+    Delivery, GithubState, WebhookError, WebhookSecret, DELIVERY_HEADER,
+    EVENT_HEADER, SIGNATURE_HEADER,
+};
+use crate::TokenManager;
+use axum::body::Bytes;
+use axum::extract::{FromRef, FromRequest, Request};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use std::sync::Arc;
+
+impl FromRef<GithubState> for WebhookSecret {
+    fn from_ref(state: &GithubState) -> WebhookSecret {
+        state.webhook_secret.clone()
+    }
+}
+
+impl FromRef<GithubState> for Arc<TokenManager> {
+    fn from_ref(state: &GithubState) -> Arc<TokenManager> {
+        state.tokens.clone()
+    }
+}
+
+impl<S> FromRequest<S> for Delivery
+where
+    WebhookSecret: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Response> {
+        let secret = WebhookSecret::from_ref(state);
+        let headers = req.headers().clone();
+        let body = Bytes::from_request(req, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let header = |name| header_str(&headers, name);
+        Delivery::verify(
+            &secret,
+            header(EVENT_HEADER),
+            header(DELIVERY_HEADER),
+            header(SIGNATURE_HEADER),
+            &body,
+        )
+        .map_err(IntoResponse::into_response)
+    }
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+impl IntoResponse for WebhookError {
+    fn into_response(self) -> Response {
+        let status = if self.is_unauthorized() {
+            StatusCode::UNAUTHORIZED
+        } else {
+            StatusCode::BAD_REQUEST
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webhook::tests::{sign, SECRET};
+    use axum::routing::post;
+    use axum::Router;
+    use tower::ServiceExt;
+
+    async fn handler(delivery: Delivery) -> String {
+        format!("{} {:?}", delivery.event, delivery.installation_id)
+    }
+
+    fn app() -> Router {
+        Router::new()
+            .route("/webhook", post(handler))
+            .with_state(WebhookSecret::new(SECRET))
+    }
+
+    fn request(signature: &str) -> Request {
+        Request::post("/webhook")
+            .header(EVENT_HEADER, "push")
+            .header(DELIVERY_HEADER, "1")
+            .header(SIGNATURE_HEADER, signature)
+            .body(r#"{"installation":{"id":7}}"#.into())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_delivery_extractor() {
+        let body = br#"{"installation":{"id":7}}"#;
+        let response = app().oneshot(request(&sign(body))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"push Some(7)");
+
+        let response = app().oneshot(request(&sign(b"{}"))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,128 @@
+//! Deduplicating webhook deliveries.
+//!
+//! GitHub may deliver the same webhook more than once, for example
+//! when a delivery is redelivered from the app settings or a response
+//! timed out. Every attempt carries the same
+//! [`DELIVERY_HEADER`](super::DELIVERY_HEADER) ID, so handlers can skip
+//! deliveries they have already seen by recording the IDs in a
+//! [`DeliveryStore`].
+//!
+//! ```
+//! use github_app_auth::webhook::dedup::{DeliveryStore, MemoryDeliveryStore};
+//! use std::time::Duration;
+//!
+//! # async fn wrapper() {
+//! let store = MemoryDeliveryStore::new(Duration::from_secs(24 * 60 * 60));
+//! assert!(store.insert("72d3162e").await.unwrap());
+//! // A redelivery is recognized.
+//! assert!(!store.insert("72d3162e").await.unwrap());
+//! # }
+//! ```
+
+use super::Delivery;
+use std::collections::{HashSet, VecDeque};
+use std::convert::Infallible;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A record of delivery IDs that have been processed.
+///
+/// Implement this for an external store, such as Redis or a database
+/// table with a unique key, to deduplicate across several servers.
+pub trait DeliveryStore {
+    /// Error returned by the store.
+    type Error;
+
+    /// Record a delivery ID, returning `true` if it had not been
+    /// recorded before. This must be atomic, so that only one of
+    /// several concurrent calls with the same ID returns `true`.
+    fn insert(
+        &self,
+        delivery_id: &str,
+    ) -> impl Future<Output = Result<bool, Self::Error>> + Send;
+}
+
+impl Delivery {
+    /// Record the delivery in `store`, returning `true` if this is the
+    /// first time it has been seen and should be processed.
+    pub async fn is_first<S: DeliveryStore>(
+        &self,
+        store: &S,
+    ) -> Result<bool, S::Error> {
+        store.insert(&self.delivery_id).await
+    }
+}
+
+/// An in-memory [`DeliveryStore`] that forgets IDs after a fixed time.
+///
+/// IDs are only shared within one process, and are lost on restart.
+pub struct MemoryDeliveryStore {
+    ttl: Duration,
+    seen: Mutex<Seen>,
+}
+
+#[derive(Default)]
+struct Seen {
+    ids: HashSet<String>,
+    /// IDs in the order they were inserted, which is also the order in
+    /// which they expire. `None` never expires, for a `ttl` too long to
+    /// add to the time.
+    expiry: VecDeque<(Option<Instant>, String)>,
+}
+
+impl MemoryDeliveryStore {
+    /// Create a store that remembers each ID for `ttl`. GitHub only
+    /// allows redelivering webhooks from the past three days.
+    pub fn new(ttl: Duration) -> MemoryDeliveryStore {
+        MemoryDeliveryStore {
+            ttl,
+            seen: Mutex::default(),
+        }
+    }
+
+    fn insert_at(&self, delivery_id: &str, now: Instant) -> bool {
+        let mut seen = self.seen.lock().unwrap();
+        while let Some((expires_at, _)) = seen.expiry.front() {
+            if expires_at.is_none_or(|expires_at| expires_at > now) {
+                break;
+            }
+            let (_, id) = seen.expiry.pop_front().unwrap();
+            seen.ids.remove(&id);
+        }
+        if !seen.ids.insert(delivery_id.to_string()) {
+            return false;
+        }
+        seen.expiry
+            .push_back((now.checked_add(self.ttl), delivery_id.to_string()));
+        true
+    }
+}
+
+impl DeliveryStore for MemoryDeliveryStore {
+    type Error = Infallible;
+
+    async fn insert(&self, delivery_id: &str) -> Result<bool, Infallible> {
+        Ok(self.insert_at(delivery_id, Instant::now()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_store_expiry() {
+        let store = MemoryDeliveryStore::new(Duration::from_secs(60));
+        let start = Instant::now();
+        assert!(store.insert_at("a", start));
+        assert!(store.insert_at("b", start + Duration::from_secs(30)));
+        assert!(!store.insert_at("a", start + Duration::from_secs(59)));
+        assert!(store.insert_at("a", start + Duration::from_secs(60)));
+        assert!(!store.insert_at("b", start + Duration::from_secs(61)));
+
+        let store = MemoryDeliveryStore::new(Duration::MAX);
+        assert!(store.insert_at("a", start));
+        assert!(!store.insert_at("a", start + Duration::from_secs(60)));
+    }
+}
@@ -0,0 +1,332 @@
+//! Typed payloads for common webhook events.
+//!
+//! Enabled by the `webhook-events` feature. [`WebhookEvent`] is chosen
+//! by the [`EVENT_HEADER`](super::EVENT_HEADER) value, so handlers can
+//! `match` on it instead of digging through [`serde_json::Value`]s.
+//! The payload types only contain a selection of the fields GitHub
+//! sends; use [`Delivery::parse`] with your own types for others.
+//!
+//! ```
+//! use github_app_auth::webhook::events::WebhookEvent;
+//! use github_app_auth::webhook::Delivery;
+//!
+//! fn handle(delivery: &Delivery) -> Result<(), serde_json::Error> {
+//!     match delivery.to_event()? {
+//!         WebhookEvent::Push(push) => println!("pushed to {}", push.git_ref),
+//!         WebhookEvent::PullRequest(pr) => println!("PR #{} {}", pr.number, pr.action),
+//!         _ => {}
+//!     }
+//!     Ok(())
+//! }
+//! ```
+
+use super::Delivery;
+use crate::app::{Account, Installation, Repository};
+use serde::{Deserialize, Serialize};
+
+/// The installation an event is for, as included in most payloads.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct InstallationRef {
+    /// Installation ID.
+    pub id: u64,
+}
+
+/// A branch or tag and the commit it points to.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct GitRef {
+    /// Name of the branch or tag.
+    #[serde(rename = "ref")]
+    pub git_ref: String,
+
+    /// SHA of the commit.
+    pub sha: String,
+}
+
+/// A pull request.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct PullRequest {
+    /// Pull request ID.
+    pub id: u64,
+
+    /// Pull request number within the repository.
+    pub number: u64,
+
+    /// Title of the pull request.
+    pub title: String,
+
+    /// Either `"open"` or `"closed"`.
+    pub state: String,
+
+    /// URL of the pull request on GitHub.
+    pub html_url: String,
+
+    /// The branch that is proposed to be merged.
+    pub head: GitRef,
+
+    /// The branch the pull request would be merged into.
+    pub base: GitRef,
+}
+
+/// An issue.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Issue {
+    /// Issue ID.
+    pub id: u64,
+
+    /// Issue number within the repository.
+    pub number: u64,
+
+    /// Title of the issue.
+    pub title: String,
+
+    /// Either `"open"` or `"closed"`.
+    pub state: String,
+
+    /// URL of the issue on GitHub.
+    pub html_url: String,
+}
+
+/// A check suite.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct CheckSuite {
+    /// Check suite ID.
+    pub id: u64,
+
+    /// Branch the check suite ran on, if any.
+    pub head_branch: Option<String>,
+
+    /// SHA of the commit the check suite ran on.
+    pub head_sha: String,
+
+    /// Status of the check suite, such as `"completed"`.
+    pub status: Option<String>,
+
+    /// Conclusion of a completed check suite, such as `"success"`.
+    pub conclusion: Option<String>,
+}
+
+/// Payload of a `push` event.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct PushEvent {
+    /// Full name of the pushed ref, such as `refs/heads/main`.
+    #[serde(rename = "ref")]
+    pub git_ref: String,
+
+    /// SHA of the ref before the push.
+    pub before: String,
+
+    /// SHA of the ref after the push.
+    pub after: String,
+
+    /// The repository that was pushed to.
+    pub repository: Repository,
+
+    /// The account that pushed.
+    pub sender: Account,
+
+    /// The installation the event is for.
+    pub installation: Option<InstallationRef>,
+}
+
+/// Payload of a `pull_request` event.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct PullRequestEvent {
+    /// What happened, such as `"opened"` or `"synchronize"`.
+    pub action: String,
+
+    /// Pull request number within the repository.
+    pub number: u64,
+
+    /// The pull request.
+    pub pull_request: PullRequest,
+
+    /// The repository of the pull request.
+    pub repository: Repository,
+
+    /// The account that triggered the event.
+    pub sender: Account,
+
+    /// The installation the event is for.
+    pub installation: Option<InstallationRef>,
+}
+
+/// Payload of an `issues` event.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct IssuesEvent {
+    /// What happened, such as `"opened"` or `"closed"`.
+    pub action: String,
+
+    /// The issue.
+    pub issue: Issue,
+
+    /// The repository of the issue.
+    pub repository: Repository,
+
+    /// The account that triggered the event.
+    pub sender: Account,
+
+    /// The installation the event is for.
+    pub installation: Option<InstallationRef>,
+}
+
+/// Payload of a `check_suite` event.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct CheckSuiteEvent {
+    /// What happened, such as `"requested"` or `"completed"`.
+    pub action: String,
+
+    /// The check suite.
+    pub check_suite: CheckSuite,
+
+    /// The repository of the check suite.
+    pub repository: Repository,
+
+    /// The account that triggered the event.
+    pub sender: Account,
+
+    /// The installation the event is for.
+    pub installation: Option<InstallationRef>,
+}
+
+/// Payload of an `installation` event.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct InstallationEvent {
+    /// What happened, such as `"created"`, `"deleted"` or
+    /// `"suspend"`.
+    pub action: String,
+
+    /// The installation.
+    pub installation: Installation,
+
+    /// Repositories the installation has access to, if it was
+    /// installed on selected repositories.
+    #[serde(default)]
+    pub repositories: Vec<Repository>,
+
+    /// The account that triggered the event.
+    pub sender: Account,
+}
+
+/// Payload of an `installation_repositories` event.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct InstallationRepositoriesEvent {
+    /// Either `"added"` or `"removed"`.
+    pub action: String,
+
+    /// The installation.
+    pub installation: Installation,
+
+    /// Repositories that were added to the installation.
+    pub repositories_added: Vec<Repository>,
+
+    /// Repositories that were removed from the installation.
+    pub repositories_removed: Vec<Repository>,
+
+    /// The account that triggered the event.
+    pub sender: Account,
+}
+
+/// A webhook event with a typed payload.
+#[derive(Clone, Debug, PartialEq)]
+pub enum WebhookEvent {
+    /// A `push` event.
+    Push(Box<PushEvent>),
+
+    /// A `pull_request` event.
+    PullRequest(Box<PullRequestEvent>),
+
+    /// An `issues` event.
+    Issues(Box<IssuesEvent>),
+
+    /// A `check_suite` event.
+    CheckSuite(Box<CheckSuiteEvent>),
+
+    /// An `installation` event.
+    Installation(Box<InstallationEvent>),
+
+    /// An `installation_repositories` event.
+    InstallationRepositories(Box<InstallationRepositoriesEvent>),
+
+    /// Any other event, with its untyped payload.
+    Other {
+        /// Name of the event.
+        event: String,
+
+        /// The payload.
+        payload: serde_json::Value,
+    },
+}
+
+impl WebhookEvent {
+    /// Parse the payload of an event named `event`.
+    pub fn from_payload(
+        event: &str,
+        payload: &serde_json::Value,
+    ) -> Result<WebhookEvent, serde_json::Error> {
+        fn parse<T: serde::de::DeserializeOwned>(
+            payload: &serde_json::Value,
+        ) -> Result<Box<T>, serde_json::Error> {
+            T::deserialize(payload).map(Box::new)
+        }
+
+        Ok(match event {
+            "push" => WebhookEvent::Push(parse(payload)?),
+            "pull_request" => WebhookEvent::PullRequest(parse(payload)?),
+            "issues" => WebhookEvent::Issues(parse(payload)?),
+            "check_suite" => WebhookEvent::CheckSuite(parse(payload)?),
+            "installation" => WebhookEvent::Installation(parse(payload)?),
+            "installation_repositories" => {
+                WebhookEvent::InstallationRepositories(parse(payload)?)
+            }
+            _ => WebhookEvent::Other {
+                event: event.to_string(),
+                payload: payload.clone(),
+            },
+        })
+    }
+}
+
+impl Delivery {
+    /// Parse the payload according to the event name.
+    pub fn to_event(&self) -> Result<WebhookEvent, serde_json::Error> {
+        WebhookEvent::from_payload(&self.event, &self.payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_from_payload() {
+        let account = json!({"id": 1, "login": "octocat", "type": "User"});
+        let repository = json!({
+            "id": 2,
+            "name": "hello-world",
+            "full_name": "octocat/hello-world",
+            "private": false,
+        });
+        let payload = json!({
+            "ref": "refs/heads/main",
+            "before": "0000000000000000000000000000000000000000",
+            "after": "6dcb09b5b57875f334f61aebed695e2e4193db5e",
+            "repository": repository,
+            "sender": account,
+            "installation": {"id": 3, "node_id": "MDIz"},
+        });
+        let WebhookEvent::Push(push) =
+            WebhookEvent::from_payload("push", &payload).unwrap()
+        else {
+            panic!("not a push event");
+        };
+        assert_eq!(push.git_ref, "refs/heads/main");
+        assert_eq!(push.installation, Some(InstallationRef { id: 3 }));
+
+        assert!(WebhookEvent::from_payload("issues", &payload).is_err());
+        assert!(matches!(
+            WebhookEvent::from_payload("star", &payload).unwrap(),
+            WebhookEvent::Other { event, .. } if event == "star"
+        ));
+    }
+}
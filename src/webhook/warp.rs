@@ -0,0 +1,138 @@
+//! Receiving webhooks in [warp] servers.
+//!
+//! [`delivery`] is a filter that verifies the signature of a webhook
+//! and extracts the [`Delivery`]; [`payload`] additionally parses the
+//! payload into a typed struct. Bad deliveries are rejected with a
+//! [`WebhookError`], which [`recover`] turns into a `401` or `400`
+//! response. [`with_tokens`] passes a [`TokenManager`] to downstream
+//! filters.
+//!
+//! ```no_run
+//! use github_app_auth::webhook::{warp as webhook, Delivery, WebhookSecret};
+//! use github_app_auth::TokenManager;
+//! use std::sync::Arc;
+//! use warp::Filter;
+//!
+//! # fn wrapper(tokens: TokenManager) {
+//! let route = warp::path("webhook")
+//!     .and(warp::post())
+//!     .and(webhook::delivery(WebhookSecret::new("my webhook secret")))
+//!     .and(webhook::with_tokens(Arc::new(tokens)))
+//!     .then(|delivery: Delivery, tokens: Arc<TokenManager>| async move {
+//!         // ...
+//!         "ok"
+//!     })
+//!     .recover(webhook::recover);
+//! # }
+//! ```
+
+use super::{
+    Delivery, WebhookError, WebhookSecret, DELIVERY_HEADER, EVENT_HEADER,
+    SIGNATURE_HEADER,
+};
+use crate::TokenManager;
+use bytes::Bytes;
+use serde::de::DeserializeOwned;
+use std::convert::Infallible;
+use std::sync::Arc;
+use warp::filters::header;
+use warp::http::StatusCode;
+use warp::reject::{Reject, Rejection};
+use warp::{Filter, Reply};
+
+impl Reject for WebhookError {}
+
+/// Verify a webhook delivery against `secret` and extract it.
+pub fn delivery(
+    secret: WebhookSecret,
+) -> impl Filter<Extract = (Delivery,), Error = Rejection> + Clone {
+    header::optional::<String>(EVENT_HEADER)
+        .and(header::optional::<String>(DELIVERY_HEADER))
+        .and(header::optional::<String>(SIGNATURE_HEADER))
+        .and(warp::body::bytes())
+        .and_then(
+            move |event: Option<String>,
+                  delivery_id: Option<String>,
+                  signature: Option<String>,
+                  body: Bytes| {
+                let result = Delivery::verify(
+                    &secret,
+                    event.as_deref(),
+                    delivery_id.as_deref(),
+                    signature.as_deref(),
+                    &body,
+                );
+                async move { result.map_err(warp::reject::custom) }
+            },
+        )
+}
+
+/// Verify a webhook delivery against `secret` and extract the event
+/// name and the payload parsed as `T`.
+pub fn payload<T: DeserializeOwned + Send>(
+    secret: WebhookSecret,
+) -> impl Filter<Extract = (String, T), Error = Rejection> + Clone {
+    delivery(secret)
+        .and_then(|delivery: Delivery| async move {
+            match delivery.parse() {
+                Ok(payload) => Ok((delivery.event, payload)),
+                Err(err) => Err(warp::reject::custom(
+                    WebhookError::InvalidPayload(err.to_string()),
+                )),
+            }
+        })
+        .untuple_one()
+}
+
+/// Pass `tokens` to downstream filters.
+pub fn with_tokens(
+    tokens: Arc<TokenManager>,
+) -> impl Filter<Extract = (Arc<TokenManager>,), Error = Infallible> + Clone {
+    warp::any().map(move || tokens.clone())
+}
+
+/// Turn a [`WebhookError`] rejection into a response. Other rejections
+/// are passed on.
+pub async fn recover(rejection: Rejection) -> Result<impl Reply, Rejection> {
+    let Some(err) = rejection.find::<WebhookError>() else {
+        return Err(rejection);
+    };
+    let status = if err.is_unauthorized() {
+        StatusCode::UNAUTHORIZED
+    } else {
+        StatusCode::BAD_REQUEST
+    };
+    Ok(warp::reply::with_status(err.to_string(), status))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webhook::tests::{sign, SECRET};
+
+    #[tokio::test]
+    async fn test_delivery_filter() {
+        let filter = delivery(WebhookSecret::new(SECRET));
+        let body = r#"{"installation":{"id":7}}"#;
+        let request = |signature: String| {
+            warp::test::request()
+                .method("POST")
+                .header(EVENT_HEADER, "push")
+                .header(DELIVERY_HEADER, "1")
+                .header(SIGNATURE_HEADER, signature)
+                .body(body)
+        };
+
+        let delivery = request(sign(body.as_bytes()))
+            .filter(&filter)
+            .await
+            .unwrap();
+        assert_eq!(delivery.event, "push");
+        assert_eq!(delivery.installation_id, Some(7));
+
+        let response = request(sign(b"{}"))
+            .reply(&filter.map(|_| "ok").recover(recover))
+            .await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,151 @@
+//! Fixtures for testing code that uses this crate against a
+//! [wiremock](https://docs.rs/wiremock) server.
+//!
+//! Enabled by the `wiremock` feature. The responses have the shapes
+//! GitHub sends, so tests don't have to copy its JSON:
+//!
+//! ```no_run
+//! use github_app_auth::wiremock::{access_token_request, token_created};
+//! use github_app_auth::{Endpoints, GithubAuthParams, InstallationAccessToken};
+//! use wiremock::MockServer;
+//!
+//! # async fn wrapper(private_key: Vec<u8>) {
+//! let server = MockServer::start().await;
+//! access_token_request(5678)
+//!     .respond_with(token_created("ghs_test"))
+//!     .mount(&server)
+//!     .await;
+//! let params = GithubAuthParams::builder()
+//!     .private_key(private_key)
+//!     .installation_id(5678)
+//!     .endpoints(Endpoints::from_api_url(&server.uri()))
+//!     .build();
+//! let token = InstallationAccessToken::new(params).await.unwrap();
+//! # }
+//! ```
+
+use crate::timestamp;
+use std::time::{Duration, SystemTime};
+use wiremock::matchers::{method, path};
+use wiremock::{Match, Mock, MockBuilder, Request, ResponseTemplate};
+
+/// Matches requests authenticated with an app JWT, checking only that
+/// the bearer token has the form of a JWT.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AppJwt;
+
+impl Match for AppJwt {
+    fn matches(&self, request: &Request) -> bool {
+        request
+            .headers
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|jwt| jwt.split('.').count() == 3)
+    }
+}
+
+/// A mock of `POST /app/installations/{installation_id}/access_tokens`
+/// authenticated with an app JWT. Add a response with
+/// [`MockBuilder::respond_with`].
+pub fn access_token_request(installation_id: u64) -> MockBuilder {
+    Mock::given(method("POST"))
+        .and(path(format!(
+            "/app/installations/{}/access_tokens",
+            installation_id
+        )))
+        .and(AppJwt)
+}
+
+fn error(status: u16, message: &str) -> ResponseTemplate {
+    ResponseTemplate::new(status).set_body_json(serde_json::json!({
+        "message": message,
+        "documentation_url": "https://docs.github.com/rest",
+    }))
+}
+
+/// A new installation access token, valid for an hour.
+pub fn token_created(token: &str) -> ResponseTemplate {
+    let expires_at = SystemTime::now() + Duration::from_secs(60 * 60);
+    ResponseTemplate::new(201).set_body_json(serde_json::json!({
+        "token": token,
+        "expires_at": timestamp::format(expires_at),
+        "permissions": {"contents": "read", "metadata": "read"},
+        "repository_selection": "all",
+    }))
+}
+
+/// The response to a JWT that GitHub can't verify, for example because
+/// it was signed with the wrong key.
+pub fn bad_credentials() -> ResponseTemplate {
+    error(401, "Bad credentials")
+}
+
+/// The response for an installation that doesn't exist or belongs to
+/// another app.
+pub fn installation_not_found() -> ResponseTemplate {
+    error(404, "Not Found")
+}
+
+/// The response for an installation that has been suspended.
+pub fn installation_suspended() -> ResponseTemplate {
+    error(403, "This installation has been suspended")
+}
+
+/// The response when the rate limit is exhausted, to be retried after
+/// `retry_after`.
+pub fn rate_limited(retry_after: Duration) -> ResponseTemplate {
+    let reset = SystemTime::now() + retry_after;
+    let reset = reset
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    error(429, "API rate limit exceeded")
+        .insert_header("retry-after", retry_after.as_secs().to_string())
+        .insert_header("x-ratelimit-limit", "5000")
+        .insert_header("x-ratelimit-remaining", "0")
+        .insert_header("x-ratelimit-used", "5000")
+        .insert_header("x-ratelimit-reset", reset.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        AuthError, Endpoints, GithubAuthParams, InstallationAccessToken,
+    };
+    use wiremock::MockServer;
+
+    fn params(server: &MockServer) -> GithubAuthParams {
+        GithubAuthParams {
+            private_key: include_bytes!("../testdata/private-key.pem").to_vec(),
+            installation_id: 2,
+            endpoints: Endpoints::from_api_url(&server.uri()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fixtures() {
+        let server = MockServer::start().await;
+        access_token_request(2)
+            .respond_with(token_created("ghs_test"))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        access_token_request(2)
+            .respond_with(installation_suspended())
+            .mount(&server)
+            .await;
+
+        let token =
+            InstallationAccessToken::new(params(&server)).await.unwrap();
+        assert_eq!(token.token().await.unwrap(), "ghs_test");
+        match InstallationAccessToken::new(params(&server)).await {
+            Err(AuthError::ReqwestError(err)) => {
+                assert_eq!(err.status().map(|s| s.as_u16()), Some(403))
+            }
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
+}
@@ -1,6 +1,6 @@
-use chrono::Duration;
 use github_app_auth::{GithubAuthParams, InstallationAccessToken};
 use serde::Deserialize;
+use std::time::Duration;
 use std::{env, os::unix::ffi::OsStrExt};
 
 type BoxError = Box<dyn std::error::Error>;
@@ -28,7 +28,7 @@ fn get_var_bytes(name: &str) -> Result<Vec<u8>, BoxError> {
 }
 
 async fn check_secrets(
-    token: &mut InstallationAccessToken,
+    token: &InstallationAccessToken,
 ) -> Result<(), BoxError> {
     // Format: owner/repo
     let repo = env::var("GITHUB_REPOSITORY")?;
@@ -44,7 +44,7 @@ async fn check_secrets(
 
     let resp: SecretsResponse = token
         .client
-        .get(&format!(
+        .get(format!(
             "https://api.github.com/repos/{}/actions/secrets",
             repo
         ))
@@ -74,20 +74,20 @@ async fn get_secrets() -> Result<(), BoxError> {
     let app_id = env::var("TEST_APP_ID")?.parse::<u64>()?;
     let installation_id = env::var("TEST_INSTALLATION_ID")?.parse::<u64>()?;
 
-    let mut token = InstallationAccessToken::new(GithubAuthParams {
-        user_agent: "github-app-auth-example".into(),
-        private_key,
-        app_id,
-        installation_id,
-    })
-    .await?;
+    let params = GithubAuthParams::builder()
+        .user_agent("github-app-auth-example")
+        .private_key(private_key)
+        .app_id(app_id)
+        .installation_id(installation_id)
+        .build();
+    let mut token = InstallationAccessToken::new(params).await?;
 
-    check_secrets(&mut token).await?;
+    check_secrets(&token).await?;
 
     // Set the refresh margin to a ridiculously large value to ensure
     // a refresh, then verify another request succeeds.
-    token.refresh_safety_margin = Duration::weeks(1);
-    check_secrets(&mut token).await?;
+    token.refresh_safety_margin = Duration::from_secs(7 * 24 * 60 * 60);
+    check_secrets(&token).await?;
 
     Ok(())
 }